@@ -0,0 +1,34 @@
+//! Fails inside of an `#![abort]` block within an `extern "C"` callback,
+//! which must abort rather than unwind across the boundary.
+
+use impass::fatal;
+
+
+// Only ever read through its `Debug` implementation, in the report.
+#[derive(Debug)]
+#[allow(dead_code)]
+enum EventError {
+    Unknown(i32),
+}
+
+
+fn decode(code: i32) -> Result<u8, EventError> {
+    u8::try_from(code).map_err(|_| EventError::Unknown(code))
+}
+
+extern "C" fn on_event(code: i32) -> u8 {
+    fatal! {
+        #![abort]
+        #![error(EventError)]
+        #![reason("Received an invalid event")]
+        let event: u8 = decode(code)?;
+        Ok(event)
+    }
+}
+
+
+fn main() {
+    let callback: extern "C" fn(i32) -> u8 = on_event;
+    println!("{}", callback(7));
+    println!("{}", callback(-1));
+}
@@ -0,0 +1,65 @@
+//! Fails inside of a module whose `fatal_defaults!` make its sites exit, unless
+//! they say otherwise.
+//!
+//! The failing site is selected through `DEFAULTS_SITE`: `module` for a site
+//! which exits with the module's code, `site` for one which gives its own, and
+//! `plain` for one in a child module without any defaults, which panics.
+
+use std::env;
+
+use thiserror::Error;
+
+
+#[derive(Error, Debug)]
+#[error("The journal is corrupted")]
+struct Corrupted;
+
+
+fn replay() -> Result<(), Corrupted> {
+    Err(Corrupted)
+}
+
+
+mod storage {
+    use impass::{fatal, fatal_defaults, fatal_fn};
+    use super::replay;
+
+    fatal_defaults! { exit = 4 }
+
+    #[fatal_fn(reason = "Failed to replay the journal")]
+    pub fn replay_journal() {
+        replay()?;
+        Ok(())
+    }
+
+    pub fn replay_index() {
+        fatal! {
+            #![exit(5)]
+            #![reason("Failed to replay the index")]
+            replay()?;
+            Ok(())
+        }
+    }
+
+    pub mod plain {
+        use impass::fatal;
+        use super::super::replay;
+
+        pub fn replay_cache() {
+            fatal! {
+                #![reason("Failed to replay the cache")]
+                replay()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+
+fn main() {
+    match env::var("DEFAULTS_SITE").as_deref() {
+        Ok("site")  => storage::replay_index(),
+        Ok("plain") => storage::plain::replay_cache(),
+        _           => storage::replay_journal(),
+    }
+}
@@ -0,0 +1,37 @@
+//! Fails inside of an `#![exit(...)]` block, which must exit with its code
+//! rather than panic.
+
+use impass::{fatal, fatal_fn};
+use thiserror::Error;
+
+
+#[derive(Error, Debug)]
+#[error("The input file is missing")]
+struct MissingInput;
+
+
+fn read_input() -> Result<String, MissingInput> {
+    Err(MissingInput)
+}
+
+#[fatal_fn(exit = 3, reason = "The output can't be written")]
+fn write_output(input: &str) {
+    std::fs::write("/", input)?;
+    Ok(())
+}
+
+
+fn main() {
+    // The function form is selected through the environment instead.
+    if std::env::var_os("EXIT_CODE_FORM").is_some_and(|form| form == "fn") {
+        write_output("output");
+    }
+
+    let input: String = fatal! {
+        #![exit(2)]
+        #![reason("Failed to read the input")]
+        let input: String = read_input()?;
+        Ok(input)
+    };
+    println!("{input}");
+}
@@ -0,0 +1,106 @@
+//! Counts the fatal sites compiled into an artifact, per crate.
+//!
+//! With the `audit` feature enabled, every fatal site embeds a marker static
+//! whose symbol names the crate it was expanded in. This scans an artifact
+//! (an executable, library or object file built with symbols) for those
+//! markers and prints the number of sites found for each crate:
+//!
+//! ```text
+//! impass_audit <artifact> [--deny <crate>]...
+//! ```
+//!
+//! Exits with a nonzero status if any crate passed through `--deny` contains a
+//! fatal site.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+
+/// The prefix of every marker symbol, which is followed by the crate name and
+/// the site's index, each ending in `__`.
+const MARKER_PREFIX: &str = "__impass_site__";
+
+
+/// Collects the distinct sites of every crate, from the marker symbols found
+/// anywhere in `bytes`.
+fn find_sites(bytes: &[u8]) -> BTreeMap<String, BTreeSet<usize>> {
+    let mut sites: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+    let     prefix: &[u8]                            = MARKER_PREFIX.as_bytes();
+
+    let mut start: usize = 0;
+    while let Some(offset) = bytes[start..].windows(prefix.len()).position(|window| window == prefix) {
+        let begin: usize = start + offset + prefix.len();
+        let end:   usize = bytes[begin..].iter()
+            .position(|byte| !(byte.is_ascii_alphanumeric() || *byte == b'_'))
+            .map_or(bytes.len(), |len| begin + len);
+
+        if let Some((krate, site)) = parse_marker(&String::from_utf8_lossy(&bytes[begin..end])) {
+            sites.entry(krate).or_default().insert(site);
+        }
+        start = end;
+    }
+    sites
+}
+
+/// Splits the remainder of a marker into its crate name and site index. As
+/// mangled symbols continue right after the marker, anything following it is
+/// ignored.
+fn parse_marker(marker: &str) -> Option<(String, usize)> {
+    marker.match_indices("__").find_map(|(index, _)| {
+        let rest:   &str = &marker[index + 2..];
+        let digits: &str = &rest[..rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len())];
+        if index == 0 || digits.is_empty() || !rest[digits.len()..].starts_with("__") {
+            return None;
+        }
+        Some((marker[..index].to_string(), digits.parse().ok()?))
+    })
+}
+
+
+fn main() -> ExitCode {
+    let mut args:     env::Args      = env::args();
+    let     program:  String         = args.next().unwrap_or_else(|| String::from("impass_audit"));
+    let mut artifact: Option<String> = None;
+    let mut denied:   Vec<String>    = Vec::new();
+    let mut valid:    bool           = true;
+    while let Some(arg) = args.next() {
+        if arg == "--deny" {
+            match args.next() {
+                Some(krate) => denied.push(krate.replace('-', "_")),
+                None        => valid = false,
+            }
+        } else if artifact.is_none() {
+            artifact = Some(arg);
+        } else {
+            valid = false;
+        }
+    }
+
+    let artifact: String = match artifact {
+        Some(artifact) if valid => artifact,
+        _                       => {
+            eprintln!("usage: {program} <artifact> [--deny <crate>]...");
+            return ExitCode::from(2);
+        },
+    };
+    let bytes: Vec<u8> = match fs::read(&artifact) {
+        Ok(bytes) => bytes,
+        Err(e)    => {
+            eprintln!("failed to read `{artifact}`: {e}");
+            return ExitCode::from(2);
+        },
+    };
+
+    let sites:      BTreeMap<String, BTreeSet<usize>> = find_sites(&bytes);
+    let mut failed: bool                              = false;
+    for (krate, sites) in &sites {
+        println!("{krate}: {}", sites.len());
+        if denied.contains(krate) {
+            eprintln!("error: the crate `{krate}` is denied fatal sites, but contains {}", sites.len());
+            failed = true;
+        }
+    }
+    if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
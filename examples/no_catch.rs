@@ -0,0 +1,28 @@
+//! Fails inside of a `#![no_catch]` block run under `catch_unwind`, which must
+//! not be able to swallow it.
+
+use std::panic;
+
+use impass::fatal;
+use thiserror::Error;
+
+
+#[derive(Error, Debug)]
+#[error("The plugin state is corrupted")]
+struct CorruptedState;
+
+
+fn check_state() -> Result<(), CorruptedState> {
+    Err(CorruptedState)
+}
+
+
+fn main() {
+    let result = panic::catch_unwind(|| fatal! {
+        #![no_catch]
+        #![reason("The plugin host cannot continue")]
+        check_state()?;
+        Ok(())
+    });
+    println!("Caught: {}", result.is_err());
+}
@@ -0,0 +1,24 @@
+//! Fails inside of a `fatal!` block, terminating through the default panic.
+
+use impass::fatal;
+use thiserror::Error;
+
+
+#[derive(Error, Debug)]
+#[error("The configuration file is missing")]
+struct MissingConfig;
+
+
+fn load_config() -> Result<String, MissingConfig> {
+    Err(MissingConfig)
+}
+
+
+fn main() {
+    let config: String = fatal! {
+        #![reason("Failed to load the configuration")]
+        let config: String = load_config()?;
+        Ok(config)
+    };
+    println!("Loaded: {config}");
+}
@@ -0,0 +1,31 @@
+//! Fails with the same attributes either within a `fatal!` block or through
+//! the `Fatal` builder, which must report them identically.
+
+use impass::{fatal, Fatal};
+use thiserror::Error;
+
+
+#[derive(Error, Debug)]
+#[error("The last worker hung up")]
+struct PoolCollapsed;
+
+
+fn join_workers() -> Result<(), PoolCollapsed> {
+    Err(PoolCollapsed)
+}
+
+
+fn main() {
+    // The builder is selected through the environment instead.
+    if std::env::var_os("RAISE_FORM").is_some_and(|form| form == "builder") {
+        Fatal::new(PoolCollapsed.into()).reason("The worker pool collapsed").code("E0070").exit_code(70).raise();
+    }
+
+    fatal! {
+        #![reason("The worker pool collapsed")]
+        #![code("E0070")]
+        #![exit(70)]
+        join_workers()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Fails mid-transaction inside of a block which would exit or abort, while an
+//! unwind-required guard may be alive. The transaction is only rolled back if
+//! the block unwinds.
+//!
+//! The kind of block is selected through `ROLLBACK_MODE` (`exit`, `abort` or
+//! `no_catch`), and the guard is registered if `ROLLBACK_GUARD` is set to `1`.
+
+use std::env;
+
+use impass::{fatal, UnwindRequiredGuard};
+use thiserror::Error;
+
+
+#[derive(Error, Debug)]
+#[error("The row is locked")]
+struct RowLocked;
+
+
+/// Rolls back when dropped, unless committed.
+struct Transaction;
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        println!("Rolled back");
+    }
+}
+
+
+fn update_row(_transaction: &Transaction) -> Result<(), RowLocked> {
+    Err(RowLocked)
+}
+
+fn run(mode: &str) {
+    let transaction: Transaction = Transaction;
+    match mode {
+        "exit" => fatal! {
+            #![exit(2)]
+            #![reason("Failed to update the row")]
+            update_row(&transaction)?;
+            Ok(())
+        },
+        "abort" => fatal! {
+            #![abort]
+            #![reason("Failed to update the row")]
+            update_row(&transaction)?;
+            Ok(())
+        },
+        _ => fatal! {
+            #![no_catch]
+            #![reason("Failed to update the row")]
+            update_row(&transaction)?;
+            Ok(())
+        },
+    }
+    std::mem::forget(transaction);
+}
+
+
+fn main() {
+    let mode:   String                      = env::var("ROLLBACK_MODE").unwrap_or_else(|_| String::from("exit"));
+    let _guard: Option<UnwindRequiredGuard> = env::var_os("ROLLBACK_GUARD")
+        .is_some_and(|guard| guard == "1")
+        .then(impass::register_unwind_required_guard);
+    run(&mode);
+}
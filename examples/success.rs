@@ -0,0 +1,13 @@
+//! Runs a `fatal!` block which succeeds, exiting normally.
+
+use impass::fatal;
+
+
+fn main() {
+    let port: u16 = fatal! {
+        #![reason("The default port is invalid")]
+        let port: u16 = "8080".parse()?;
+        Ok(port)
+    };
+    println!("Listening on port {port}");
+}
@@ -0,0 +1,24 @@
+//! Fails with a report full of characters beyond ASCII, which `IMPASS_ASCII=1`
+//! restricts the console output to.
+
+use impass::fatal;
+use thiserror::Error;
+
+
+#[derive(Error, Debug)]
+#[error("アップロードに失敗しました 😞 (写真 → 東京)")]
+struct UploadFailed;
+
+
+fn upload() -> Result<(), UploadFailed> {
+    Err(UploadFailed)
+}
+
+
+fn main() {
+    fatal! {
+        #![reason("Failed to sync the album “Tokyo” …")]
+        upload()?;
+        Ok(())
+    }
+}
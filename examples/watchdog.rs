@@ -0,0 +1,23 @@
+//! Runs a compaction under a watchdog, which stalls after its second level
+//! unless `WATCHDOG_MODE` is set to `healthy`.
+
+use std::env;
+use std::thread;
+use std::time::Duration;
+
+use impass::fatal_watchdog;
+
+
+fn main() {
+    let healthy: bool  = env::var("WATCHDOG_MODE").is_ok_and(|mode| mode == "healthy");
+    let levels:  usize = fatal_watchdog!(heartbeat = hb, stall = Duration::from_millis(200), reason = "The compactor stalled", {
+        for level in 0..4 {
+            hb.beat_with(format!("compacting level {level}"));
+            if level == 2 && !healthy {
+                thread::sleep(Duration::from_secs(60));
+            }
+        }
+        4
+    });
+    println!("Compacted {levels} levels");
+}
@@ -0,0 +1,1101 @@
+//! Parsing of the attributes shared by every macro entry point.
+//!
+//! `fatal!` receives its attributes as inner attributes (`#![reason("...")]`)
+//! while `fatal_fn` receives them as arguments (`#[fatal_fn(reason = "...")]`).
+//! Both are lowered into the same [`AttrArg`] representation and validated
+//! against the single [`ATTRIBUTES`] table, so every attribute accepts the same
+//! forms regardless of the macro it is given to:
+//!
+//! - The list form: `name(value)`.
+//! - The name-value form: `name = value`.
+//!
+//! Format strings are followed by their arguments in either form, e.g.
+//! `reason("failed for {}", id)` or `reason = "failed for {}", id`.
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
+
+
+/// The macro entry points which accept attributes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum MacroKind {
+    Fatal,
+    FatalFn,
+    FatalMod,
+    FatalImpl,
+    FatalDefaults,
+}
+
+impl MacroKind {
+    fn name(self) -> &'static str {
+        match self {
+            MacroKind::Fatal         => "fatal!",
+            MacroKind::FatalFn       => "fatal_fn",
+            MacroKind::FatalMod      => "fatal_mod!",
+            MacroKind::FatalImpl     => "fatal_impl",
+            MacroKind::FatalDefaults => "fatal_defaults!",
+        }
+    }
+}
+
+
+/// The kind of value an attribute expects.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ValueKind {
+
+    /// A string literal, e.g. `reason("...")`.
+    Str,
+
+    /// An arbitrary expression, e.g. `reason_with(|| ...)`.
+    Expr,
+
+    /// No value at all, e.g. `static_init`.
+    Flag,
+
+    /// A format string followed by its arguments, e.g.
+    /// `suggest("restart {}", name)`. A lone string literal is used as-is.
+    Format,
+
+    /// A predicate followed by a format string and its arguments, e.g.
+    /// `suggest_if(|e| ..., "restart {}", name)`. Only the list form is
+    /// accepted.
+    CondFormat,
+
+    /// A type, either as-is or within a string literal, e.g. `error(MyError)`
+    /// or `error = "MyError"`.
+    Type,
+}
+
+/// Describes a known attribute.
+pub(crate) struct AttrSpec {
+    pub(crate) name:    &'static str,
+    pub(crate) value:   ValueKind,
+    pub(crate) accepts: &'static [MacroKind],
+}
+
+impl AttrSpec {
+    fn syntax(&self) -> String {
+        let placeholder: &str = match self.value {
+            ValueKind::Str        => "\"...\"",
+            ValueKind::Expr       => "<expr>",
+            ValueKind::Flag       => return format!("`{}`", self.name),
+            ValueKind::Format     => return format!("`{0}(\"...\", args...)` or `{0} = \"...\", args...`", self.name),
+            ValueKind::CondFormat => return format!("`{}(<predicate>, \"...\", args...)`", self.name),
+            ValueKind::Type       => return format!("`{0}(<type>)` or `{0} = \"<type>\"`", self.name),
+        };
+        format!("`{0}({1})` or `{0} = {1}`", self.name, placeholder)
+    }
+}
+
+/// Every attribute understood by the macros, along with the macros accepting
+/// it.
+pub(crate) const ATTRIBUTES: &[AttrSpec] = &[
+    AttrSpec { name: "reason",           value: ValueKind::Format,     accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl]                           },
+    AttrSpec { name: "reason_with",      value: ValueKind::Expr,       accepts: &[MacroKind::Fatal, MacroKind::FatalFn]                                                 },
+    AttrSpec { name: "code",             value: ValueKind::Str,        accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl]                           },
+    AttrSpec { name: "also_fallible",    value: ValueKind::Str,        accepts: &[MacroKind::FatalFn]                                                                   },
+    AttrSpec { name: "static_init",      value: ValueKind::Flag,       accepts: &[MacroKind::Fatal]                                                                     },
+    AttrSpec { name: "must_use",         value: ValueKind::Flag,       accepts: &[MacroKind::Fatal]                                                                     },
+    AttrSpec { name: "suggest",          value: ValueKind::Format,     accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl, MacroKind::FatalDefaults] },
+    AttrSpec { name: "suggest_if",       value: ValueKind::CondFormat, accepts: &[MacroKind::Fatal]                                                                     },
+    AttrSpec { name: "ensure_ok",        value: ValueKind::CondFormat, accepts: &[MacroKind::Fatal, MacroKind::FatalFn]                                                 },
+    AttrSpec { name: "error",            value: ValueKind::Type,       accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl]                           },
+    AttrSpec { name: "context",          value: ValueKind::Flag,       accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl]                           },
+    AttrSpec { name: "no_catch",         value: ValueKind::Flag,       accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl, MacroKind::FatalDefaults] },
+    AttrSpec { name: "abort",            value: ValueKind::Flag,       accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl, MacroKind::FatalDefaults] },
+    AttrSpec { name: "exit",             value: ValueKind::Expr,       accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl, MacroKind::FatalDefaults] },
+    AttrSpec { name: "deterministic",    value: ValueKind::Flag,       accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl, MacroKind::FatalDefaults] },
+    AttrSpec { name: "lint_reasons",     value: ValueKind::Flag,       accepts: &[MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalImpl]                           },
+    AttrSpec { name: "recoverable",      value: ValueKind::Flag,       accepts: &[MacroKind::Fatal]                                                                     },
+    AttrSpec { name: "recurse",          value: ValueKind::Flag,       accepts: &[MacroKind::FatalMod]                                                                  },
+    AttrSpec { name: "unwrap_signature", value: ValueKind::Flag,       accepts: &[MacroKind::FatalFn]                                                                   },
+];
+
+/// The attributes which need the `std` feature of `impass`, as there is
+/// nowhere to put what they add to the report of a `core::panic!`.
+pub(crate) const STD_ATTRIBUTES: &[&str] = &[
+    "code", "suggest", "suggest_if", "ensure_ok", "context", "no_catch", "abort", "exit", "deterministic", "recoverable",
+];
+
+
+/// A single attribute before validation, in either of the accepted forms.
+pub(crate) struct AttrArg {
+    name:  syn::Ident,
+    value: AttrValue,
+}
+
+enum AttrValue {
+
+    /// `name`
+    None,
+
+    /// `name(tokens)`
+    List(TokenStream2),
+
+    /// `name = expr`, or `name = "...", args...` for a format string.
+    NameValue(Vec<syn::Expr>),
+}
+
+impl Parse for AttrArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(AttrArg {
+            name:  input.parse()?,
+            value: input.parse()?,
+        })
+    }
+}
+
+impl Parse for AttrValue {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::Token![=]) {
+            input.parse::<syn::Token![=]>()?;
+
+            // Any arguments following the value are taken along, up until
+            // the next attribute.
+            let mut values: Vec<syn::Expr> = vec![input.parse()?];
+            while input.peek(syn::Token![,]) && !starts_attribute(input) {
+                input.parse::<syn::Token![,]>()?;
+                values.push(input.parse()?);
+            }
+            Ok(AttrValue::NameValue(values))
+        } else if input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            Ok(AttrValue::List(content.parse()?))
+        } else {
+            Ok(AttrValue::None)
+        }
+    }
+}
+
+
+/// Whether the tokens following the next comma start another attribute
+/// rather than continuing the arguments of a format string. That is the case
+/// for the name of a known attribute, and for anything of the form
+/// `name = ...`.
+fn starts_attribute(input: ParseStream) -> bool {
+    let fork = input.fork();
+    if fork.parse::<syn::Token![,]>().is_err() || fork.is_empty() {
+        return true;
+    }
+    match fork.parse::<syn::Ident>() {
+        Ok(name) => ATTRIBUTES.iter().any(|spec| name == spec.name) || (fork.peek(syn::Token![=]) && !fork.peek(syn::Token![==])),
+        Err(_)   => false,
+    }
+}
+
+
+/// Strips the invisible groups which `macro_rules!` wraps around interpolated
+/// fragments, such as `$lit:literal` or `$e:expr`, so the value underneath can
+/// be matched on.
+pub(crate) fn ungroup(expr: syn::Expr) -> syn::Expr {
+    match expr {
+        syn::Expr::Group(group) => ungroup(*group.expr),
+        other                   => other,
+    }
+}
+
+/// Strips the trailing whitespace of a message, such as a newline captured
+/// from a template, which the report would render as a blank line.
+pub(crate) fn trim_end(msg: &syn::LitStr) -> syn::LitStr {
+    syn::LitStr::new(msg.value().trim_end(), msg.span())
+}
+
+
+/// The reason attached as context to the error of a failed block.
+#[derive(Clone)]
+pub(crate) enum Reason {
+
+    /// A plain message, given through `reason("...")`.
+    Message(syn::LitStr),
+
+    /// A format string followed by its arguments, given through
+    /// `reason("...", args...)`. It is only formatted on the failure path.
+    Format(Punctuated<syn::Expr, syn::Token![,]>),
+
+    /// A closure producing the message, given through `reason_with(|| ...)`.
+    /// It is only invoked on the failure path.
+    Lazy(Box<syn::Expr>),
+
+    /// Any other expression whose value is `Display`, given through
+    /// `reason(EXPR)`, such as a constant. It is only evaluated on the failure
+    /// path.
+    Expr(Box<syn::Expr>),
+}
+
+impl Reason {
+
+    /// Builds the reason for a format string followed by its arguments, or
+    /// hands the arguments back if they don't start with a format string. A
+    /// lone string literal is used as-is.
+    pub(crate) fn from_format(mut args: Punctuated<syn::Expr, syn::Token![,]>) -> Result<Reason, Punctuated<syn::Expr, syn::Token![,]>> {
+        if !matches!(args.first(), Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(_), .. }))) {
+            return Err(args);
+        }
+        if args.len() > 1 {
+            return Ok(Reason::Format(args));
+        }
+        match args.pop().map(|pair| pair.into_value()) {
+            Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. })) => Ok(Reason::Message(lit)),
+            _                                                                  => unreachable!("the format string was just checked"),
+        }
+    }
+}
+
+impl ToTokens for Reason {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        match self {
+            Reason::Message(msg)  => trim_end(msg).to_tokens(tokens),
+            Reason::Format(args)  => quote! { ::impass::__private::render_reason(&::core::format_args!(#args)) }.to_tokens(tokens),
+            Reason::Lazy(closure) => quote! { ::impass::__private::render_reason(&(#closure)()) }.to_tokens(tokens),
+
+            // The reference is spanned to the expression, so that a value
+            // which isn't `Display` is reported there.
+            Reason::Expr(expr) => {
+                let reference: TokenStream2 = quote_spanned! { syn::spanned::Spanned::span(expr) => &(#expr) };
+                quote! { ::impass::__private::render_reason(#reference) }.to_tokens(tokens)
+            },
+        }
+    }
+}
+
+
+/// A suggestion rendered as a `try: ...` line after the report, given through
+/// `suggest("...", args...)` or `suggest_if(<predicate>, "...", args...)`.
+#[derive(Clone)]
+pub(crate) struct Suggestion {
+
+    /// A predicate called with the `&anyhow::Error` on the failure path,
+    /// deciding whether the suggestion applies.
+    pub(crate) condition: Option<syn::Expr>,
+
+    /// The format string, followed by its arguments.
+    pub(crate) message:   Punctuated<syn::Expr, syn::Token![,]>,
+}
+
+/// A check of the success value, given through
+/// `ensure_ok(<predicate>, "...", args...)`.
+#[derive(Clone)]
+pub(crate) struct Assertion {
+
+    /// A predicate called with a reference to the success value, deciding
+    /// whether it is valid.
+    pub(crate) predicate: syn::Expr,
+
+    /// The format string, followed by its arguments.
+    pub(crate) message:   Punctuated<syn::Expr, syn::Token![,]>,
+}
+
+
+/// The validated attributes of a macro invocation.
+#[derive(Clone, Default)]
+pub(crate) struct Attrs {
+    pub(crate) reason:           Option<Reason>,
+    pub(crate) code:             Option<syn::LitStr>,
+    pub(crate) also_fallible:    Option<syn::LitStr>,
+    pub(crate) error:            Option<syn::Type>,
+    pub(crate) context:          Option<Span>,
+    pub(crate) static_init:      Option<Span>,
+    pub(crate) must_use:         Option<Span>,
+    pub(crate) no_catch:         Option<Span>,
+    pub(crate) abort:            Option<Span>,
+    pub(crate) exit:             Option<syn::Expr>,
+    pub(crate) deterministic:    Option<Span>,
+    pub(crate) lint_reasons:     Option<Span>,
+    pub(crate) recoverable:      Option<Span>,
+    pub(crate) suggestions:      Vec<Suggestion>,
+    pub(crate) assertions:       Vec<Assertion>,
+    pub(crate) recurse:          Option<Span>,
+    pub(crate) unwrap_signature: Option<Span>,
+}
+
+impl Attrs {
+
+    /// Parses the inner attributes at the start of a `fatal!` block or a
+    /// `fatal_mod!` invocation.
+    pub(crate) fn parse_inner(input: ParseStream, kind: MacroKind) -> syn::Result<Self> {
+        let mut attrs: Attrs = Attrs::default();
+        while input.peek(syn::Token![#]) && input.peek2(syn::Token![!]) {
+            input.parse::<syn::Token![#]>()?;
+            input.parse::<syn::Token![!]>()?;
+
+            // The value is parsed by hand rather than as a `syn::Meta`, which
+            // wouldn't let a name-value form carry format arguments. None of
+            // our attributes has a path of more than a single identifier.
+            let content;
+            syn::bracketed!(content in input);
+            let path:   syn::Path    = content.call(syn::Path::parse_mod_style)?;
+            let tokens: TokenStream2 = content.parse()?;
+            match path.get_ident() {
+                Some(name) => attrs.apply(AttrArg { name: name.clone(), value: AttrValue::parse.parse2(tokens)? }, kind)?,
+                None       => return Err(unknown_attribute(&path, kind)),
+            }
+        }
+        attrs.check_combinations()?;
+        Ok(attrs)
+    }
+
+    /// Parses the comma-separated arguments of an attribute macro such as
+    /// `fatal_fn`.
+    pub(crate) fn parse_args(args: TokenStream2, kind: MacroKind) -> syn::Result<Self> {
+        let mut attrs: Attrs = Attrs::default();
+        for arg in Punctuated::<AttrArg, syn::Token![,]>::parse_terminated.parse2(args)? {
+            attrs.apply(arg, kind)?;
+        }
+        attrs.check_combinations()?;
+        Ok(attrs)
+    }
+
+    /// Forwards the attributes which apply to the generated block as inner
+    /// attributes of a `fatal!` invocation.
+    pub(crate) fn to_inner_attrs(&self) -> TokenStream2 {
+        let mut tokens: TokenStream2 = match &self.reason {
+            Some(Reason::Message(msg))  => quote! { #![reason(#msg)] },
+            Some(Reason::Format(args))  => quote! { #![reason(#args)] },
+            Some(Reason::Lazy(closure)) => quote! { #![reason_with(#closure)] },
+            Some(Reason::Expr(expr))    => quote! { #![reason(#expr)] },
+            None                        => TokenStream2::new(),
+        };
+        if let Some(code) = &self.code {
+            tokens.extend(quote! { #![code(#code)] });
+        }
+        if let Some(error) = &self.error {
+            tokens.extend(quote! { #![error(#error)] });
+        }
+        if self.context.is_some() {
+            tokens.extend(quote! { #![context] });
+        }
+        if self.no_catch.is_some() {
+            tokens.extend(quote! { #![no_catch] });
+        }
+        if self.abort.is_some() {
+            tokens.extend(quote! { #![abort] });
+        }
+        if let Some(exit) = &self.exit {
+            tokens.extend(quote! { #![exit(#exit)] });
+        }
+        if self.deterministic.is_some() {
+            tokens.extend(quote! { #![deterministic] });
+        }
+        if self.lint_reasons.is_some() {
+            tokens.extend(quote! { #![lint_reasons] });
+        }
+        for Suggestion { condition, message } in &self.suggestions {
+            tokens.extend(match condition {
+                Some(condition) => quote! { #![suggest_if(#condition, #message)] },
+                None            => quote! { #![suggest(#message)] },
+            });
+        }
+        for Assertion { predicate, message } in &self.assertions {
+            tokens.extend(quote! { #![ensure_ok(#predicate, #message)] });
+        }
+        tokens
+    }
+
+    /// Validates a single argument against the table of known attributes and
+    /// records it.
+    fn apply(&mut self, arg: AttrArg, kind: MacroKind) -> syn::Result<()> {
+
+        // Unknown attributes are rejected, as dropping them would silently
+        // lose whatever they were meant to do.
+        let name: String    = arg.name.to_string();
+        let spec: &AttrSpec = match ATTRIBUTES.iter().find(|spec| spec.name == name) {
+            Some(spec) => spec,
+            None       => return Err(unknown_attribute(&arg.name, kind)),
+        };
+
+        if !spec.accepts.contains(&kind) {
+            return Err(syn::Error::new(
+                arg.name.span(),
+                format!("`{}` is not supported by `{}`", spec.name, kind.name())
+            ));
+        }
+
+        // Without `std`, a failure is nothing more than a `core::panic!`.
+        if !cfg!(feature = "std") && STD_ATTRIBUTES.contains(&spec.name) {
+            return Err(syn::Error::new(
+                arg.name.span(),
+                format!("`{}` needs the `std` feature of `impass`", spec.name)
+            ));
+        }
+
+        // Flags are only ever written as their bare name.
+        let span: Span = arg.name.span();
+        if spec.value == ValueKind::Flag {
+            if !matches!(arg.value, AttrValue::None) {
+                return Err(syn::Error::new(
+                    span,
+                    format!("`{}` does not take a value, write it as {}", spec.name, spec.syntax())
+                ));
+            }
+            match spec.name {
+                "static_init"      => self.static_init      = Some(span),
+                "must_use"         => self.must_use         = Some(span),
+                "context"          => self.context          = Some(span),
+                "no_catch"         => self.no_catch         = Some(span),
+                "abort"            => self.abort            = Some(span),
+                "deterministic"    => self.deterministic    = Some(span),
+                "lint_reasons"     => self.lint_reasons     = Some(span),
+                "recoverable"      => self.recoverable      = Some(span),
+                "recurse"          => self.recurse          = Some(span),
+                "unwrap_signature" => self.unwrap_signature = Some(span),
+                _                  => unreachable!("attribute `{}` is in the table but not handled", spec.name),
+            }
+            return Ok(());
+        }
+
+        // Format strings are followed by their arguments, so they are parsed
+        // as a list rather than as a single value.
+        if matches!(spec.value, ValueKind::Format | ValueKind::CondFormat) {
+            let args: Punctuated<syn::Expr, syn::Token![,]> = match arg.value {
+                AttrValue::None => return Err(syn::Error::new(
+                    span,
+                    format!("`{}` expects a value, written as {}", spec.name, spec.syntax())
+                )),
+                AttrValue::List(tokens) => Punctuated::parse_terminated.parse2(tokens).map_err(|e| syn::Error::new(
+                    e.span(),
+                    format!("invalid `{}` attribute, expected {}", spec.name, spec.syntax())
+                ))?,
+                AttrValue::NameValue(values) if spec.value == ValueKind::Format => values.into_iter().collect(),
+                AttrValue::NameValue(_) => return Err(syn::Error::new(
+                    span,
+                    format!("`{}` only accepts the list form, written as {}", spec.name, spec.syntax())
+                )),
+            };
+
+            let mut args:      Vec<syn::Expr>   = args.into_iter().map(ungroup).collect();
+            let     condition: Option<syn::Expr> = match spec.value {
+                ValueKind::CondFormat if !args.is_empty() => Some(args.remove(0)),
+                _                                         => None,
+            };
+            let formats: bool = matches!(args.first(), Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(_), .. })));
+
+            // A reason may be any other expression as well, on its own.
+            if spec.name == "reason" && !formats && args.len() == 1 {
+                return self.set_reason(span, Reason::Expr(Box::new(args.remove(0))));
+            }
+            if !formats {
+                return Err(syn::Error::new(
+                    span,
+                    format!("`{}` expects a format string followed by its arguments, written as {}", spec.name, spec.syntax())
+                ));
+            }
+
+            let message: Punctuated<syn::Expr, syn::Token![,]> = args.into_iter().collect();
+            match spec.name {
+                "reason" => {
+                    let reason: Reason = Reason::from_format(message)
+                        .unwrap_or_else(|_| unreachable!("the format string was just checked"));
+                    self.set_reason(span, reason)?;
+                },
+                "suggest" | "suggest_if" => self.suggestions.push(Suggestion { condition, message }),
+                "ensure_ok"              => self.assertions.push(Assertion {
+                    predicate: condition.unwrap_or_else(|| unreachable!("the predicate precedes the format string")),
+                    message,
+                }),
+                _ => unreachable!("attribute `{}` is in the table but not handled", spec.name),
+            }
+            return Ok(());
+        }
+
+        // Types can't be parsed as expressions, and are handled on their own.
+        if spec.value == ValueKind::Type {
+            let tokens: TokenStream2 = match arg.value {
+                AttrValue::None => return Err(syn::Error::new(
+                    span,
+                    format!("`{}` expects a value, written as {}", spec.name, spec.syntax())
+                )),
+                AttrValue::List(tokens)     => tokens,
+                AttrValue::NameValue(values) => {
+                    if let Some(extra) = values.get(1) {
+                        return Err(syn::Error::new_spanned(
+                            extra,
+                            format!("invalid `{}` attribute, expected {}", spec.name, spec.syntax())
+                        ));
+                    }
+                    values[0].to_token_stream()
+                },
+            };
+            self.error = Some(parse_type(spec, tokens)?);
+            return Ok(());
+        }
+
+        // Parse the value according to what the attribute expects.
+        let value: syn::Expr = match arg.value {
+            AttrValue::None => return Err(syn::Error::new(
+                arg.name.span(),
+                format!("`{}` expects a value, written as {}", spec.name, spec.syntax())
+            )),
+            AttrValue::List(tokens)    => syn::parse2(tokens).map_err(|e| syn::Error::new(
+                e.span(),
+                format!("invalid `{}` attribute, expected {}", spec.name, spec.syntax())
+            ))?,
+            AttrValue::NameValue(mut values) => {
+                if let Some(extra) = values.get(1) {
+                    return Err(syn::Error::new_spanned(
+                        extra,
+                        format!("invalid `{}` attribute, expected {}", spec.name, spec.syntax())
+                    ));
+                }
+                values.remove(0)
+            },
+        };
+        let value: syn::Expr = ungroup(value);
+
+        match spec.name {
+            "reason_with" => self.set_reason(span, Reason::Lazy(Box::new(value)))?,
+            "code" => {
+                let code: syn::LitStr = lit_str(spec, value)?;
+                let text: String      = code.value();
+                if text.is_empty() || !text.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    return Err(syn::Error::new(
+                        code.span(),
+                        "`code` may only contain ASCII letters, digits and dashes, e.g. `code(\"MYAPP-0422\")`"
+                    ));
+                }
+                self.code = Some(code);
+            },
+            "also_fallible" => self.also_fallible = Some(lit_str(spec, value)?),
+            "exit"          => self.exit          = Some(value),
+            _               => unreachable!("attribute `{}` is in the table but not handled", spec.name),
+        }
+        Ok(())
+    }
+
+    /// Rejects attributes which can't be used together.
+    fn check_combinations(&self) -> syn::Result<()> {
+
+        // A failed assertion is raised as an `anyhow::Error`, which can't be
+        // turned into an arbitrary error type.
+        if let (Some(error), Some(assertion)) = (&self.error, self.assertions.first()) {
+            let mut e: syn::Error = syn::Error::new_spanned(
+                &assertion.predicate,
+                "`ensure_ok` can't be combined with `error`, as it raises an `anyhow::Error`"
+            );
+            e.combine(syn::Error::new_spanned(error, "the error type is given here"));
+            return Err(e);
+        }
+
+        // The context is attached through `anyhow`, which the error type of
+        // the block must then be.
+        if let (Some(error), Some(context)) = (&self.error, self.context) {
+            let mut e: syn::Error = syn::Error::new(
+                context,
+                "`context` can't be combined with `error`, as it attaches the context to an `anyhow::Error`"
+            );
+            e.combine(syn::Error::new_spanned(error, "the error type is given here"));
+            return Err(e);
+        }
+
+        // An unwrapped signature already names the error type.
+        if let (Some(error), Some(unwrap)) = (&self.error, self.unwrap_signature) {
+            let mut e: syn::Error = syn::Error::new_spanned(
+                error,
+                "`error` can't be combined with `unwrap_signature`, which takes the error type from the signature"
+            );
+            e.combine(syn::Error::new(unwrap, "`unwrap_signature` is given here"));
+            return Err(e);
+        }
+
+        // A block can only terminate in one way.
+        for (name, flag) in [("no_catch", self.no_catch), ("abort", self.abort)] {
+            if let (Some(flag), Some(exit)) = (flag, &self.exit) {
+                let mut e: syn::Error = syn::Error::new_spanned(exit, format!("`exit` can't be combined with `{name}`"));
+                e.combine(syn::Error::new(flag, format!("`{name}` is given here")));
+                return Err(e);
+            }
+        }
+
+        // A recoverable block doesn't terminate at all, and attaches its
+        // reason to an `anyhow::Error`.
+        if let Some(recoverable) = self.recoverable {
+            let conflicts: [(&str, Option<Span>); 5] = [
+                ("no_catch",    self.no_catch),
+                ("abort",       self.abort),
+                ("exit",        self.exit.as_ref().map(syn::spanned::Spanned::span)),
+                ("error",       self.error.as_ref().map(syn::spanned::Spanned::span)),
+                ("static_init", self.static_init),
+            ];
+            if let Some((name, span)) = conflicts.into_iter().find_map(|(name, span)| Some((name, span?))) {
+                let mut e: syn::Error = syn::Error::new(recoverable, format!("`recoverable` can't be combined with `{name}`"));
+                e.combine(syn::Error::new(span, format!("`{name}` is given here")));
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// The style issues of a literal reason, each along with the span of the
+    /// literal, as reported through `lint_reasons`. A reason which isn't a
+    /// literal is never linted, as it is only known at runtime.
+    pub(crate) fn reason_lints(&self) -> Vec<(Span, &'static str)> {
+        if self.lint_reasons.is_none() {
+            return Vec::new();
+        }
+        let literal: &syn::LitStr = match &self.reason {
+            Some(Reason::Message(msg)) => msg,
+            Some(Reason::Format(args)) => match args.first() {
+                Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. })) => lit,
+                _                                                                  => return Vec::new(),
+            },
+            _ => return Vec::new(),
+        };
+
+        let     value: String                    = literal.value();
+        let mut lints: Vec<(Span, &'static str)> = Vec::new();
+        if value.trim_end().ends_with(['.', '!']) {
+            lints.push((literal.span(), "the reason ends with punctuation, which the report follows with its own separators"));
+        }
+        if value.contains('\n') {
+            lints.push((literal.span(), "the reason spans several lines, while the short line and the report expect a single one"));
+        }
+        lints
+    }
+
+    /// Records the reason, which may only be given once.
+    fn set_reason(&mut self, span: Span, reason: Reason) -> syn::Result<()> {
+        if self.reason.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "`reason` and `reason_with` may only be given once, and are mutually exclusive"
+            ));
+        }
+        self.reason = Some(reason);
+        Ok(())
+    }
+}
+
+/// Builds the error for an attribute which isn't ours, pointing at its path.
+///
+/// A name which is close to one of the attributes accepted by `kind`, such as
+/// `Reason` or `resaon`, is most likely a typo, and the error suggests the
+/// attribute along with how it is written. Anything else lists every
+/// attribute `kind` accepts.
+fn unknown_attribute(path: &dyn ToTokens, kind: MacroKind) -> syn::Error {
+    let name:     String            = path.to_token_stream().to_string().replace(' ', "");
+    let accepted: Vec<&AttrSpec>    = ATTRIBUTES.iter().filter(|spec| spec.accepts.contains(&kind)).collect();
+    let similar:  Option<&AttrSpec> = accepted.iter()
+        .map(|&spec| (spec, edit_distance(&name.to_lowercase(), spec.name)))
+        .filter(|(spec, distance)| *distance == 0 || (*distance <= 2 && spec.name.len() > 3))
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(spec, _)| spec);
+
+    let message: String = match similar {
+        Some(spec) => format!(
+            "unknown attribute `{name}` for `{}`; did you mean `{}`, written as {}?",
+            kind.name(), spec.name, spec.syntax()
+        ),
+        None => format!(
+            "unknown attribute `{name}` for `{}`, expected one of {}",
+            kind.name(),
+            accepted.iter().map(|spec| format!("`{}`", spec.name)).collect::<Vec<String>>().join(", ")
+        ),
+    };
+    syn::Error::new_spanned(path, message)
+}
+
+/// The number of single character insertions, deletions and substitutions
+/// turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b:            Vec<char>  = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current: Vec<usize> = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution: usize = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Unwraps the string literal expected by the attribute `spec`.
+fn lit_str(spec: &AttrSpec, value: syn::Expr) -> syn::Result<syn::LitStr> {
+    match value {
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => Ok(lit),
+        other => Err(syn::Error::new_spanned(
+            other,
+            format!("`{}` expects a string literal, written as {}", spec.name, spec.syntax())
+        )),
+    }
+}
+
+/// Parses the type expected by the attribute `spec`, which may also be given
+/// within a string literal.
+fn parse_type(spec: &AttrSpec, tokens: TokenStream2) -> syn::Result<syn::Type> {
+    if let Ok(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. })) = syn::parse2(tokens.clone()).map(ungroup) {
+        return lit.parse();
+    }
+    syn::parse2(tokens).map_err(|e| syn::Error::new(
+        e.span(),
+        format!("invalid `{}` attribute, expected {}", spec.name, spec.syntax())
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The forms an attribute can be written in, for a given value.
+    fn forms(spec: &AttrSpec) -> Vec<(&'static str, String)> {
+        let value: &str = match spec.value {
+            ValueKind::Str        => "\"message\"",
+            ValueKind::Expr       => "|| \"message\"",
+            ValueKind::Flag       => "true",
+            ValueKind::Format     => "\"message {}\", 42",
+            ValueKind::CondFormat => "|_| true, \"message {}\", 42",
+            ValueKind::Type       => "std::io::Error",
+        };
+        vec![
+            ("flag",       spec.name.to_string()),
+            ("list",       format!("{}({value})", spec.name)),
+            ("name-value", format!("{} = {value}", spec.name)),
+        ]
+    }
+
+    /// Parses `attr` as it would be given to the macro `kind`.
+    fn parse(attr: &str, kind: MacroKind) -> syn::Result<Attrs> {
+        match kind {
+            MacroKind::Fatal | MacroKind::FatalMod => parse_block(format!("#![{attr}] Ok(())").parse().unwrap(), kind),
+            MacroKind::FatalFn | MacroKind::FatalImpl | MacroKind::FatalDefaults => Attrs::parse_args(attr.parse().unwrap(), kind),
+        }
+    }
+
+    /// Parses the tokens `attr` as they would be given to the macro `kind`.
+    fn parse_tokens(attr: TokenStream2, kind: MacroKind) -> syn::Result<Attrs> {
+        match kind {
+            MacroKind::Fatal | MacroKind::FatalMod => parse_block(quote! { #![#attr] Ok(()) }, kind),
+            MacroKind::FatalFn | MacroKind::FatalImpl | MacroKind::FatalDefaults => Attrs::parse_args(attr, kind),
+        }
+    }
+
+    /// Parses the inner attributes at the start of a block.
+    fn parse_block(block: TokenStream2, kind: MacroKind) -> syn::Result<Attrs> {
+        (|input: ParseStream| {
+            let attrs: Attrs = Attrs::parse_inner(input, kind)?;
+            input.call(syn::Block::parse_within)?;
+            Ok(attrs)
+        }).parse2(block)
+    }
+
+    #[test]
+    fn every_attribute_and_form() {
+        for spec in ATTRIBUTES {
+            for kind in [MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalMod, MacroKind::FatalImpl, MacroKind::FatalDefaults] {
+                for (form, attr) in forms(spec) {
+                    let result: syn::Result<Attrs> = parse(&attr, kind);
+                    let context: String            = format!("`{attr}` ({form} form) on `{}`", kind.name());
+
+                    if spec.value == ValueKind::CondFormat && form == "name-value" {
+                        // A predicate can't be given as a value.
+                        assert!(result.is_err(), "{context} should fail");
+                    } else if !spec.accepts.contains(&kind) {
+                        let error: String = result.err().unwrap_or_else(|| panic!("{context} should fail")).to_string();
+                        assert_eq!(error, format!("`{}` is not supported by `{}`", spec.name, kind.name()), "{context}");
+                    } else if spec.value == ValueKind::Flag && form != "flag" {
+                        let error: String = result.err().unwrap_or_else(|| panic!("{context} should fail")).to_string();
+                        assert!(error.starts_with(&format!("`{}` does not take a value", spec.name)), "{context}: {error}");
+                    } else if spec.value != ValueKind::Flag && form == "flag" {
+                        let error: String = result.err().unwrap_or_else(|| panic!("{context} should fail")).to_string();
+                        assert!(error.starts_with(&format!("`{}` expects a value", spec.name)), "{context}: {error}");
+                    } else {
+                        assert!(result.is_ok(), "{context} should parse: {}", result.err().unwrap());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn string_attributes_reject_expressions() {
+        for spec in ATTRIBUTES.iter().filter(|spec| spec.value == ValueKind::Str) {
+            for &kind in spec.accepts {
+                for attr in [format!("{}(1 + 1)", spec.name), format!("{} = some_ident", spec.name)] {
+                    let error: String = parse(&attr, kind).err()
+                        .unwrap_or_else(|| panic!("`{attr}` should fail on `{}`", kind.name()))
+                        .to_string();
+                    assert!(error.contains("expects a string literal"), "`{attr}`: {error}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn list_form_rejects_trailing_tokens() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            let error: String = parse("reason(\"message\" extra)", kind).err().unwrap().to_string();
+            assert!(error.starts_with("invalid `reason` attribute"), "{error}");
+        }
+    }
+
+    #[test]
+    fn reasons_are_exclusive() {
+        let error: String = parse("reason(\"a\")] #![reason_with(|| \"b\")", MacroKind::Fatal).err().unwrap().to_string();
+        assert!(error.contains("mutually exclusive"), "{error}");
+
+        let error: String = parse("reason = \"a\", reason = \"b\"", MacroKind::FatalFn).err().unwrap().to_string();
+        assert!(error.contains("only be given once"), "{error}");
+
+        let error: String = parse("reason = \"a\", reason_with = || \"b\"", MacroKind::FatalFn).err().unwrap().to_string();
+        assert!(error.contains("mutually exclusive"), "{error}");
+    }
+
+    #[test]
+    fn codes_are_validated() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            for valid in ["E0422", "MYAPP-0422", "abc"] {
+                let attrs: Attrs = parse(&format!("code(\"{valid}\")"), kind).unwrap();
+                assert_eq!(attrs.code.unwrap().value(), valid);
+            }
+            for invalid in ["", "MYAPP 0422", "E_0422", "E0422!"] {
+                let error: String = parse(&format!("code(\"{invalid}\")"), kind).err()
+                    .unwrap_or_else(|| panic!("`{invalid}` should be rejected"))
+                    .to_string();
+                assert!(error.starts_with("`code` may only contain"), "{error}");
+            }
+        }
+    }
+
+    #[test]
+    fn invisible_groups_are_unwrapped() {
+        use proc_macro2::{Delimiter, Group, Literal, TokenTree};
+
+        // This is how `macro_rules!` passes along a `$lit:literal` fragment.
+        let grouped: TokenStream2 = TokenTree::Group(Group::new(
+            Delimiter::None,
+            TokenTree::Literal(Literal::string("message")).into()
+        )).into();
+
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            for attr in [quote! { reason(#grouped) }, quote! { reason = #grouped }] {
+                let attrs: Attrs = parse_tokens(attr.clone(), kind).unwrap_or_else(|e| panic!("`{attr}`: {e}"));
+                assert!(matches!(attrs.reason, Some(Reason::Message(msg)) if msg.value() == "message"));
+            }
+        }
+    }
+
+    #[test]
+    fn suggestions_take_format_args() {
+        for (kind, attr) in [
+            (MacroKind::Fatal,   "suggest = \"restart\"] #![suggest(\"raise the limit to {}\", 1024)"),
+            (MacroKind::FatalFn, "suggest = \"restart\", suggest(\"raise the limit to {}\", 1024)"),
+        ] {
+            let attrs: Attrs = parse(attr, kind).unwrap();
+            assert_eq!(attrs.suggestions.len(), 2);
+            assert!(attrs.suggestions.iter().all(|suggestion| suggestion.condition.is_none()));
+            assert_eq!(attrs.suggestions[1].message.len(), 2);
+
+            let error: String = parse("suggest(limit)", kind).err().unwrap().to_string();
+            assert!(error.starts_with("`suggest` expects a format string"), "{error}");
+        }
+
+        let attrs: Attrs = parse("suggest_if(|e| e.is::<std::io::Error>(), \"run as {}\", user)", MacroKind::Fatal).unwrap();
+        assert!(attrs.suggestions[0].condition.is_some());
+        assert_eq!(attrs.suggestions[0].message.len(), 2);
+
+        let error: String = parse("suggest_if(|_| true)", MacroKind::Fatal).err().unwrap().to_string();
+        assert!(error.starts_with("`suggest_if` expects a format string"), "{error}");
+    }
+
+    #[test]
+    fn reasons_take_format_args() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            for attr in ["reason(\"failed for {}\", id)", "reason = \"failed for {}\", id"] {
+                let attrs: Attrs = parse(attr, kind).unwrap_or_else(|e| panic!("`{attr}`: {e}"));
+                assert!(matches!(attrs.reason, Some(Reason::Format(args)) if args.len() == 2), "`{attr}`");
+            }
+
+            let error: String = parse("reason(name, id)", kind).err().unwrap().to_string();
+            assert!(error.starts_with("`reason` expects a format string"), "{error}");
+        }
+
+        // The arguments of the name-value form end at the next attribute.
+        let attrs: Attrs = parse("reason = \"failed for {} {}\", id, a == b, code = \"E1\", no_catch", MacroKind::FatalFn).unwrap();
+        assert!(matches!(attrs.reason, Some(Reason::Format(args)) if args.len() == 3));
+        assert!(attrs.code.is_some() && attrs.no_catch.is_some());
+    }
+
+    #[test]
+    fn reasons_take_expressions() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            for attr in ["reason(INIT_FAIL)", "reason = INIT_FAIL", "reason(format!(\"worker {id}\"))", "reason = self.name()"] {
+                let attrs: Attrs = parse(attr, kind).unwrap_or_else(|e| panic!("`{attr}`: {e}"));
+                assert!(matches!(attrs.reason, Some(Reason::Expr(_))), "`{attr}`");
+            }
+        }
+
+        // A literal is still a plain message.
+        let attrs: Attrs = parse("reason = \"message\"", MacroKind::FatalFn).unwrap();
+        assert!(matches!(attrs.reason, Some(Reason::Message(msg)) if msg.value() == "message"));
+    }
+
+    #[test]
+    fn reasons_take_closures() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            for attr in ["reason_with(|| \"message\")", "reason_with = move || format!(\"worker {id}\")"] {
+                let attrs: Attrs = parse(attr, kind).unwrap_or_else(|e| panic!("`{attr}`: {e}"));
+                assert!(matches!(attrs.reason, Some(Reason::Lazy(_))), "`{attr}`");
+            }
+        }
+    }
+
+    #[test]
+    fn reasons_are_linted() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            for (reason, count) in [
+                ("reason(\"Failed to start\")", 0),
+                ("reason(\"Failed to start.\")", 1),
+                ("reason(\"Failed to start!  \")", 1),
+                ("reason(\"Failed to start\\nfor good\")", 1),
+                ("reason(\"Failed to start.\\n\")", 2),
+                ("reason(\"Failed for {}!\", id)", 1),
+                ("reason(INIT_FAIL)", 0),
+            ] {
+                let linted: String = match kind {
+                    MacroKind::Fatal => format!("lint_reasons] #![{reason}"),
+                    _                => format!("lint_reasons, {reason}"),
+                };
+                let attrs: Attrs = parse(&linted, kind).unwrap_or_else(|e| panic!("`{linted}`: {e}"));
+                assert_eq!(attrs.reason_lints().len(), count, "`{linted}`");
+
+                // Without the flag, any reason is left as it is.
+                let attrs: Attrs = parse(reason, kind).unwrap();
+                assert!(attrs.reason_lints().is_empty(), "`{reason}`");
+            }
+        }
+    }
+
+    #[test]
+    fn assertions_keep_their_order() {
+        for (kind, attr) in [
+            (MacroKind::Fatal,   "ensure_ok(|v| *v > 0, \"positive\")] #![ensure_ok(|v| *v < 10, \"below {}\", 10)"),
+            (MacroKind::FatalFn, "ensure_ok(|v| *v > 0, \"positive\"), ensure_ok(|v| *v < 10, \"below {}\", 10)"),
+        ] {
+            let attrs: Attrs      = parse(attr, kind).unwrap();
+            let lens:  Vec<usize> = attrs.assertions.iter().map(|assertion| assertion.message.len()).collect();
+            assert_eq!(lens, [1, 2]);
+
+            let error: String = parse("ensure_ok(|v| *v > 0)", kind).err().unwrap().to_string();
+            assert!(error.starts_with("`ensure_ok` expects a format string"), "{error}");
+        }
+    }
+
+    #[test]
+    fn error_types_are_parsed() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            for attr in ["error(Vec<u8>)", "error(\"Vec<u8>\")", "error = \"Vec<u8>\""] {
+                let attrs: Attrs = parse(attr, kind).unwrap_or_else(|e| panic!("`{attr}`: {e}"));
+                assert_eq!(attrs.error.to_token_stream().to_string(), "Vec < u8 >", "`{attr}`");
+            }
+
+            let error: String = parse("error(\"not a type\")", kind).err().unwrap().to_string();
+            assert!(error.starts_with("unexpected token"), "{error}");
+        }
+    }
+
+    #[test]
+    fn error_types_exclude_assertions() {
+        for (kind, attr) in [
+            (MacroKind::Fatal,   "error(MyError)] #![ensure_ok(|_| true, \"message\")"),
+            (MacroKind::FatalFn, "ensure_ok(|_| true, \"message\"), error = \"MyError\""),
+        ] {
+            let error: String = parse(attr, kind).err().unwrap().to_string();
+            assert!(error.starts_with("`ensure_ok` can't be combined with `error`"), "{error}");
+        }
+    }
+
+    #[test]
+    fn exit_codes_are_parsed() {
+        let attrs: Attrs = parse("exit = 2, reason = \"failed\"", MacroKind::FatalFn).unwrap();
+        assert_eq!(attrs.exit.unwrap().to_token_stream().to_string(), "2");
+
+        for (kind, attr) in [
+            (MacroKind::Fatal,   "exit(EXIT_CONFIG)] #![no_catch"),
+            (MacroKind::FatalFn, "no_catch, exit = 3"),
+        ] {
+            let error: String = parse(attr, kind).err().unwrap().to_string();
+            assert!(error.starts_with("`exit` can't be combined with `no_catch`"), "{error}");
+        }
+
+        let error: String = parse("abort, exit = 3", MacroKind::FatalFn).err().unwrap().to_string();
+        assert!(error.starts_with("`exit` can't be combined with `abort`"), "{error}");
+    }
+
+    #[test]
+    fn context_excludes_error() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            let attr:  &str   = if kind == MacroKind::Fatal { "context] #![error(MyError)" } else { "context, error = \"MyError\"" };
+            let error: String = parse(attr, kind).err().unwrap().to_string();
+            assert!(error.starts_with("`context` can't be combined with `error`"), "{error}");
+        }
+    }
+
+    #[test]
+    fn unwrap_signature_excludes_error() {
+        let attrs: Attrs = parse("unwrap_signature, reason = \"failed\"", MacroKind::FatalFn).unwrap();
+        assert!(attrs.unwrap_signature.is_some());
+
+        let error: String = parse("unwrap_signature, error = \"MyError\"", MacroKind::FatalFn).err().unwrap().to_string();
+        assert!(error.starts_with("`error` can't be combined with `unwrap_signature`"), "{error}");
+    }
+
+    #[test]
+    fn recoverable_excludes_termination() {
+        assert!(parse("recoverable] #![reason(\"failed\")", MacroKind::Fatal).unwrap().recoverable.is_some());
+        for name in ["no_catch", "abort", "exit(2)", "error(MyError)", "static_init"] {
+            let error: String = parse(&format!("recoverable] #![{name}"), MacroKind::Fatal).err().unwrap().to_string();
+            let name:  &str   = name.split('(').next().unwrap();
+            assert!(error.starts_with(&format!("`recoverable` can't be combined with `{name}`")), "{error}");
+        }
+
+        let error: String = parse("recoverable", MacroKind::FatalFn).err().unwrap().to_string();
+        assert!(error.starts_with("`recoverable` is not supported by `fatal_fn`"), "{error}");
+    }
+
+    #[test]
+    fn unknown_attributes_are_rejected() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn, MacroKind::FatalMod, MacroKind::FatalImpl, MacroKind::FatalDefaults] {
+            let error: String = parse("not_ours(\"message\")", kind).err().unwrap().to_string();
+            assert!(error.starts_with(&format!("unknown attribute `not_ours` for `{}`, expected one of", kind.name())), "{error}");
+        }
+
+        let error: String = parse("rustfmt::skip", MacroKind::Fatal).err().unwrap().to_string();
+        assert!(error.starts_with("unknown attribute `rustfmt::skip` for `fatal!`"), "{error}");
+    }
+
+    #[test]
+    fn misspelled_attributes_are_suggested() {
+        for (attr, kind) in [("resaon(\"message\")", MacroKind::Fatal), ("Reason(\"message\")", MacroKind::Fatal), ("reasons = \"message\"", MacroKind::FatalFn)] {
+            let error: String = parse(attr, kind).err().unwrap().to_string();
+            assert!(error.contains("did you mean `reason`, written as `reason(\"...\", args...)`"), "`{attr}`: {error}");
+        }
+
+        let error: String = parse("sugest(\"retry\")", MacroKind::FatalDefaults).err().unwrap().to_string();
+        assert!(error.contains("did you mean `suggest`"), "{error}");
+    }
+
+    #[test]
+    fn reason_forms() {
+        for kind in [MacroKind::Fatal, MacroKind::FatalFn] {
+            let attrs: Attrs = parse("reason = \"message\"", kind).unwrap();
+            assert!(matches!(attrs.reason, Some(Reason::Message(msg)) if msg.value() == "message"));
+
+            let error: String = parse("reason(\"message\", 1 2)", kind).err().unwrap().to_string();
+            assert!(error.starts_with("invalid `reason` attribute, expected `reason(\"...\", args...)`"), "{error}");
+        }
+    }
+
+    #[test]
+    fn defaults_exclude_termination() {
+        let error: String = parse("abort, exit = 2", MacroKind::FatalDefaults).err().unwrap().to_string();
+        assert_eq!(error, "`exit` can't be combined with `abort`");
+
+        let attrs: Attrs = parse("exit = 2, deterministic, suggest(\"retry\")", MacroKind::FatalDefaults).unwrap();
+        assert!(attrs.exit.is_some() && attrs.deterministic.is_some());
+        assert_eq!(attrs.suggestions.len(), 1);
+    }
+}
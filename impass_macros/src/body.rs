@@ -0,0 +1,951 @@
+//! Rewriting passes applied to the statements of a fatal body before it is
+//! expanded.
+//!
+//! Every macro wrapping a body funnels its statements through [`rewrite`], so
+//! statement-level attributes such as `#[ctx("...")]` behave the same way in
+//! `fatal!` blocks, `fatal_fn` functions and their fallible siblings. The same
+//! goes for `?` on an `Option`, which is only supported through [`LiftTry`].
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+
+use crate::attrs;
+
+
+/// Applies every rewriting pass to the statements of a body.
+///
+/// `?` can only be used on an `Option` if the body's error type is
+/// `anyhow::Error`, that is without an `error` attribute. `flow` says whether
+/// the body has been through [`escape`], which changes what `fatal_ok!`
+/// returns.
+pub(crate) fn rewrite(mut stmts: Vec<syn::Stmt>, attrs: &attrs::Attrs, flow: bool) -> syn::Result<Vec<syn::Stmt>> {
+
+    // The source of each `?` is taken before `#[ctx]` wraps it, whose message
+    // then reads as the outer context.
+    if attrs.context.is_some() {
+        for stmt in &mut stmts {
+            TrySource.visit_stmt_mut(stmt);
+        }
+    }
+
+    let mut stmts: Vec<syn::Stmt> = stmts.into_iter().map(apply_ctx).collect::<syn::Result<_>>()?;
+    let mut early: EarlySuccess = EarlySuccess {
+        marker: if flow { FATAL_FLOW_MARKER } else { FATAL_BODY_MARKER },
+    };
+    for stmt in &mut stmts {
+        early.visit_stmt_mut(stmt);
+    }
+
+    // The trait `?` is lifted through is only declared if there is a `?` to
+    // lift, so a body without one expands to exactly what it was.
+    if attrs.error.is_some() {
+        return Ok(stmts);
+    }
+    let mut lift: LiftTry = LiftTry { found: 0 };
+    for stmt in &mut stmts {
+        lift.visit_stmt_mut(stmt);
+    }
+    if lift.found > 0 {
+        stmts.splice(0..0, LiftTry::items());
+    }
+    Ok(stmts)
+}
+
+
+/// Rejects a body which syntactically can't end in a `Result`, rather than
+/// leaving it to fail with errors about the closure it is expanded into.
+///
+/// Only the shapes which are wrong whatever the types are caught: an empty
+/// body, a body ending in a `let` or an item, and one ending in an expression
+/// which always evaluates to `()`. The latter are assignments, `for` and
+/// `while` loops, an `if` without an `else`, and an expression discarded by a
+/// trailing semicolon whose type can't be `!`. Anything else, such as
+/// `continue;` or a statement macro like `fatal_ok!(value);`, is left to the
+/// type checker. `span` locates an empty body.
+pub(crate) fn check_tail(stmts: &[syn::Stmt], span: proc_macro2::Span) -> syn::Result<()> {
+    const MESSAGE: &str = "`fatal!` block must end with an expression of type `Result`";
+
+    let Some(last) = stmts.last() else {
+        return Err(syn::Error::new(span, format!("{MESSAGE}, but it is empty; did you mean to add `Ok(())`?")));
+    };
+    let discarded: bool = match last {
+        syn::Stmt::Local(_) | syn::Stmt::Item(_) => true,
+        syn::Stmt::Macro(_)                      => false,
+        syn::Stmt::Expr(expr, semi)              => {
+            if semi.is_some() && matches!(expr, syn::Expr::Call(call) if is_variant(&call.func, &["Ok", "Err"])) {
+                return Err(syn::Error::new_spanned(
+                    last,
+                    format!("{MESSAGE}, but this one is discarded by its semicolon; did you mean to remove the `;`?")
+                ));
+            }
+            is_unit(expr) || (semi.is_some() && !may_diverge(expr))
+        },
+    };
+    if discarded {
+        return Err(syn::Error::new_spanned(last, format!("{MESSAGE}; did you mean to add `Ok(...)`?")));
+    }
+    Ok(())
+}
+
+/// Whether an expression always evaluates to `()`.
+fn is_unit(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Assign(_) | syn::Expr::ForLoop(_) | syn::Expr::While(_) => true,
+        syn::Expr::If(expr_if)                                             => expr_if.else_branch.is_none(),
+        syn::Expr::Binary(binary)                                          => matches!(
+            binary.op,
+            syn::BinOp::AddAssign(_) | syn::BinOp::SubAssign(_) | syn::BinOp::MulAssign(_) | syn::BinOp::DivAssign(_)
+                | syn::BinOp::RemAssign(_) | syn::BinOp::BitXorAssign(_) | syn::BinOp::BitAndAssign(_)
+                | syn::BinOp::BitOrAssign(_) | syn::BinOp::ShlAssign(_) | syn::BinOp::ShrAssign(_)
+        ),
+        _ => false,
+    }
+}
+
+/// Whether an expression may be of type `!`, which makes discarding it fine.
+/// Only `?`, literals and the expressions which always evaluate to `()` are
+/// known not to.
+fn may_diverge(expr: &syn::Expr) -> bool {
+    !matches!(expr, syn::Expr::Try(_) | syn::Expr::Lit(_)) && !is_unit(expr)
+}
+
+/// Whether `func` is a path to one of the enum variants `names`, as in
+/// `Ok(...)` or `Result::Ok(...)`.
+fn is_variant(func: &syn::Expr, names: &[&str]) -> bool {
+    matches!(func, syn::Expr::Path(path) if path.path.segments.last().is_some_and(|segment| names.iter().any(|name| segment.ident == name)))
+}
+
+
+/// Consumes a `#[ctx("...", args...)]` attribute on a statement, attaching
+/// the formatted message as context to the errors of every `?` within it.
+fn apply_ctx(mut stmt: syn::Stmt) -> syn::Result<syn::Stmt> {
+
+    // Take the `ctx` attribute out of the statement, so it never reaches rustc.
+    let attrs: &mut Vec<syn::Attribute> = match stmt_attrs_mut(&mut stmt) {
+        Some(attrs) => attrs,
+        None        => return Ok(stmt),
+    };
+    let (ctx, rest): (Vec<syn::Attribute>, Vec<syn::Attribute>) = std::mem::take(attrs)
+        .into_iter()
+        .partition(|attr| attr.path().is_ident("ctx"));
+    *attrs = rest;
+
+    let attr: syn::Attribute = match ctx.len() {
+        0 => return Ok(stmt),
+        1 => ctx.into_iter().next().unwrap(),
+        _ => return Err(syn::Error::new_spanned(&ctx[1], "a statement may only have a single `ctx` attribute")),
+    };
+
+    // The context is rendered into a `String`, which only `std` provides.
+    if !cfg!(feature = "std") {
+        return Err(syn::Error::new_spanned(&attr, "`ctx` needs the `std` feature of `impass`"));
+    }
+
+    // The arguments are handed to `format!` as-is, but must start with the
+    // format string.
+    let mut args: Punctuated<syn::Expr, syn::Token![,]> = attr.parse_args_with(Punctuated::parse_terminated)?;
+    if let Some(format) = args.first_mut() {
+        *format = attrs::ungroup(format.clone());
+    }
+    if !matches!(args.first(), Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(_), .. }))) {
+        return Err(syn::Error::new_spanned(
+            &attr,
+            "`ctx` expects a format string followed by its arguments, e.g. `#[ctx(\"loading {}\", path)]`"
+        ));
+    }
+
+    let mut visitor: TryContext = TryContext {
+        context: quote! { || ::impass::__private::render_guarded(&::core::format_args!(#args)) },
+        found:   0,
+    };
+    visitor.visit_stmt_mut(&mut stmt);
+
+    if visitor.found == 0 {
+        return Err(syn::Error::new_spanned(
+            &attr,
+            "`ctx` can only be applied to a statement which uses the `?` operator"
+        ));
+    }
+    Ok(stmt)
+}
+
+/// Wraps the operand of every `?` with `anyhow::Context::with_context`.
+///
+/// Closures, async blocks and nested items are not descended into, as any `?`
+/// within them propagates somewhere else entirely.
+struct TryContext {
+    context: TokenStream2,
+    found:   usize,
+}
+
+impl VisitMut for TryContext {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        match expr {
+            syn::Expr::Closure(_) | syn::Expr::Async(_) | syn::Expr::TryBlock(_) => {},
+            syn::Expr::Try(try_expr) => {
+                visit_mut::visit_expr_mut(self, &mut try_expr.expr);
+
+                let operand: &syn::Expr    = &try_expr.expr;
+                let context: &TokenStream2 = &self.context;
+                *try_expr.expr = syn::parse_quote! {
+                    ::impass::__private::anyhow::Context::with_context(#operand, #context)
+                };
+                self.found += 1;
+            },
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+/// Wraps the operand of every `?` with `anyhow::Context::with_context`, naming
+/// the operand through its stringified source, as asked by `#![context]`.
+///
+/// The source is taken before the operand itself is visited, so that it reads
+/// just as it was written. As with `TryContext`, closures, async blocks and
+/// nested items are not descended into.
+struct TrySource;
+
+impl VisitMut for TrySource {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        match expr {
+            syn::Expr::Closure(_) | syn::Expr::Async(_) | syn::Expr::TryBlock(_) => {},
+            syn::Expr::Try(try_expr) => {
+                let source: String = Source::render(try_expr.expr.to_token_stream());
+                visit_mut::visit_expr_mut(self, &mut try_expr.expr);
+
+                let operand: &syn::Expr = &try_expr.expr;
+                *try_expr.expr = syn::parse_quote! {
+                    ::impass::__private::anyhow::Context::with_context(
+                        #operand,
+                        || ::impass::__private::Evaluating(#source)
+                    )
+                };
+            },
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+/// Renders tokens back into source the way they are usually written, e.g.
+/// `text.trim().parse::<u16>()`, which `stringify!` would space out as
+/// `text.trim().parse :: < u16 > ()` once they went through a macro.
+///
+/// Only the spacing of expressions is known: no space around `.`, `::` and
+/// the angle brackets of generics, none before `?`, `,`, `;` and `:` or the
+/// arguments of a call, none within the pipes of a closure and none after a
+/// unary operator. Anything else is separated by a single space. Generics are
+/// told apart from comparisons by following `::` or a `CamelCase` name.
+#[derive(Default)]
+struct Source {
+    text:     String,
+    previous: Previous,
+    generics: usize,
+    is_type:  bool,
+    closure:  bool,
+}
+
+/// What was last rendered by `Source`, which decides the spacing of what
+/// follows.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Previous {
+
+    /// Nothing, at the start of the source or of a group.
+    #[default]
+    Start,
+
+    /// Anything a call or an index may follow: a name, a literal or a group.
+    Operand,
+
+    /// A keyword, which is spaced like an operator.
+    Keyword,
+
+    /// A punctuation character which is followed by a space.
+    Spaced,
+
+    /// A punctuation character which is directly followed by what follows.
+    Joined,
+
+    /// A unary operator, which may be followed by another one.
+    Unary,
+
+    /// The `!` of a macro invocation.
+    Bang,
+}
+
+/// The keywords an operator or group may follow without being applied to
+/// them.
+const KEYWORDS: &[&str] = &["as", "break", "else", "for", "if", "in", "let", "loop", "match", "move", "mut", "ref", "return", "while"];
+
+impl Source {
+
+    /// Renders `tokens`.
+    fn render(tokens: TokenStream2) -> String {
+        let mut source: Source = Source::default();
+        source.push(tokens);
+        source.text
+    }
+
+    fn push(&mut self, tokens: TokenStream2) {
+        use proc_macro2::{Delimiter, Spacing, TokenTree};
+
+        for token in tokens {
+            let after_type: bool                = std::mem::take(&mut self.is_type) && self.previous == Previous::Operand;
+            let (spaced, next): (bool, Previous) = match &token {
+                TokenTree::Ident(ident) => {
+                    let name:    String = ident.to_string();
+                    let keyword: bool   = KEYWORDS.contains(&name.as_str());
+                    self.is_type = name.starts_with(|c: char| c.is_ascii_uppercase()) && name.contains(|c: char| c.is_ascii_lowercase());
+                    (true, if keyword { Previous::Keyword } else { Previous::Operand })
+                },
+                TokenTree::Literal(_) => (true, Previous::Operand),
+                TokenTree::Group(group) => {
+                    let call: bool = matches!(group.delimiter(), Delimiter::Parenthesis | Delimiter::Bracket)
+                        && matches!(self.previous, Previous::Operand | Previous::Bang);
+                    (!call, Previous::Operand)
+                },
+                TokenTree::Punct(punct) => {
+                    let c:     char = punct.as_char();
+                    let joint: bool = punct.spacing() == Spacing::Joint;
+                    let unary: bool = matches!(c, '&' | '*' | '-' | '!')
+                        && matches!(self.previous, Previous::Start | Previous::Keyword | Previous::Spaced | Previous::Unary);
+                    match c {
+                        '<' if after_type || (self.previous == Previous::Joined && self.text.ends_with("::")) => {
+                            self.generics += 1;
+                            (false, Previous::Joined)
+                        },
+                        '>' if self.generics > 0 => {
+                            self.generics -= 1;
+                            (false, Previous::Operand)
+                        },
+                        '|' if self.closure => {
+                            self.closure = false;
+                            (false, Previous::Spaced)
+                        },
+                        '|' if !joint && matches!(self.previous, Previous::Start | Previous::Keyword | Previous::Spaced) => {
+                            self.closure = true;
+                            (true, Previous::Joined)
+                        },
+                        '!' if !joint && self.previous == Previous::Operand => (false, Previous::Bang),
+                        ':' if joint || self.text.ends_with(':') => (false, Previous::Joined),
+                        '.'                                      => (false, Previous::Joined),
+                        '?'                                      => (false, Previous::Operand),
+                        ',' | ';' | ':'                          => (false, Previous::Spaced),
+                        _ if unary                               => (true, Previous::Unary),
+                        _ if joint                               => (true, Previous::Joined),
+                        _                                        => (true, Previous::Spaced),
+                    }
+                },
+            };
+
+            // The start of a group, the first characters of an operator and a
+            // unary operator are directly followed by what follows.
+            if spaced && !matches!(self.previous, Previous::Start | Previous::Joined | Previous::Unary) {
+                self.text.push(' ');
+            }
+            match &token {
+                TokenTree::Group(group) => {
+                    let (open, close): (&str, &str) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Bracket     => ("[", "]"),
+                        Delimiter::Brace       => ("{ ", " }"),
+                        Delimiter::None        => ("", ""),
+                    };
+                    let inner: String = Source::render(group.stream());
+                    if inner.is_empty() {
+                        self.text.push_str(open.trim_end());
+                        self.text.push_str(close.trim_start());
+                    } else {
+                        self.text.push_str(open);
+                        self.text.push_str(&inner);
+                        self.text.push_str(close);
+                    }
+                },
+                _ => self.text.push_str(&token.to_string()),
+            }
+            self.previous = next;
+        }
+    }
+}
+
+/// Routes the operand of every `?` through a trait declared within the body,
+/// which turns an `Option` into a `Result` whose error reads
+/// `value was None`, and leaves any other `Result` as it is. This lets `?` be
+/// used on both within a body returning `Result<_, anyhow::Error>`.
+///
+/// As with `TryContext`, closures, async blocks and nested items are not
+/// descended into.
+struct LiftTry {
+    found: usize,
+}
+
+/// The name of the trait declared by `LiftTry`.
+const LIFT_TRAIT: &str = "__ImpassTry";
+
+impl LiftTry {
+
+    /// The items declaring the trait, prepended to the body.
+    fn items() -> Vec<syn::Stmt> {
+        let lift: syn::Ident = syn::Ident::new(LIFT_TRAIT, proc_macro2::Span::call_site());
+        let items: syn::Block = syn::parse_quote! {{
+            trait #lift {
+                type Ok;
+                type Err;
+                fn lift(self) -> ::core::result::Result<Self::Ok, Self::Err>;
+            }
+            impl<T, E> #lift for ::core::result::Result<T, E> {
+                type Ok  = T;
+                type Err = E;
+                #[inline(always)]
+                fn lift(self) -> ::core::result::Result<T, E> {
+                    self
+                }
+            }
+            impl<T> #lift for ::core::option::Option<T> {
+                type Ok  = T;
+                type Err = ::impass::__private::anyhow::Error;
+                #[inline(always)]
+                fn lift(self) -> ::core::result::Result<T, ::impass::__private::anyhow::Error> {
+                    ::core::option::Option::ok_or_else(self, || ::impass::__private::anyhow::Error::msg("value was None"))
+                }
+            }
+        }};
+        items.stmts
+    }
+}
+
+impl VisitMut for LiftTry {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        match expr {
+            syn::Expr::Closure(_) | syn::Expr::Async(_) | syn::Expr::TryBlock(_) => {},
+            syn::Expr::Try(try_expr) => {
+                visit_mut::visit_expr_mut(self, &mut try_expr.expr);
+
+                let operand: &syn::Expr = &try_expr.expr;
+                let lift:    syn::Ident = syn::Ident::new(LIFT_TRAIT, proc_macro2::Span::call_site());
+                *try_expr.expr = syn::parse_quote! {
+                    #lift::lift(#operand)
+                };
+                self.found += 1;
+            },
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+
+/// Adapts a body made up of nothing but a call, method call, path, field
+/// access or `.await`, such as the one of `fatal!(do_thing())`, so that it may
+/// fail with any error converting into an `anyhow::Error`.
+///
+/// The value is passed through `Tail`, whose inherent method takes a `Result`
+/// whose error is an `anyhow::Error`, and whose trait method takes any other.
+/// An inherent method is picked first whenever it may apply, so an error type
+/// left to inference, as in `get().map_err(Into::into)`, is still taken to be
+/// an `anyhow::Error`, just as the block's return type would have it.
+pub(crate) fn adapt_lone_result(stmts: &mut [syn::Stmt]) {
+    let [syn::Stmt::Expr(expr, None)] = stmts else {
+        return;
+    };
+    if !matches!(
+        expr,
+        syn::Expr::Call(_) | syn::Expr::MethodCall(_) | syn::Expr::Path(_) | syn::Expr::Field(_) | syn::Expr::Await(_)
+    ) {
+        return;
+    }
+
+    let value: syn::Expr = expr.clone();
+    *expr = syn::parse_quote! {
+        {
+            use ::impass::__private::ConvertTail as _;
+            ::impass::__private::Tail(#value).into_result()
+        }
+    };
+}
+
+/// Whether a body uses `.await` anywhere it would have to be awaited by the
+/// body itself, i.e. outside of closures, async blocks and nested items.
+///
+/// The arguments of other macros can't be parsed, so their tokens are
+/// scanned for `.await` instead, skipping over any `async { ... }` block.
+pub(crate) fn uses_await(stmts: &[syn::Stmt]) -> bool {
+    let mut visitor: FindAwait = FindAwait { found: false };
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+    visitor.found
+}
+
+struct FindAwait {
+    found: bool,
+}
+
+impl FindAwait {
+    fn scan(&mut self, tokens: TokenStream2) {
+        use proc_macro2::TokenTree;
+
+        let mut after_dot:   bool = false;
+        let mut after_async: bool = false;
+        for token in tokens {
+            match &token {
+                TokenTree::Ident(ident) if after_dot && ident == "await" => self.found = true,
+                TokenTree::Group(group) if !after_async                  => self.scan(group.stream()),
+                _                                                        => {},
+            }
+            after_dot   = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '.');
+            after_async = matches!(&token, TokenTree::Ident(ident) if ident == "async" || (after_async && ident == "move"));
+        }
+    }
+}
+
+impl Visit<'_> for FindAwait {
+    fn visit_expr(&mut self, expr: &syn::Expr) {
+        match expr {
+            syn::Expr::Closure(_) | syn::Expr::Async(_) => {},
+            syn::Expr::Await(_) => self.found = true,
+            _                   => visit::visit_expr(self, expr),
+        }
+    }
+
+    fn visit_macro(&mut self, mac: &syn::Macro) {
+        self.scan(mac.tokens.clone());
+    }
+
+    fn visit_item(&mut self, _: &syn::Item) {}
+}
+
+
+/// The control flow leaving a `fatal!` block, which [`escape`] has turned into
+/// values returned from the closure wrapping the block.
+#[derive(Default)]
+pub(crate) struct Escapes {
+
+    /// Whether the block returns from the enclosing function.
+    pub(crate) returns: bool,
+
+    /// The `break` and `continue` expressions leaving the block, each of which
+    /// is returned as a `Flow::Jump` of its index.
+    pub(crate) jumps: Vec<TokenStream2>,
+}
+
+impl Escapes {
+
+    /// Whether nothing leaves the block, which then expands as it always has.
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.returns && self.jumps.is_empty()
+    }
+}
+
+/// Rewrites every `return` within a `fatal!` block, and every `break` or
+/// `continue` targeting a loop outside of it, into an early return of a
+/// `Flow` from the closure wrapping the block. The block then carries each of
+/// them out once the closure has returned, where they reach the enclosing
+/// function or loop.
+///
+/// Closures, async blocks and nested items are not descended into, as their
+/// control flow never leaves them. Neither are the arguments of other macros,
+/// which can't be parsed. A `break` leaving the block can't carry a value, as
+/// the values of different loops would need a type each.
+pub(crate) fn escape(stmts: &mut [syn::Stmt]) -> syn::Result<Escapes> {
+    let mut visitor: Escape = Escape {
+        loops:   0,
+        labels:  Vec::new(),
+        escapes: Escapes::default(),
+        error:   None,
+    };
+    for stmt in stmts.iter_mut() {
+        visitor.visit_stmt_mut(stmt);
+    }
+    match visitor.error {
+        Some(e) => Err(e),
+        None    => Ok(visitor.escapes),
+    }
+}
+
+struct Escape {
+    loops:   usize,
+    labels:  Vec<syn::Lifetime>,
+    escapes: Escapes,
+    error:   Option<syn::Error>,
+}
+
+impl Escape {
+
+    /// Whether a `break` or `continue` with `label` targets a loop outside of
+    /// the block.
+    fn leaves(&self, label: Option<&syn::Lifetime>) -> bool {
+        match label {
+            Some(label) => !self.labels.contains(label),
+            None        => self.loops == 0,
+        }
+    }
+
+    /// Visits the body of a loop or labeled block declared within the block.
+    fn within(&mut self, label: Option<&syn::Label>, looping: bool, visit: impl FnOnce(&mut Self)) {
+        let labels: usize = self.labels.len();
+        self.labels.extend(label.map(|label| label.name.clone()));
+        self.loops += usize::from(looping);
+        visit(self);
+        self.loops -= usize::from(looping);
+        self.labels.truncate(labels);
+    }
+
+    /// Records a `break` or `continue` leaving the block, returning the
+    /// expression which returns its index from the closure instead.
+    fn jump(&mut self, jump: TokenStream2) -> syn::Expr {
+        let key:   String = jump.to_string();
+        let index: usize  = match self.escapes.jumps.iter().position(|known| known.to_string() == key) {
+            Some(index) => index,
+            None        => {
+                self.escapes.jumps.push(jump);
+                self.escapes.jumps.len() - 1
+            },
+        };
+        syn::parse_quote! {
+            return ::core::result::Result::Ok(::impass::__private::Flow::Jump(#index))
+        }
+    }
+}
+
+impl VisitMut for Escape {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        match expr {
+            syn::Expr::Closure(_) | syn::Expr::Async(_) | syn::Expr::TryBlock(_) => {},
+            syn::Expr::ForLoop(for_loop) => {
+                self.visit_expr_mut(&mut for_loop.expr);
+                self.within(for_loop.label.as_ref(), true, |this| this.visit_block_mut(&mut for_loop.body));
+            },
+            syn::Expr::While(expr_while) => {
+                let label: Option<syn::Label> = expr_while.label.clone();
+                self.within(label.as_ref(), true, |this| visit_mut::visit_expr_while_mut(this, expr_while));
+            },
+            syn::Expr::Loop(expr_loop) => {
+                let label: Option<syn::Label> = expr_loop.label.clone();
+                self.within(label.as_ref(), true, |this| visit_mut::visit_expr_loop_mut(this, expr_loop));
+            },
+            syn::Expr::Block(block) if block.label.is_some() => {
+                let label: Option<syn::Label> = block.label.clone();
+                self.within(label.as_ref(), false, |this| visit_mut::visit_expr_block_mut(this, block));
+            },
+            syn::Expr::Return(ret) => {
+                if let Some(value) = &mut ret.expr {
+                    self.visit_expr_mut(value);
+                }
+                let value: TokenStream2 = match &ret.expr {
+                    Some(value) => value.to_token_stream(),
+                    None        => quote! { () },
+                };
+                *expr = syn::parse_quote_spanned! { ret.return_token.span =>
+                    return ::core::result::Result::Ok(::impass::__private::Flow::Return(#value))
+                };
+                self.escapes.returns = true;
+            },
+            syn::Expr::Break(brk) if self.leaves(brk.label.as_ref()) => {
+                if let Some(value) = &brk.expr {
+                    self.error.get_or_insert_with(|| syn::Error::new_spanned(
+                        value,
+                        "a `break` leaving a `fatal!` block can't carry a value; \
+                         assign the value to a variable declared outside of the block instead"
+                    ));
+                    return;
+                }
+                let jump: TokenStream2 = brk.to_token_stream();
+                *expr = self.jump(jump);
+            },
+            syn::Expr::Continue(cont) if self.leaves(cont.label.as_ref()) => {
+                let jump: TokenStream2 = cont.to_token_stream();
+                *expr = self.jump(jump);
+            },
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+
+/// Marks every `fatal_ok!(value)` invocation as being within a fatal body,
+/// which makes it expand to an early return of `Ok(value)` from the closure
+/// wrapping the body, or of `Ok(Flow::Value(value))` once the body has been
+/// through [`escape`].
+///
+/// The invocation itself is kept, so that it still resolves to (and uses the
+/// import of) `impass::fatal_ok`. As with `?`, closures, async blocks and
+/// nested items are not descended into, so that a `fatal_ok!` in there expands
+/// to its own compile error rather than silently returning from the wrong
+/// scope. Statements and expressions marked `#[fatal_opaque]` are skipped as
+/// well.
+struct EarlySuccess {
+    marker: &'static str,
+}
+
+/// The tokens prepended to the arguments of a `fatal_ok!` invocation within a
+/// fatal body.
+pub(crate) const FATAL_BODY_MARKER: &str = "__impass_fatal_body";
+
+/// The tokens prepended instead within a body which has been through
+/// [`escape`].
+pub(crate) const FATAL_FLOW_MARKER: &str = "__impass_fatal_flow";
+
+impl EarlySuccess {
+    fn mark(&self, mac: &mut syn::Macro) {
+        let marker: syn::Ident    = syn::Ident::new(self.marker, proc_macro2::Span::call_site());
+        let tokens: &TokenStream2 = &mac.tokens;
+        mac.tokens = quote! { @#marker #tokens };
+    }
+}
+
+impl VisitMut for EarlySuccess {
+    fn visit_stmt_mut(&mut self, stmt: &mut syn::Stmt) {
+
+        // Statement-position macros aren't expressions.
+        if let syn::Stmt::Macro(stmt_macro) = stmt {
+            if !take_opaque(&mut stmt_macro.attrs) && is_fatal_macro(&stmt_macro.mac, "fatal_ok") {
+                self.mark(&mut stmt_macro.mac);
+            }
+            return;
+        }
+        if stmt_attrs_mut(stmt).is_some_and(take_opaque) {
+            return;
+        }
+        visit_mut::visit_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        if expr_attrs_mut(expr).is_some_and(take_opaque) {
+            return;
+        }
+        match expr {
+            syn::Expr::Closure(_) | syn::Expr::Async(_) | syn::Expr::TryBlock(_) => {},
+            syn::Expr::Macro(expr_macro) if is_fatal_macro(&expr_macro.mac, "fatal_ok") => self.mark(&mut expr_macro.mac),
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+/// Whether a macro invocation is the impass macro `name`.
+///
+/// Macro paths cannot be resolved from within a proc macro, so this only
+/// looks at the last segment of the path: `fatal_ok!`, `impass::fatal_ok!`
+/// and `::impass::fatal_ok!` all match, whereas an alias such as
+/// `use impass::fatal_ok as done;` or a `macro_rules!` wrapper expanding to
+/// `fatal_ok!` does not. Anything that doesn't match is left untouched.
+fn is_fatal_macro(mac: &syn::Macro, name: &str) -> bool {
+    mac.path.segments.last().is_some_and(|segment| segment.ident == name)
+}
+
+/// Removes any `#[fatal_opaque]` attribute, returning whether there was one.
+fn take_opaque(attrs: &mut Vec<syn::Attribute>) -> bool {
+    let count: usize = attrs.len();
+    attrs.retain(|attr| !attr.path().is_ident("fatal_opaque"));
+    attrs.len() != count
+}
+
+
+/// Returns the outer attributes of a statement, if it can have any.
+fn stmt_attrs_mut(stmt: &mut syn::Stmt) -> Option<&mut Vec<syn::Attribute>> {
+    match stmt {
+        syn::Stmt::Local(local)  => Some(&mut local.attrs),
+        syn::Stmt::Expr(expr, _) => expr_attrs_mut(expr),
+        syn::Stmt::Macro(mac)    => Some(&mut mac.attrs),
+        syn::Stmt::Item(_)       => None,
+    }
+}
+
+/// Returns the outer attributes of an expression, if it can have any.
+fn expr_attrs_mut(expr: &mut syn::Expr) -> Option<&mut Vec<syn::Attribute>> {
+    use syn::Expr;
+    match expr {
+        Expr::Array(e)      => Some(&mut e.attrs),
+        Expr::Assign(e)     => Some(&mut e.attrs),
+        Expr::Async(e)      => Some(&mut e.attrs),
+        Expr::Await(e)      => Some(&mut e.attrs),
+        Expr::Binary(e)     => Some(&mut e.attrs),
+        Expr::Block(e)      => Some(&mut e.attrs),
+        Expr::Break(e)      => Some(&mut e.attrs),
+        Expr::Call(e)       => Some(&mut e.attrs),
+        Expr::Cast(e)       => Some(&mut e.attrs),
+        Expr::Closure(e)    => Some(&mut e.attrs),
+        Expr::Const(e)      => Some(&mut e.attrs),
+        Expr::Continue(e)   => Some(&mut e.attrs),
+        Expr::Field(e)      => Some(&mut e.attrs),
+        Expr::ForLoop(e)    => Some(&mut e.attrs),
+        Expr::Group(e)      => Some(&mut e.attrs),
+        Expr::If(e)         => Some(&mut e.attrs),
+        Expr::Infer(e)      => Some(&mut e.attrs),
+        Expr::Index(e)      => Some(&mut e.attrs),
+        Expr::Let(e)        => Some(&mut e.attrs),
+        Expr::Lit(e)        => Some(&mut e.attrs),
+        Expr::Loop(e)       => Some(&mut e.attrs),
+        Expr::Macro(e)      => Some(&mut e.attrs),
+        Expr::Match(e)      => Some(&mut e.attrs),
+        Expr::MethodCall(e) => Some(&mut e.attrs),
+        Expr::Paren(e)      => Some(&mut e.attrs),
+        Expr::RawAddr(e)    => Some(&mut e.attrs),
+        Expr::Path(e)       => Some(&mut e.attrs),
+        Expr::Range(e)      => Some(&mut e.attrs),
+        Expr::Reference(e)  => Some(&mut e.attrs),
+        Expr::Repeat(e)     => Some(&mut e.attrs),
+        Expr::Return(e)     => Some(&mut e.attrs),
+        Expr::Struct(e)     => Some(&mut e.attrs),
+        Expr::Try(e)        => Some(&mut e.attrs),
+        Expr::TryBlock(e)   => Some(&mut e.attrs),
+        Expr::Tuple(e)      => Some(&mut e.attrs),
+        Expr::Unary(e)      => Some(&mut e.attrs),
+        Expr::Unsafe(e)     => Some(&mut e.attrs),
+        Expr::While(e)      => Some(&mut e.attrs),
+        Expr::Yield(e)      => Some(&mut e.attrs),
+        _                   => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse::Parser;
+
+    /// Checks the tail of the body `body`, returning the error message if any.
+    fn check(body: &str) -> Option<String> {
+        let stmts: Vec<syn::Stmt> = syn::Block::parse_within.parse_str(body).unwrap();
+        check_tail(&stmts, proc_macro2::Span::call_site()).err().map(|e| e.to_string())
+    }
+
+    #[test]
+    fn result_tails_are_accepted() {
+        for body in [
+            "let value: i32 = might_fail()?; Ok(value)",
+            "Ok(())",
+            "might_fail()",
+            "if flag { Ok(1) } else { Err(anyhow!(\"failed\")) }",
+            "return Ok(1);",
+            "fatal_ok!(1);",
+            "panic!(\"failed\");",
+            "std::process::exit(1);",
+            "loop {}",
+        ] {
+            assert_eq!(check(body), None, "`{body}`");
+        }
+    }
+
+    #[test]
+    fn unit_tails_are_rejected() {
+        assert_eq!(
+            check("").unwrap(),
+            "`fatal!` block must end with an expression of type `Result`, but it is empty; did you mean to add `Ok(())`?"
+        );
+        for body in [
+            "let value: i32 = might_fail()?;",
+            "might_fail()?;",
+            "value = 1",
+            "value += 1;",
+            "for _ in 0..3 { might_fail()?; }",
+            "while flag { might_fail()?; }",
+            "if flag { return Ok(1) }",
+            "fn helper() {}",
+        ] {
+            assert_eq!(
+                check(body).as_deref(),
+                Some("`fatal!` block must end with an expression of type `Result`; did you mean to add `Ok(...)`?"),
+                "`{body}`"
+            );
+        }
+        for body in ["Ok(value);", "let value: i32 = 1; Result::Err(anyhow!(\"failed\"));"] {
+            assert_eq!(
+                check(body).as_deref(),
+                Some("`fatal!` block must end with an expression of type `Result`, but this one is discarded by its semicolon; did you mean to remove the `;`?"),
+                "`{body}`"
+            );
+        }
+    }
+
+    /// Rewrites the escaping control flow of the body `body`, returning the
+    /// jumps found along with the rewritten body.
+    fn rewrite_escapes(body: &str) -> syn::Result<(Escapes, String)> {
+        let mut stmts: Vec<syn::Stmt> = syn::Block::parse_within.parse_str(body).unwrap();
+        let escapes: Escapes = escape(&mut stmts)?;
+        Ok((escapes, quote! { #(#stmts)* }.to_string()))
+    }
+
+    #[test]
+    fn escaping_flow_is_rewritten() {
+        let (escapes, body) = rewrite_escapes("if done { continue; } if stop { break 'outer; } if last { continue; } Ok(())").unwrap();
+        assert!(!escapes.returns);
+        assert_eq!(escapes.jumps.iter().map(ToString::to_string).collect::<Vec<_>>(), ["continue", "break 'outer"]);
+        assert_eq!(body.matches("Flow :: Jump (0usize)").count(), 2);
+        assert_eq!(body.matches("Flow :: Jump (1usize)").count(), 1);
+
+        let (escapes, body) = rewrite_escapes("if done { return; } Ok(Some(value))").unwrap();
+        assert!(escapes.returns && escapes.jumps.is_empty());
+        assert!(body.contains("Flow :: Return (())"), "{body}");
+    }
+
+    #[test]
+    fn local_flow_is_kept() {
+        for body in [
+            "for value in values { if value { continue; } } Ok(())",
+            "'inner: loop { loop { break 'inner; } } Ok(())",
+            "'scope: { if done { break 'scope; } } Ok(())",
+            "let double = |value: i32| { return value * 2; }; Ok(())",
+            "let future = async { return 1; }; Ok(())",
+            "fn helper() -> i32 { return 1; } Ok(())",
+            "println!(\"{}\", { return; }); Ok(())",
+        ] {
+            assert!(rewrite_escapes(body).unwrap().0.is_empty(), "`{body}`");
+        }
+    }
+
+    #[test]
+    fn break_values_are_rejected() {
+        assert_eq!(
+            rewrite_escapes("if done { break 'outer 1; } Ok(())").err().unwrap().to_string(),
+            "a `break` leaving a `fatal!` block can't carry a value; \
+             assign the value to a variable declared outside of the block instead"
+        );
+        assert!(rewrite_escapes("let value: i32 = loop { break 1; }; Ok(value)").is_ok());
+    }
+
+    #[test]
+    fn lone_results_are_adapted() {
+        let adapt = |body: &str| {
+            let mut stmts: Vec<syn::Stmt> = syn::Block::parse_within.parse_str(body).unwrap();
+            adapt_lone_result(&mut stmts);
+            quote::quote! { #(#stmts)* }.to_string().contains("Tail")
+        };
+        for body in ["might_fail()", "get().map_err(Into::into)", "Ok(might_fail()?)", "result", "self.result", "fetch().await"] {
+            assert!(adapt(body), "`{body}`");
+        }
+        for body in ["let value: i32 = might_fail()?; Ok(value)", "might_fail();", "if flag { a() } else { b() }", "loop {}"] {
+            assert!(!adapt(body), "`{body}`");
+        }
+    }
+
+    #[test]
+    fn sources_are_rendered_as_written() {
+        for source in [
+            "read_config(path)",
+            "std::fs::read_to_string(format!(\"{dir}/port\"))",
+            "text.trim().parse::<u16>()",
+            "i8::try_from(might_fail(false)? * 10)",
+            "map.get(&key).ok_or(Error::Missing)",
+            "values.iter().map(|value| value * 2).sum::<Result<Vec<u8>, Error>>()",
+            "Vec::<u8>::with_capacity(-offset + 1)",
+            "lookup(if flag { a } else { b }, &mut cache, !done)",
+            "items[index].load(a != b, c < d, &*guard)",
+            "HashMap::<String, u32>::new()",
+        ] {
+            let expr: syn::Expr = syn::parse_str(source).unwrap();
+            assert_eq!(Source::render(expr.to_token_stream()), source);
+        }
+    }
+}
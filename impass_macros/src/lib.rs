@@ -0,0 +1,2613 @@
+//! # impass_macros
+//!
+//! The procedural macros behind `impass`, which re-exports every one of them.
+//! Depend on `impass` rather than on this crate directly: the expansions refer
+//! to `anyhow` through `::impass::__private`, so they only resolve with the
+//! facade crate in the dependency graph.
+
+extern crate proc_macro;
+
+mod attrs;
+mod body;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned, ToTokens};
+use syn::parse_macro_input;
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::parse2;
+use syn::punctuated::Punctuated;
+
+use attrs::{Attrs, MacroKind, Reason};
+
+
+/// A declarative macro for handling critical, unrecoverable errors.
+///
+/// The `fatal!` macro wraps a block of code, allowing for a clean and
+/// expressive "fail-fast" pattern. It provides a highly visible boundary for
+/// code that is expected to be infallible, and it signals a critical bug if a
+/// `Result::Err` is returned.
+///
+/// This macro is a more ergonomic and readable alternative to
+/// ```rust,ignore
+/// (|| -> Result<_, anyhow::Error> { ... })().unwrap()
+/// ```
+///
+/// ### Behavior
+///
+/// 1.  **Allows `?` Operator:** The macro wraps the provided block in a
+///     closure, enabling the use of the `?` operator for seamless error
+///     propagation.
+/// 2.  **Detailed Panic:** If the block returns an `Err`, the macro catches the
+///     error, prints a detailed error message which includes any context. For
+///     this reason it is recommended to use the `context` function from
+///     `anyhow`.
+/// 3.  **Returns a Value:** The macro returns the inner value of the `Ok`
+///     variant, allowing it to be used in assignments.
+///
+/// The block must end with an expression of type `Result`. A block which
+/// can't, such as an empty one or one ending in a `let`, is rejected with an
+/// error pointing at its last statement, and so is a trailing `Ok(...)`
+/// discarded by a semicolon:
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// let value: i32 = fatal! {
+///     let value: i32 = "42".parse()?;
+///     Ok(value);
+/// };
+/// ```
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// fatal! {
+///     let value: i32 = "42".parse()?;
+/// }
+/// ```
+///
+/// ### Expression Form
+///
+/// A single fallible call doesn't need a block: `fatal!(expr)` unwraps any
+/// `Result<T, E>` whose error converts into an `anyhow::Error`, and evaluates
+/// to the `T`. Like `expect`, `fatal!(expr, "...", args...)` additionally
+/// attaches a message, which is rendered just like a reason.
+///
+/// ```rust
+/// use impass::fatal;
+///
+/// let port:    u16 = fatal!("8080".parse::<u16>()) + 1;
+/// let workers: u8  = fatal!("4".parse(), "The worker count {} is invalid", "4");
+/// assert_eq!((port, workers), (8081, 4));
+/// ```
+///
+/// Without a message, the expression is expanded as a block made up of it
+/// alone. A lone call, method call, path, field access or `.await` may then
+/// fail with any error converting into an `anyhow::Error`, while an error type
+/// left to inference, as in `fatal!(get().map_err(Into::into))`, is taken to
+/// be an `anyhow::Error` just as within any other block.
+///
+/// ### Async Context
+///
+/// A block using `.await` is evaluated as an async block which is awaited
+/// right away, so it can be used anywhere an `.await` could be. Locals are
+/// borrowed rather than moved, just as if the statements were written inline.
+/// Awaits within closures and async blocks nested in the block don't count.
+///
+/// ```rust
+/// use impass::fatal;
+///
+/// async fn read(path: &str) -> Result<String, std::io::Error> {
+///     Ok(format!("contents of {path}"))
+/// }
+///
+/// async fn load(path: &str) -> usize {
+///     let contents: String = fatal! {
+///         #![reason("Failed to load {}", path)]
+///         let contents: String = read(path).await?;
+///         Ok(contents)
+///     };
+///     contents.len() + path.len()
+/// }
+/// ```
+///
+/// ### Control Flow
+///
+/// A `return` within the block returns from the enclosing function, and a
+/// `break` or `continue` reaches the enclosing loop, just as if the statements
+/// were written inline. The block itself only ends early through `?` or
+/// `fatal_ok!`.
+///
+/// ```rust
+/// use impass::fatal;
+///
+/// fn first_port(lines: &[&str]) -> Option<u16> {
+///     for line in lines {
+///         fatal! {
+///             if line.starts_with('#') {
+///                 continue;
+///             }
+///             let port: u16 = line.parse()?;
+///             if port != 0 {
+///                 return Some(port);
+///             }
+///             Ok(())
+///         }
+///     }
+///     None
+/// }
+///
+/// assert_eq!(first_port(&["# default", "0", "8080"]), Some(8080));
+/// ```
+///
+/// The block is still evaluated within a closure, so these are returned from
+/// it and carried out right after, which leaves a few limitations:
+///
+/// - A `break` leaving the block can't carry a value, not even to a `loop`.
+/// - A `return`, `break` or `continue` within the arguments of another macro
+///   can't be seen, and refers to the closure, which usually fails to compile.
+/// - Within a `#[fatal_fn]` function, `return` keeps returning the `Result`
+///   of its body, as the enclosing function is the one being wrapped.
+/// - As always, a `return` within a closure, async block or nested item
+///   refers to that, and so does a `break` or `continue` within a loop of the
+///   block.
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// let port: u16 = loop {
+///     fatal! {
+///         break "8080".parse()?;
+///     }
+/// };
+/// ```
+///
+/// ### Options
+///
+/// `?` may be used on an `Option` as well, mixed freely with `Result`s. A
+/// `None` is treated as an error reading `value was None`, which is reported
+/// along with the reason like any other. This also applies to `fatal_fn`
+/// functions and their fallible siblings, but not to a `?` written within
+/// another macro's arguments, as those are never looked into.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use impass::fatal;
+///
+/// let config: HashMap<&str, &str> = HashMap::from([("port", "8080")]);
+/// let port: u16 = fatal! {
+///     #![reason("The port is missing or invalid")]
+///     let port: &str = config.get("port")?;
+///     let port: u16  = port.parse()?;
+///     Ok(port)
+/// };
+/// assert_eq!(port, 8080);
+/// ```
+///
+/// ### Error Types
+///
+/// The block's errors are collected into an `anyhow::Error` by default.
+/// `#![error(MyError)]` uses another error type instead, which only has to
+/// implement `Debug`: the report then consists of the reason, followed by the
+/// error's `Debug` rendering as its cause. `?` on an `Option`, `#[ctx(...)]`
+/// and `#![ensure_ok(...)]` all produce `anyhow::Error`s, so they can't be used
+/// with an error type which can't be converted from one.
+///
+/// ```rust,should_panic
+/// use impass::fatal;
+///
+/// #[derive(Debug)]
+/// enum ConfigError {
+///     Missing(&'static str),
+/// }
+///
+/// let host: &str = fatal! {
+///     #![error(ConfigError)]
+///     #![reason("The configuration is incomplete")]
+///     let host: &str = Err(ConfigError::Missing("host"))?;
+///     Ok(host)
+/// };
+/// ```
+///
+/// An error type without a `Debug` implementation is rejected:
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// enum ConfigError {
+///     Missing(&'static str),
+/// }
+///
+/// let host: &str = fatal! {
+///     #![error(ConfigError)]
+///     let host: &str = Err(ConfigError::Missing("host"))?;
+///     Ok(host)
+/// };
+/// ```
+///
+/// ### Statement Context
+///
+/// Individual statements of the block may be labelled with
+/// `#[ctx("...", args...)]`, which attaches the formatted message as context
+/// to the error of every `?` within that statement. The message is only
+/// formatted on failure. The attribute must be placed on a statement which
+/// uses `?`.
+///
+/// ```rust,should_panic
+/// use impass::fatal;
+///
+/// let path: &str = "/etc/app/kernel.bin";
+/// fatal! {
+///     #[ctx("loading the GPU kernel blob from {path}")]
+///     let blob: Vec<u8> = std::fs::read(path)?;
+///     Ok(blob)
+/// };
+/// ```
+///
+/// Labelling a statement which cannot fail is an error:
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// fatal! {
+///     #[ctx("this statement never fails")]
+///     let value: i32 = 42;
+///     Ok(value)
+/// };
+/// ```
+///
+/// ### Source Context
+///
+/// With `#![context]`, the error of every `?` in the block is given the source
+/// of the expression it was applied to as context, so that the report tells
+/// exactly which step failed. The source is cut short beyond 80 characters.
+/// Closures and async blocks within the block are left as they are, as their
+/// `?` propagates elsewhere. Any `#[ctx]` message is attached on top of it.
+///
+/// ```rust,should_panic
+/// use impass::fatal;
+///
+/// let dir: &str = "/etc/app";
+/// fatal! {
+///     #![context]
+///     let text: String = std::fs::read_to_string(format!("{dir}/port"))?;
+///     let port: u16    = text.trim().parse()?;
+///     Ok(port)
+/// };
+/// // Failed with: while evaluating `std::fs::read_to_string(format!("{dir}/port"))`
+/// ```
+///
+/// As with `#[ctx]`, the context is attached to an `anyhow::Error`, so it
+/// can't be combined with `#![error(...)]`.
+///
+/// ### Must Use
+///
+/// A block whose value is meaningful can be marked with `#![must_use]`, which
+/// makes discarding its value trigger the usual `unused_must_use` warning:
+///
+/// ```rust,compile_fail
+/// #![deny(unused_must_use)]
+/// use impass::fatal;
+///
+/// fn main() {
+///     fatal! {
+///         #![must_use]
+///         let port: u16 = "8080".parse()?;
+///         Ok(port)
+///     };
+/// }
+/// ```
+///
+/// ### Error Codes
+///
+/// A short, stable identifier can be attached with `#![code("MYAPP-0422")]`.
+/// It may only contain ASCII letters, digits and dashes, and is rendered as a
+/// `fatal error [MYAPP-0422]` header line before the report, so it can be
+/// quoted by users and searched for in logs. Without the `legacy-format`
+/// feature, it joins the location the report begins with instead, as in
+/// `fatal error [MYAPP-0422] at src/main.rs:12:5: ...`.
+///
+/// ### Suggestions
+///
+/// What an operator should do next can be attached with
+/// `#![suggest("...", args...)]`, which takes a format string and its
+/// arguments just like `format!`. Each suggestion is rendered as an indented
+/// `try: ...` line at the end of the report, and is only formatted on failure.
+/// The attribute may be repeated.
+///
+/// `#![suggest_if(<predicate>, "...", args...)]` only renders its suggestion
+/// when the predicate, called with the `&anyhow::Error` of the failed block,
+/// returns `true`.
+///
+/// ```rust,should_panic
+/// use std::io::ErrorKind;
+/// use impass::fatal;
+///
+/// fatal! {
+///     #![suggest("check that the configuration file exists")]
+///     #![suggest_if(
+///         |e: &anyhow::Error| e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == ErrorKind::PermissionDenied),
+///         "run as the service user"
+///     )]
+///     let config: String = std::fs::read_to_string("/etc/app/config.toml")?;
+///     Ok(config)
+/// };
+/// ```
+///
+/// ### Formatted Reasons
+///
+/// Like `panic!`, a reason may be followed by format arguments, as in
+/// `#![reason("failed to load {}", path.display())]`. The message is only
+/// formatted once the block has failed, and a malformed format string is a
+/// compile error pointing at the literal. The arguments are evaluated after
+/// the block has finished, so the same restrictions as for lazy reasons
+/// apply. A reason without arguments is used as-is, braces included.
+///
+/// ```rust,should_panic
+/// use impass::fatal;
+///
+/// let path: &str = "/etc/app/missing.toml";
+/// let config: String = fatal! {
+///     #![reason("Failed to load the configuration from {}", path)]
+///     let config: String = std::fs::read_to_string(path)?;
+///     Ok(config)
+/// };
+/// ```
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// let path: &str = "/etc/app/missing.toml";
+/// let config: String = fatal! {
+///     #![reason("Failed to load the configuration from {} as {}", path)]
+///     let config: String = std::fs::read_to_string(path)?;
+///     Ok(config)
+/// };
+/// ```
+///
+/// ### Reason Expressions
+///
+/// A reason may also be any other expression whose value is `Display`, such
+/// as a constant or a `format!`, as in `#![reason(INIT_FAIL)]`. Like the
+/// arguments of a formatted reason, it is only evaluated once the block has
+/// failed. With `fatal_fn`, it is written as `reason = EXPR` and may refer to
+/// the function's parameters. A value which isn't `Display` is a compile error
+/// pointing at the expression.
+///
+/// ```rust,should_panic
+/// use impass::fatal;
+///
+/// const INIT_FAIL: &str = "Failed to initialise the worker";
+///
+/// let port: u16 = fatal! {
+///     #![reason(INIT_FAIL)]
+///     let port: u16 = "80a".parse()?;
+///     Ok(port)
+/// };
+/// ```
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// struct Opaque;
+///
+/// let port: u16 = fatal! {
+///     #![reason(Opaque)]
+///     let port: u16 = "80a".parse()?;
+///     Ok(port)
+/// };
+/// ```
+///
+/// ### Reason Style
+///
+/// Trailing whitespace is stripped from every reason once it is rendered, so
+/// that a newline captured from a template doesn't leave a blank line in the
+/// report. Anything else is kept as it is, including the lines of a reason
+/// spanning several of them.
+///
+/// `#![lint_reasons]` additionally warns about a literal reason which ends
+/// with `.` or `!`, which the report follows with its own separators, or which
+/// contains a newline. The warnings are emitted as uses of a deprecated item,
+/// so `#[deny(deprecated)]` turns them into errors:
+///
+/// ```rust,compile_fail
+/// #![deny(deprecated)]
+/// use impass::fatal;
+///
+/// let port: u16 = fatal! {
+///     #![lint_reasons]
+///     #![reason("The port is invalid.")]
+///     let port: u16 = "80a".parse()?;
+///     Ok(port)
+/// };
+/// ```
+///
+/// ### Attribute Syntax
+///
+/// Every attribute may be written in the list form, `#![reason("...")]`, or
+/// in the name-value form, `#![reason = "..."]`. An attribute the macros
+/// don't know, including a misspelled or differently cased one, is a compile
+/// error rather than being dropped, and so is one whose value isn't in a
+/// supported form. The error suggests the attribute which was most likely
+/// meant, along with how it is written:
+///
+/// ```rust
+/// use impass::fatal;
+///
+/// let port: u16 = fatal! {
+///     #![reason = "The port is invalid"]
+///     let port: u16 = "8080".parse()?;
+///     Ok(port)
+/// };
+/// ```
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// let port: u16 = fatal! {
+///     #![resaon("The port is invalid")]
+///     let port: u16 = "8080".parse()?;
+///     Ok(port)
+/// };
+/// ```
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// let port: u16 = fatal! {
+///     #![reason("The port is invalid" because)]
+///     let port: u16 = "8080".parse()?;
+///     Ok(port)
+/// };
+/// ```
+///
+/// The same goes for the arguments of `fatal_fn`:
+///
+/// ```rust,compile_fail
+/// use impass::fatal_fn;
+///
+/// #[fatal_fn(Reason = "The port is invalid")]
+/// fn port() -> u16 {
+///     let port: u16 = "8080".parse()?;
+///     Ok(port)
+/// }
+/// ```
+///
+/// ### Success Assertions
+///
+/// A block may succeed with a value which is nonetheless invalid. Each
+/// `#![ensure_ok(<predicate>, "...", args...)]` calls its predicate with a
+/// reference to the success value, in the order they are given. The first one
+/// returning `false` fails the block through the usual path, with the message
+/// and the value's `Debug` rendering (truncated to 256 characters) as the
+/// error. The value must implement `Debug` once any assertion is given.
+///
+/// ```rust,should_panic
+/// use impass::fatal;
+///
+/// let listeners: Vec<u16> = fatal! {
+///     #![reason("The configuration is invalid")]
+///     #![ensure_ok(|listeners| !listeners.is_empty(), "at least one listener must be configured")]
+///     let listeners: Vec<u16> = "".split_terminator(',').map(str::parse).collect::<Result<_, _>>()?;
+///     Ok(listeners)
+/// };
+/// ```
+///
+/// ### Lazy Reasons
+///
+/// A reason which is expensive to compute can be given as a closure through
+/// `#![reason_with(|| ...)]` instead, or `reason_with = || ...` on a
+/// `fatal_fn`. The closure is only created and invoked once the block has
+/// failed, and must return something implementing `Display`. It is called
+/// after the block has finished, so it may borrow any local declared *before*
+/// the `fatal!` invocation, or any parameter of a `fatal_fn` which its body
+/// doesn't move, but not the locals declared inside of the block. Its captures
+/// are borrowed unless it is a `move` closure, which only takes them on
+/// failure. `reason` and
+/// `reason_with` are mutually exclusive.
+///
+/// ```rust
+/// use impass::fatal;
+///
+/// let config: Vec<&str> = vec!["listen = 8080", "workers = 4"];
+/// let port: u16 = fatal! {
+///     #![reason_with(|| format!("Invalid configuration:\n{}", config.join("\n")))]
+///     let port: u16 = "8080".parse()?;
+///     Ok(port)
+/// };
+/// assert_eq!(port, 8080);
+/// ```
+///
+/// ### Recoverable
+///
+/// To unit test or embed code which uses `fatal!` internally, a block can be
+/// marked with `#![recoverable]`. Rather than terminating, it then returns the
+/// error from the enclosing function, with the reason attached as context and
+/// converted with `Into` like `?` would. The enclosing function must return a
+/// `Result` whose error type an `anyhow::Error` converts into, such as
+/// `anyhow::Error` itself or `Box<dyn Error + Send + Sync>`. As the block never
+/// terminates, the attribute can't be combined with `no_catch`, `abort`,
+/// `exit` or an `error` type.
+///
+/// ```rust
+/// use impass::fatal;
+///
+/// fn parse_port(port: &str) -> anyhow::Result<u16> {
+///     let port: u16 = fatal! {
+///         #![recoverable]
+///         #![reason("The port {} is invalid", port)]
+///         let port: u16 = port.parse()?;
+///         Ok(port)
+///     };
+///     Ok(port)
+/// }
+///
+/// assert_eq!(parse_port("8080").unwrap(), 8080);
+/// assert_eq!(parse_port("eighty").unwrap_err().to_string(), "The port eighty is invalid");
+/// ```
+///
+/// The error is returned through a plain `return`, which is reported at the
+/// attribute if the enclosing function can't return it:
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// fn parse_port(port: &str) -> u16 {
+///     fatal! {
+///         #![recoverable]
+///         let port: u16 = port.parse()?;
+///         Ok(port)
+///     }
+/// }
+/// ```
+///
+/// ### No Catch
+///
+/// A failing block panics, so an enclosing `catch_unwind` (such as one set up
+/// by a plugin host) may swallow it. A block marked with `#![no_catch]` does
+/// not unwind at all: it prints the same report to stderr, then terminates
+/// the process through `std::process::abort`. Neither `Drop` implementations
+/// nor the panic hook run in that case.
+///
+/// ### Abort
+///
+/// Unwinding out of an `extern "C"` function is undefined behavior, so a
+/// block within an FFI callback should be marked with `#![abort]` (or its
+/// `fatal_fn` given `abort`). It terminates exactly like a `no_catch` block,
+/// whatever the panic strategy of the binary: the report is printed to stderr
+/// just as the panic would carry it, then the process is aborted.
+///
+/// ```rust
+/// use impass::fatal;
+///
+/// extern "C" fn on_event(code: i32) -> u8 {
+///     fatal! {
+///         #![abort]
+///         #![reason("Received the invalid event code {}", code)]
+///         let code: u8 = u8::try_from(code)?;
+///         Ok(code)
+///     }
+/// }
+///
+/// assert_eq!(on_event(7), 7);
+/// ```
+///
+/// ### Exit Codes
+///
+/// Command line tools usually want a plain error and a specific exit status
+/// rather than a panic. A block marked with `#![exit(code)]` (or a `fatal_fn`
+/// given `exit = code`) prints the report to stderr right after the short
+/// line, starting with the reason, then ends the process through
+/// `std::process::exit(code)`. As with `no_catch`, nothing unwinds, so neither
+/// `Drop` implementations nor the panic hook run. The code may be any `i32`
+/// expression, and is only evaluated on failure.
+///
+/// ```rust,no_run
+/// use impass::fatal;
+///
+/// const EXIT_CONFIG: i32 = 78;
+///
+/// let config: String = fatal! {
+///     #![exit(EXIT_CONFIG)]
+///     #![reason("Failed to read the configuration")]
+///     let config: String = std::fs::read_to_string("config.toml")?;
+///     Ok(config)
+/// };
+/// ```
+///
+/// ### Unwind-Required Guards
+///
+/// A scope whose cleanup lives in `Drop` implementations, such as the rollback
+/// of a transaction, may hold the guard returned by
+/// `impass::register_unwind_required_guard()`. While it is alive, `abort` and
+/// `exit` blocks failing on the same thread print their report and panic
+/// instead, so that the scope is unwound. `no_catch` blocks still abort, and
+/// end their report with a note that the cleanup was skipped.
+///
+/// ### Panicking Values
+///
+/// The messages formatted on the failure path (the reason, suggestions, `ctx`
+/// labels and the `Debug` rendering of a value rejected by `ensure_ok`) are
+/// each formatted under `catch_unwind`. A message whose arguments panic while
+/// being formatted is replaced with `«format panicked»`, and the report ends
+/// with a single note saying so, rather than the failure being lost to a
+/// second panic.
+///
+/// ### Deterministic Output
+///
+/// For golden tests, a block marked with `#![deterministic]` (or any block,
+/// when the `IMPASS_DETERMINISTIC=1` environment variable is set) produces
+/// byte-identical output for identical failures:
+///
+/// - The time in the short line is always `0.000`.
+/// - Any backtrace captured by `anyhow` is kept, but without the addresses of
+///   its frames, and with the paths within the package made relative to its
+///   manifest directory (`CARGO_MANIFEST_DIR`, or else the current directory).
+/// - The report is printed by impass itself rather than by the panic hook,
+///   which would include the thread's ID. The block still unwinds with the
+///   report as its payload, so `catch_unwind` and `#[should_panic]` behave as
+///   usual, but the panic hook is not invoked.
+///
+/// Locations are rendered as given by `file!()`, i.e. relative to the
+/// workspace root when built by Cargo.
+///
+/// ### Short Line
+///
+/// As soon as a block fails, and before the report is assembled, a single
+/// line is written to stderr:
+///
+/// ```text
+/// fatal: <seconds>.<millis> <file>:<line>:<column>: <reason>
+/// ```
+///
+/// The time is the number of seconds since the Unix epoch (or `0.000` in the
+/// [deterministic mode](#deterministic-output)), the location is
+/// that of the `fatal!` invocation (or the `#[fatal_fn]` attribute), and the
+/// reason is the one given to the block or the default message. This format
+/// is stable, and can be relied upon by log parsers. It always precedes the
+/// full report, so something is emitted even if the process dies while the
+/// report is being assembled.
+///
+/// ### Usage
+///
+/// The macro accepts a code block that must return a `Result` type.
+///
+/// ```rust,should_panic
+/// use thiserror::Error;
+/// use impass::fatal;
+/// 
+/// // Declare an error type for demonstration purposes.
+/// #[derive(Error, Debug)]
+/// pub enum MyError {
+///     #[error("This operation failed")]
+///     OperationFailed
+/// }
+///
+/// // A fallible function for demonstration.
+/// fn might_fail(value: i32) -> Result<i32, MyError> {
+///     if value < 10 {
+///         Err(MyError::OperationFailed)
+///     } else {
+///         Ok(value * 2)
+///     }
+/// }
+///
+/// fn main() {
+///     // Simple usage without a custom message.
+///     let final_value = fatal! {
+///         let value = might_fail(15)?;
+///         Ok(value)
+///     };
+///     println!("Successfully computed: {}", final_value);
+///
+///     // Usage with a custom message using the `#![reason]` attribute.
+///     fatal! {
+///         #![reason("Failed to initialize system-critical component.")]
+///         let result = might_fail(5)?; // This will cause a panic.
+///         Ok(())
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn fatal(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as FatalInput).expand().into()
+}
+
+/// Lazily initializes a `static` with the result of a `fatal!` block.
+///
+/// A `fatal!` block cannot run inside of a `static` or `const` initializer, as
+/// those are evaluated at compile time. `fatal_lazy!` accepts the exact same
+/// input as `fatal!`, but evaluates it on first access through a
+/// [`std::sync::LazyLock`] instead.
+///
+/// ```rust
+/// use std::sync::LazyLock;
+/// use impass::fatal_lazy;
+///
+/// static PORT: LazyLock<u16> = fatal_lazy! {
+///     #![reason("The default port is invalid")]
+///     let port: u16 = "8080".parse()?;
+///     Ok(port)
+/// };
+///
+/// assert_eq!(*PORT, 8080);
+/// ```
+///
+/// Marking a `fatal!` block with `#![static_init]` turns any attempt at using
+/// it in a `static` into a single, explicit compile error pointing here:
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+///
+/// static PORT: u16 = fatal! {
+///     #![static_init]
+///     let port: u16 = "8080".parse()?;
+///     Ok(port)
+/// };
+/// ```
+#[proc_macro]
+pub fn fatal_lazy(input: TokenStream) -> TokenStream {
+    let block: TokenStream2 = parse_macro_input!(input as FatalInput).expand();
+    quote! {
+        ::impass::__private::std::sync::LazyLock::new(|| #block)
+    }.into()
+}
+
+
+/// Runs a long-running body under a watchdog, which treats the body as
+/// fatally wedged once it stops making progress.
+///
+/// The body pings the `impass::Heartbeat` named by `heartbeat` at its progress
+/// points, through `beat()` or `beat_with(label)`. If no beat arrives within
+/// the `stall` window, a monitor running on a thread of its own reports the
+/// stall through the usual failure path, with `reason` and the time (and
+/// label) of the last beat, and aborts the process. A stalled body can't be
+/// made to unwind from another thread, so a stall always aborts, whatever
+/// unwind-required guards are alive. The window starts when the body does,
+/// and the monitor is disarmed as soon as the body completes or unwinds.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use impass::fatal_watchdog;
+///
+/// let compacted: usize = fatal_watchdog!(heartbeat = hb, stall = Duration::from_secs(120), reason = "The compactor stalled", {
+///     let mut compacted: usize = 0;
+///     for level in 0..4 {
+///         hb.beat_with(format!("compacting level {level}"));
+///         compacted += level;
+///     }
+///     compacted
+/// });
+/// assert_eq!(compacted, 6);
+/// ```
+///
+/// The value of the body is returned as is. Errors within it are its own to
+/// handle, e.g. through a `fatal!` block.
+///
+/// ### Async Bodies
+///
+/// An `async` block evaluates to a future instead, which the watchdog monitors
+/// from its first poll until it completes or is dropped. The monitor is still
+/// a thread, so it works with any executor, and catches a body which blocks
+/// the executor as well as one which never wakes up.
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use impass::fatal_watchdog;
+///
+/// async fn compact(level: usize) {}
+///
+/// async fn run() {
+///     fatal_watchdog!(heartbeat = hb, stall = Duration::from_secs(120), reason = "The compactor stalled", async {
+///         for level in 0..4 {
+///             compact(level).await;
+///             hb.beat();
+///         }
+///     }).await;
+/// }
+/// ```
+#[proc_macro]
+pub fn fatal_watchdog(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as FatalWatchdog).expand().into()
+}
+
+/// The input of `fatal_watchdog!`: its named arguments, followed by the body.
+struct FatalWatchdog {
+    heartbeat: syn::Ident,
+    stall:     syn::Expr,
+    reason:    Option<syn::Expr>,
+    body:      syn::Expr,
+}
+
+impl Parse for FatalWatchdog {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        const ARGUMENTS: &str = "expected one of `heartbeat`, `stall` and `reason`";
+
+        // Parse the `name = value` arguments, up to the body.
+        let mut heartbeat: Option<syn::Ident> = None;
+        let mut stall:     Option<syn::Expr>  = None;
+        let mut reason:    Option<syn::Expr>  = None;
+        while input.peek(syn::Ident) && input.peek2(syn::Token![=]) {
+            let name: syn::Ident = input.parse()?;
+            input.parse::<syn::Token![=]>()?;
+            let duplicate: bool = match name.to_string().as_str() {
+                "heartbeat" => heartbeat.replace(input.parse()?).is_some(),
+                "stall"     => stall.replace(input.parse()?).is_some(),
+                "reason"    => reason.replace(input.parse()?).is_some(),
+                _           => return Err(syn::Error::new(
+                    name.span(),
+                    format!("unknown argument `{name}` for `fatal_watchdog!`, {ARGUMENTS}")
+                )),
+            };
+            if duplicate {
+                return Err(syn::Error::new(name.span(), format!("`{name}` may only be given once")));
+            }
+            input.parse::<syn::Token![,]>()?;
+        }
+
+        let body: syn::Expr = input.parse()?;
+        input.parse::<Option<syn::Token![,]>>()?;
+        let missing = |name: &str, example: &str| syn::Error::new(
+            proc_macro2::Span::call_site(),
+            format!("`fatal_watchdog!` expects a `{name}` argument before its body, e.g. `{name} = {example}`")
+        );
+        Ok(FatalWatchdog {
+            heartbeat: heartbeat.ok_or_else(|| missing("heartbeat", "hb"))?,
+            stall:     stall.ok_or_else(|| missing("stall", "Duration::from_secs(120)"))?,
+            reason,
+            body,
+        })
+    }
+}
+
+impl FatalWatchdog {
+
+    /// Generates the code arming the watchdog around the body. An async body
+    /// arms it once polled, from within the future it is wrapped in.
+    fn expand(self) -> TokenStream2 {
+        let FatalWatchdog { heartbeat, stall, reason, body } = self;
+
+        let watchdog: syn::Ident   = syn::Ident::new("watchdog", proc_macro2::Span::mixed_site());
+        let value:    syn::Ident   = syn::Ident::new("value", proc_macro2::Span::mixed_site());
+        let reason:   TokenStream2 = match reason {
+            Some(reason) => reason.to_token_stream(),
+            None         => default_reason(),
+        };
+        let guard:    TokenStream2 = forbid_guard();
+        let marker:   TokenStream2 = audit_marker();
+        let (run, wrap): (TokenStream2, fn(TokenStream2) -> TokenStream2) = match &body {
+            syn::Expr::Async(_) => (quote! { (#body).await }, |tokens| quote! { async { #tokens } }),
+            _                   => (body.to_token_stream(), |tokens| quote! { { #tokens } }),
+        };
+
+        let scope: TokenStream2 = wrap(quote! {
+            let #heartbeat: ::impass::Heartbeat           = ::impass::__private::Watchdog::heartbeat();
+            let #watchdog:  ::impass::__private::Watchdog = ::impass::__private::Watchdog::arm(&#heartbeat, #stall, &#reason);
+            let #value = #run;
+            ::core::mem::drop(#watchdog);
+            #value
+        });
+        quote! {
+            {
+                #guard
+                #marker
+                #scope
+            }
+        }
+    }
+}
+
+
+/// Reports code which should never have been reached, in place of
+/// `unreachable!`.
+///
+/// `fatal_unreachable!` takes an optional format string followed by its
+/// arguments, which make up the reason of the report, and reports through the
+/// same path as a `fatal!` block: the fatal hook sees it, the report is located
+/// at the invocation, and its cause tells that it indicates a bug. Without a
+/// format string, the reason is the default one of a `fatal!` block.
+///
+/// The invocation diverges, so it fits wherever a value of any type is
+/// expected, such as a `match` arm:
+///
+/// ```rust,should_panic
+/// use impass::fatal_unreachable;
+///
+/// #[derive(Debug)]
+/// enum State {
+///     Idle,
+///     Running(u32),
+///     Stopped,
+/// }
+///
+/// fn progress(state: State) -> u32 {
+///     match state {
+///         State::Idle          => 0,
+///         State::Running(done) => done,
+///         State::Stopped       => fatal_unreachable!("state machine in state {:?}", state),
+///     }
+/// }
+///
+/// progress(State::Stopped);
+/// ```
+#[proc_macro]
+pub fn fatal_unreachable(input: TokenStream) -> TokenStream {
+    expand_fatal_bug(input.into(), "fatal_unreachable!", "Unreachable").unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Reports code which hasn't been written yet, in place of `todo!`.
+///
+/// It takes the same input and reports the same way as `fatal_unreachable!`.
+///
+/// ```rust,should_panic
+/// use impass::fatal_todo;
+///
+/// fn migrate(version: u32) -> u32 {
+///     match version {
+///         1 => 2,
+///         _ => fatal_todo!("migrating from version {version}"),
+///     }
+/// }
+///
+/// migrate(3);
+/// ```
+#[proc_macro]
+pub fn fatal_todo(input: TokenStream) -> TokenStream {
+    expand_fatal_bug(input.into(), "fatal_todo!", "Todo").unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Reports code which is deliberately left unimplemented, in place of
+/// `unimplemented!`.
+///
+/// It takes the same input and reports the same way as `fatal_unreachable!`.
+///
+/// ```rust,should_panic
+/// use impass::fatal_unimplemented;
+///
+/// fn checksum(algorithm: &str, data: &[u8]) -> u32 {
+///     match algorithm {
+///         "sum" => data.iter().map(|&byte| u32::from(byte)).sum(),
+///         _     => fatal_unimplemented!("the {algorithm} checksum"),
+///     }
+/// }
+///
+/// checksum("crc32", b"impass");
+/// ```
+#[proc_macro]
+pub fn fatal_unimplemented(input: TokenStream) -> TokenStream {
+    expand_fatal_bug(input.into(), "fatal_unimplemented!", "Unimplemented").unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Generates the diverging code of `fatal_unreachable!` and its siblings,
+/// which fail with the `Bug` variant named by `kind`, through the failure path
+/// of a block whose only attribute is the given reason.
+fn expand_fatal_bug(input: TokenStream2, name: &str, kind: &str) -> syn::Result<TokenStream2> {
+
+    // Unlike a reason, a lone string literal is formatted as well, just as it
+    // is by `unreachable!`, so that it may capture variables.
+    let mut args: Punctuated<syn::Expr, syn::Token![,]> = Punctuated::parse_terminated.parse2(input)?;
+    if let Some(format) = args.first_mut() {
+        *format = attrs::ungroup(format.clone());
+    }
+    let reason: Option<Reason> = match args.first() {
+        None                                                             => None,
+        Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(_), .. })) => Some(Reason::Format(args)),
+        Some(_)                                                          => return Err(syn::Error::new_spanned(
+            args,
+            format!("`{name}` expects a format string followed by its arguments, e.g. `{name}(\"in state {{:?}}\", state)`")
+        )),
+    };
+
+    let error:   syn::Ident   = syn::Ident::new("error", proc_macro2::Span::mixed_site());
+    let kind:    syn::Ident   = syn::Ident::new(kind, proc_macro2::Span::call_site());
+    let failure: TokenStream2 = failure_path(&error, &Attrs { reason, ..Attrs::default() });
+    Ok(quote! {
+        {
+            let #error: ::impass::__private::anyhow::Error = ::impass::__private::anyhow::Error::new(::impass::__private::Bug::#kind);
+            #failure
+        }
+    })
+}
+
+
+/// Generates the context attached to the error when no reason is provided,
+/// which is `impass::DEFAULT_MESSAGE` as long as the `legacy-default-message`
+/// feature is enabled.
+fn default_reason() -> TokenStream2 {
+    if cfg!(feature = "legacy-default-message") {
+        quote! { ::impass::DEFAULT_MESSAGE }
+    } else {
+        quote! { ::core::concat!("fatal error at ", ::core::file!(), ":", ::core::line!(), " (no reason provided)") }
+    }
+}
+
+/// Generates the reason of a block, which is the default one unless given.
+fn reason_value(attrs: &Attrs) -> TokenStream2 {
+    match &attrs.reason {
+        Some(reason) => reason.to_token_stream(),
+        None         => default_reason(),
+    }
+}
+
+/// The prefix of the marker statics emitted with the `audit` feature, which is
+/// followed by the crate name and the site's index, each ending in `__`.
+const AUDIT_MARKER_PREFIX: &str = "__impass_site__";
+
+/// The number of sites expanded so far in the crate being compiled.
+static AUDIT_SITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Generates the marker static of a fatal site when the `audit` feature is
+/// enabled, whose symbol tells the crate it was expanded in. It is referenced
+/// from the failure path, so it is only ever dropped from an artifact along
+/// with the site itself.
+fn audit_marker() -> TokenStream2 {
+    match audit_static() {
+        Some((item, name)) => quote! {
+            #item
+            ::core::hint::black_box(&#name);
+        },
+        None               => TokenStream2::new(),
+    }
+}
+
+/// Generates the marker static of a fatal site like `audit_marker()`, but
+/// leaves referencing it to the caller, which is given its name.
+fn audit_static() -> Option<(TokenStream2, syn::Ident)> {
+    if !cfg!(feature = "audit") {
+        return None;
+    }
+
+    let krate: String     = std::env::var("CARGO_CRATE_NAME").unwrap_or_else(|_| String::from("unknown"))
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let site:  usize      = AUDIT_SITES.fetch_add(1, Ordering::Relaxed);
+    let name:  syn::Ident = syn::Ident::new(&format!("{AUDIT_MARKER_PREFIX}{krate}__{site}__"), proc_macro2::Span::call_site());
+    let item:  TokenStream2 = quote! {
+        #[used]
+        #[allow(non_upper_case_globals)]
+        static #name: u8 = 0;
+    };
+    Some((item, name))
+}
+
+
+/// The environment variable which forbids fatal sites in the crate being
+/// compiled, when set to `1` through `cargo:rustc-env` by its build script.
+const FORBID_ENV: &str = "IMPASS_FORBID";
+
+/// Generates the guard which fails the compilation of a fatal site in a crate
+/// which forbids them, either through `IMPASS_FORBID=1` or through
+/// `--cfg impass_forbid`.
+///
+/// The variable is read while the site is expanded, within the compiler
+/// invoked for the crate, which is the only one it is set for. The `cfg` can
+/// only be checked by the crate itself, so the error is emitted under it, along
+/// with an `allow` for the crates which don't declare it.
+fn forbid_guard() -> TokenStream2 {
+    const ADVICE: &str = "return the error to the caller instead, e.g. with `#![recoverable]`";
+    if std::env::var(FORBID_ENV).is_ok_and(|value| value == "1") {
+        let message: String = format!("fatal sites are forbidden in this crate, which is built with `{FORBID_ENV}=1`; {ADVICE}");
+        return quote! { ::core::compile_error!(#message); };
+    }
+
+    let message: String = format!("fatal sites are forbidden in this crate, which is built with `--cfg impass_forbid`; {ADVICE}");
+    quote! {
+        #[allow(unexpected_cfgs)]
+        {
+            #[cfg(impass_forbid)]
+            ::core::compile_error!(#message);
+        }
+    }
+}
+
+/// Generates the code which reports the error `error` according to the
+/// block's attributes, and terminates.
+///
+/// All of the macros funnel through here, which leaves the report itself to
+/// `fail()` of the `impass` crate, along with the status file, the fatal hook
+/// and the way the process ends. `Fatal::raise()` and the watchdogs go through
+/// it as well, so the layout of a report is defined in one place. What is left
+/// here is whatever only the site knows: its location, its reason, its
+/// attributes along with the defaults of its module, and its suggestions,
+/// whose conditions and arguments may refer to the user's locals.
+///
+/// The location is taken from the invocation, which `fatal_fn` spans to the
+/// function item. It is evaluated in a `const` block, so that a site within a
+/// `#[track_caller]` function is still located at itself.
+///
+/// The locals declared here are spanned with `Span::mixed_site`, so they can
+/// never shadow the user's locals referenced by a reason or a suggestion.
+fn failure_path(error: &syn::Ident, attrs: &Attrs) -> TokenStream2 {
+    if !cfg!(feature = "std") {
+        return core_failure_path(error, attrs);
+    }
+
+    let local = |name: &str| syn::Ident::new(name, proc_macro2::Span::mixed_site());
+    let reason:      syn::Ident = local("reason");
+    let suggestions: syn::Ident = local("suggestions");
+    let defaults:    syn::Ident = local("defaults");
+    let options:     syn::Ident = local("options");
+
+    let reason_value: TokenStream2 = reason_value(attrs);
+
+    // Whatever the site doesn't give itself falls back to the defaults of its
+    // module, which are those of `Defaults::NONE` unless a `fatal_defaults!`
+    // item is in scope. They are only looked up on the failure path.
+    let terminates:     bool         = attrs.no_catch.is_some() || attrs.abort.is_some() || attrs.exit.is_some();
+    let defaults_value: TokenStream2 = if terminates && attrs.deterministic.is_some() && !attrs.suggestions.is_empty() {
+        TokenStream2::new()
+    } else {
+        let method: syn::Ident = syn::Ident::new(DEFAULTS_METHOD, proc_macro2::Span::call_site());
+        quote! {
+            let #defaults: ::impass::__private::Defaults = (&::impass::__private::DefaultsProbe).#method();
+        }
+    };
+
+    // An error of a type given through `error` is rendered through its
+    // `Debug` implementation.
+    let failing_value: TokenStream2 = match &attrs.error {
+        Some(_) => quote! { ::impass::__private::DebugError(#error) },
+        None    => error.to_token_stream(),
+    };
+
+    // The deterministic mode is either requested by the block, or by the
+    // defaults of its module. The environment is checked by `fail()`.
+    let deterministic: TokenStream2 = match attrs.deterministic {
+        Some(_) => quote! { true },
+        None    => quote! { #defaults.deterministic },
+    };
+
+    // A `no_catch` block must not unwind, or an enclosing `catch_unwind`
+    // could swallow it, and neither must an `abort` one, which may be within
+    // an FFI callback. An `exit` block exits with the given code. While an
+    // unwind-required guard is alive, `abort` and `exit` blocks panic instead,
+    // so that the guarded scope's `Drop`s run. A site which doesn't say how it
+    // terminates does as its module's defaults say.
+    let terminate: TokenStream2 = if attrs.no_catch.is_some() {
+        quote! { ::impass::__private::Terminate::NoCatch }
+    } else if attrs.abort.is_some() {
+        quote! { ::impass::__private::Terminate::Abort }
+    } else if let Some(exit) = &attrs.exit {
+        quote! { ::impass::__private::Terminate::Exit(#exit) }
+    } else {
+        quote! { #defaults.terminate }
+    };
+    let code: TokenStream2 = match &attrs.code {
+        Some(code) => quote! { ::core::option::Option::Some(#code) },
+        None       => quote! { ::core::option::Option::None },
+    };
+
+    // Suggestions are rendered as `try: ...` lines after the report. Their
+    // conditions are given the error before it is consumed by the report,
+    // from within a closure called by `fail()` once the short line is written.
+    // A site without any takes those of its module instead.
+    let suggest: TokenStream2 = attrs.suggestions.iter().map(|attrs::Suggestion { condition, message }| {
+        let push: TokenStream2 = quote! {
+            #suggestions.push_str(&::impass::__private::std::format!("\n    try: {}", ::impass::__private::render_guarded(&::core::format_args!(#message))));
+        };
+        match condition {
+            Some(condition) => quote! { if (#condition)(#error) { #push } },
+            None            => push,
+        }
+    }).collect();
+    let collect: TokenStream2 = if attrs.suggestions.is_empty() {
+        quote! { #defaults.render_suggestions() }
+    } else {
+        quote! {
+            let mut #suggestions: ::impass::__private::std::string::String = ::impass::__private::std::string::String::new();
+            #suggest
+            if !#suggestions.is_empty() {
+                #suggestions.insert(0, '\n');
+            }
+            #suggestions
+        }
+    };
+
+    // The suggestions are given the error as the site has it, which is only
+    // named if a condition asks for it.
+    let conditional: bool         = attrs.suggestions.iter().any(|suggestion| suggestion.condition.is_some());
+    let param:       TokenStream2 = if conditional { error.to_token_stream() } else { quote! { _ } };
+    let unwrap:      TokenStream2 = match (conditional, &attrs.error) {
+        (true, Some(_)) => quote! { let #error = &#error.0; },
+        _               => TokenStream2::new(),
+    };
+
+    // The audit marker, if any, is handed to `fail()`, which keeps it
+    // referenced from the failure path.
+    let (marker, marker_value): (TokenStream2, TokenStream2) = match audit_static() {
+        Some((item, name)) => (item, quote! { ::core::option::Option::Some(&#name) }),
+        None               => (TokenStream2::new(), quote! { ::core::option::Option::None }),
+    };
+
+    // The reason is only evaluated once, as a lazy reason may have side
+    // effects.
+    let guard: TokenStream2 = forbid_guard();
+    quote! {
+        #guard
+        #marker
+        let #reason = #reason_value;
+        #defaults_value
+        let #options: ::impass::__private::FailureOptions<'_> = ::impass::__private::FailureOptions {
+            code:          #code,
+            terminate:     #terminate,
+            deterministic: #deterministic,
+            backtrace:     true,
+            marker:        #marker_value,
+            class:         ::core::option::Option::None,
+            fields:        &[],
+        };
+        ::impass::__private::fail(
+            const { ::core::panic::Location::caller() },
+            #options,
+            &#reason,
+            #failing_value,
+            |#param| {
+                #unwrap
+                #collect
+            },
+        )
+    }
+}
+
+/// Generates a warning at the literal for each style issue of the reason, as
+/// asked for through `lint_reasons`.
+///
+/// A proc macro can't emit warnings on stable, so each one is the use of a
+/// deprecated item, whose note is the message. Like any other warning, it may
+/// be silenced with `#[allow(deprecated)]`, or made an error with
+/// `#[deny(deprecated)]`.
+fn reason_warnings(attrs: &Attrs) -> TokenStream2 {
+    attrs.reason_lints().into_iter().map(|(span, message)| quote_spanned! { span =>
+        {
+            #[deprecated(note = #message)]
+            #[allow(non_camel_case_types)]
+            struct lint_reasons;
+            let _ = lint_reasons;
+        }
+    }).collect()
+}
+
+/// Generates the code which reports the error `error` without the `std`
+/// feature, where all there is to report through is `core::panic!`.
+///
+/// The message is the reason followed by the `Debug` rendering of the error,
+/// be it an `anyhow::Error` or of the type given through `error`. A reason
+/// with arguments is formatted within the panic itself, as the value of
+/// `format_args!` can't be kept around until then.
+fn core_failure_path(error: &syn::Ident, attrs: &Attrs) -> TokenStream2 {
+    let reason: TokenStream2 = match &attrs.reason {
+        Some(Reason::Message(msg))  => attrs::trim_end(msg).to_token_stream(),
+        Some(Reason::Format(args))  => quote! { ::core::format_args!(#args) },
+        Some(Reason::Lazy(closure)) => quote! { (#closure)() },
+        Some(Reason::Expr(expr))    => expr.to_token_stream(),
+        None                        => default_reason(),
+    };
+
+    let marker: TokenStream2 = audit_marker();
+    let guard:  TokenStream2 = forbid_guard();
+    quote! {
+        #guard
+        #marker
+        ::core::panic!("{}: {:?}", #reason, #error)
+    }
+}
+
+
+/// Ends the enclosing `fatal!` block early with a successful value.
+///
+/// `fatal_ok!(value)` makes the enclosing `fatal!` block (or `#[fatal_fn]`
+/// function) evaluate to `value` immediately, skipping any remaining
+/// statements. `fatal_ok!()` is shorthand for `fatal_ok!(())`. It is the way
+/// of finishing a block early, as a `return` within a `fatal!` block returns
+/// from the enclosing function instead.
+///
+/// ```rust
+/// use impass::{fatal, fatal_ok};
+///
+/// let args: Vec<&str> = vec!["--version"];
+/// let code: i32 = fatal! {
+///     if args.contains(&"--version") {
+///         fatal_ok!(0);
+///     }
+///     let code: i32 = args[0].parse()?;
+///     Ok(code)
+/// };
+/// assert_eq!(code, 0);
+/// ```
+///
+/// Using it anywhere else, including in closures nested inside of a `fatal!`
+/// block, is a compile error:
+///
+/// ```rust,compile_fail
+/// use impass::fatal_ok;
+///
+/// fn main() {
+///     fatal_ok!(0);
+/// }
+/// ```
+///
+/// ### Path Matching
+///
+/// Macro paths can't be resolved while the enclosing block is expanded, so an
+/// invocation is recognized by the last segment of its path alone:
+/// `fatal_ok!`, `impass::fatal_ok!` and `::impass::fatal_ok!` all work, and
+/// so does any spelling of the enclosing `fatal!` itself. Anything else is
+/// left untouched, which means that an aliased import
+/// (`use impass::fatal_ok as done;`) or a `macro_rules!` wrapper expanding to
+/// `fatal_ok!` is not recognized, and fails to compile as if it were used
+/// outside of a `fatal!` block:
+///
+/// ```rust,compile_fail
+/// use impass::fatal;
+/// use impass::fatal_ok as done;
+///
+/// let value: i32 = fatal! {
+///     done!(42);
+///     Ok(0)
+/// };
+/// ```
+///
+/// Conversely, an unrelated macro which happens to be called `fatal_ok` can be
+/// hidden from the enclosing block by marking its statement (or expression)
+/// with `#[fatal_opaque]`:
+///
+/// ```rust
+/// use impass::fatal;
+///
+/// mod shim {
+///     macro_rules! fatal_ok {
+///         ($value:expr) => { $value };
+///     }
+///     pub(crate) use fatal_ok;
+/// }
+///
+/// let value: i32 = fatal! {
+///     #[fatal_opaque]
+///     let value: i32 = shim::fatal_ok!(42);
+///     Ok(value)
+/// };
+/// assert_eq!(value, 42);
+/// ```
+#[proc_macro]
+pub fn fatal_ok(input: TokenStream) -> TokenStream {
+    let tokens: TokenStream2 = input.into();
+
+    // Invocations within a fatal body have been marked by the enclosing macro,
+    // along with whether the body returns a `Flow`.
+    let parser = |input: ParseStream| -> syn::Result<Option<(bool, TokenStream2)>> {
+        if input.peek(syn::Token![@]) {
+            input.parse::<syn::Token![@]>()?;
+            let marker: syn::Ident = input.parse()?;
+            if marker == body::FATAL_BODY_MARKER || marker == body::FATAL_FLOW_MARKER {
+                return Ok(Some((marker == body::FATAL_FLOW_MARKER, input.parse()?)));
+            }
+        }
+        Ok(None)
+    };
+
+    match syn::parse::Parser::parse2(parser, tokens.clone()) {
+        Ok(Some((flow, value))) => {
+            let value: TokenStream2 = if value.is_empty() { quote! { () } } else { value };
+            let value: TokenStream2 = if flow { quote! { ::impass::__private::Flow::Value(#value) } } else { value };
+            quote! { return ::core::result::Result::Ok(#value) }
+        },
+        _ => syn::Error::new_spanned(
+            quote! { fatal_ok!(#tokens) },
+            "`fatal_ok!` can only be used directly within a `fatal!` block or a `#[fatal_fn]` function"
+        ).to_compile_error(),
+    }.into()
+}
+
+
+/// Handles the parsing of the `fatal!` macro's input.
+struct FatalBlock {
+    stmts:     Vec<syn::Stmt>,
+    attrs:     Attrs,
+    returns:   Option<syn::Type>,
+    asyncness: bool,
+    in_fn:     bool,
+}
+
+/// The tokens `fatal_fn` prepends to the body it wraps in `fatal!`, followed
+/// by the declared return type of the function in parentheses.
+const RETURN_TYPE_MARKER: &str = "__impass_returns";
+
+/// The tokens `fatal_fn` prepends to the body of an `async fn` it wraps in
+/// `fatal!`.
+const ASYNC_MARKER: &str = "__impass_async";
+
+/// The tokens `fatal_fn` prepends to every body it wraps in `fatal!`, within
+/// which `return` keeps returning the function's `Result`.
+const FN_MARKER: &str = "__impass_fn";
+
+impl FatalBlock {
+
+    /// Generates the code evaluating the block.
+    fn expand(self) -> TokenStream2 {
+        let FatalBlock {
+            stmts,
+            attrs,
+            returns,
+            asyncness,
+            in_fn,
+        } = self;
+
+        // A block which is meant to initialize a static can never work, so
+        // say so up front rather than letting the expansion fail obscurely.
+        if let Some(span) = attrs.static_init {
+            return syn::Error::new(
+                span,
+                "`fatal!` blocks cannot be evaluated in a `static` or `const` initializer; \
+                 use `impass::fatal_lazy!` to initialize the static on first access instead"
+            ).to_compile_error();
+        }
+
+        // Catch the bodies which can't end in a `Result` before they are
+        // expanded into errors about the closure.
+        if let Err(e) = body::check_tail(&stmts, proc_macro2::Span::call_site()) {
+            return e.to_compile_error();
+        }
+
+        // The `return`, `break` and `continue` leaving a block can't leave the
+        // closure it is placed in, so they are returned from it and carried out
+        // right after. The body of a function returns its `Result` instead.
+        let mut stmts:   Vec<syn::Stmt> = stmts;
+        let     escapes: body::Escapes  = if in_fn {
+            body::Escapes::default()
+        } else {
+            match body::escape(&mut stmts) {
+                Ok(escapes) => escapes,
+                Err(e)      => return e.to_compile_error(),
+            }
+        };
+
+        // Apply the statement-level rewrites, such as `#[ctx("...")]`.
+        let mut stmts: Vec<syn::Stmt> = match body::rewrite(stmts, &attrs, !escapes.is_empty()) {
+            Ok(stmts) => stmts,
+            Err(e)    => return e.to_compile_error(),
+        };
+
+        // A block made up of a lone call, as in `fatal!(do_thing())`, may fail
+        // with any error converting into an `anyhow::Error`.
+        if !in_fn && escapes.is_empty() && attrs.error.is_none() {
+            body::adapt_lone_result(&mut stmts);
+        }
+
+        // The block is placed inside a closure that returns a `Result`. Its
+        // success type is the return type of the `fatal_fn` function, if any,
+        // so that the values returned coerce just as they would without it.
+        let success: TokenStream2 = match &returns {
+            Some(returns) => returns.to_token_stream(),
+            None          => quote! { _ },
+        };
+        let asyncness: bool         = asyncness || body::uses_await(&stmts);
+        let result:    TokenStream2 = if escapes.is_empty() {
+            try_scope(&stmts, &success, &error_type(&attrs), asyncness)
+        } else {
+            escape_scope(&stmts, &success, &error_type(&attrs), asyncness, &escapes)
+        };
+        let result: TokenStream2 = ensure_ok(result, &attrs.assertions);
+
+        // We generate an unwrap_or_else that formats the error and panics. A
+        // recoverable block returns the error from the enclosing function
+        // instead, with the reason attached as context. The `return` is
+        // spanned to the attribute, so that an enclosing function which can't
+        // return the error is reported there.
+        let error: syn::Ident   = syn::Ident::new("error", proc_macro2::Span::mixed_site());
+        let value: TokenStream2 = match attrs.recoverable {
+            Some(span) => {
+                let ok:           syn::Ident   = syn::Ident::new("value", proc_macro2::Span::mixed_site());
+                let reason_value: TokenStream2 = reason_value(&attrs);
+                let propagate:    TokenStream2 = quote_spanned! { span =>
+                    return ::core::result::Result::Err(::core::convert::Into::into(#error.context(#reason_value)))
+                };
+                quote! {
+                    match #result {
+                        ::core::result::Result::Ok(#ok)     => #ok,
+                        ::core::result::Result::Err(#error) => #propagate,
+                    }
+                }
+            },
+            None => {
+                let failure: TokenStream2 = failure_path(&error, &attrs);
+                quote! {
+                    #result.unwrap_or_else(|#error| {
+                        #failure
+                    })
+                }
+            },
+        };
+
+        // The style issues of the reason are reported before anything else.
+        let warnings: TokenStream2 = reason_warnings(&attrs);
+        let value:    TokenStream2 = if warnings.is_empty() {
+            value
+        } else {
+            quote! {
+                {
+                    #warnings
+                    #value
+                }
+            }
+        };
+
+        // Route the value through a `#[must_use]` function when requested, so
+        // that discarding it warns just like any other `#[must_use]` call.
+        if attrs.must_use.is_none() {
+            return value;
+        }
+        quote! {
+            {
+                #[must_use = "the value of a `fatal!` block marked `#![must_use]` should be used"]
+                #[inline(always)]
+                fn must_use<T>(value: T) -> T {
+                    value
+                }
+                must_use(#value)
+            }
+        }
+    }
+}
+
+/// The error type of a body, which is `anyhow::Error` unless the `error`
+/// attribute says otherwise.
+fn error_type(attrs: &Attrs) -> TokenStream2 {
+    match &attrs.error {
+        Some(error) => error.to_token_stream(),
+        None        => quote! { ::impass::__private::anyhow::Error },
+    }
+}
+
+/// Generates the code evaluating `stmts` as the body of a closure returning
+/// `Result<success, error>`, so that `?` and `return` within them end
+/// up there.
+///
+/// A body which may `.await`, such as the body of an `async fn`, can't be
+/// placed in a closure. It is placed in an async block instead, which is
+/// awaited right away. The
+/// block's output can't be written down, so it is pinned by passing the
+/// block through a function expecting a future of the right `Result`.
+fn try_scope(stmts: &[syn::Stmt], success: &TokenStream2, error: &TokenStream2, asyncness: bool) -> TokenStream2 {
+    if !asyncness {
+        return quote! {
+            (|| -> ::core::result::Result<#success, #error> {
+                #(#stmts)*
+            })()
+        };
+    }
+    quote! {
+        {
+            #[inline(always)]
+            fn __impass_try_scope<T, F>(future: F) -> F
+            where
+                F: ::core::future::Future<Output = ::core::result::Result<T, #error>>,
+            {
+                future
+            }
+            __impass_try_scope::<#success, _>(async {
+                #(#stmts)*
+            }).await
+        }
+    }
+}
+
+/// Generates the code evaluating `stmts` just like `try_scope`, for a body
+/// whose escaping control flow has been rewritten by `body::escape`.
+///
+/// The closure returns a `Flow` wrapping the success value, or the `return`,
+/// `break` or `continue` which ended the body, which is then carried out
+/// outside of the closure. What remains is the `Result` of the body.
+fn escape_scope(
+    stmts:     &[syn::Stmt],
+    success:   &TokenStream2,
+    error:     &TokenStream2,
+    asyncness: bool,
+    escapes:   &body::Escapes,
+) -> TokenStream2 {
+
+    // A body which never returns leaves the type of the returned value to be
+    // pinned down here, and the arm returning it unreachable.
+    let value:    syn::Ident   = syn::Ident::new("value", proc_macro2::Span::mixed_site());
+    let (returned, returns): (TokenStream2, TokenStream2) = if escapes.returns {
+        (quote! { _ }, quote! { return #value })
+    } else {
+        (quote! { ::core::convert::Infallible }, quote! { match #value {} })
+    };
+
+    // Only the tail is wrapped, as a body ending in a statement diverges, and
+    // wrapping it would warn about the unreachable call.
+    let mut stmts: Vec<syn::Stmt> = stmts.to_vec();
+    if let Some(syn::Stmt::Expr(tail, None)) = stmts.last_mut() {
+        *tail = syn::parse_quote! {
+            ::core::result::Result::map(#tail, ::impass::__private::Flow::Value)
+        };
+    }
+
+    let flow:   TokenStream2 = quote! { ::impass::__private::Flow<#success, #returned> };
+    let result: TokenStream2 = try_scope(&stmts, &flow, error, asyncness);
+    let jumps = escapes.jumps.iter().enumerate().map(|(index, jump)| quote! {
+        ::core::result::Result::Ok(::impass::__private::Flow::Jump(#index)) => #jump,
+    });
+    let error: syn::Ident = syn::Ident::new("error", proc_macro2::Span::mixed_site());
+    quote! {
+        match #result {
+            ::core::result::Result::Ok(::impass::__private::Flow::Value(#value))  => ::core::result::Result::Ok(#value),
+            ::core::result::Result::Ok(::impass::__private::Flow::Return(#value)) => #returns,
+            #(#jumps)*
+            ::core::result::Result::Ok(::impass::__private::Flow::Jump(_)) => ::core::unreachable!(),
+            ::core::result::Result::Err(#error) => ::core::result::Result::Err(#error),
+        }
+    }
+}
+
+/// The number of characters of a value's `Debug` rendering kept in the error
+/// raised by a failed `ensure_ok`.
+const ENSURE_OK_DEBUG_LIMIT: usize = 256;
+
+/// Generates the code checking the success value of `result` against every
+/// `ensure_ok` assertion, in order. The first one to fail turns the value into
+/// an error carrying the assertion's message and the value's (truncated)
+/// `Debug` rendering, which then takes the usual failure path.
+fn ensure_ok(result: TokenStream2, assertions: &[attrs::Assertion]) -> TokenStream2 {
+    if assertions.is_empty() {
+        return result;
+    }
+
+    // The predicates are handed to a function rather than called directly,
+    // so that the type of their parameter can be inferred.
+    let value: syn::Ident = syn::Ident::new("value", proc_macro2::Span::mixed_site());
+    let checks: TokenStream2 = assertions.iter().map(|attrs::Assertion { predicate, message }| quote! {
+        if !__impass_ensure_ok(&#value, #predicate) {
+            return ::core::result::Result::Err(::impass::__private::anyhow::Error::msg(::impass::__private::std::format!(
+                "{} (value: {})",
+                ::impass::__private::render_guarded(&::core::format_args!(#message)),
+                __impass_debug(&#value)
+            )));
+        }
+    }).collect();
+    quote! {
+        ::core::result::Result::and_then(#result, |#value| {
+            #[inline(always)]
+            fn __impass_ensure_ok<T>(value: &T, predicate: impl ::core::ops::FnOnce(&T) -> bool) -> bool {
+                predicate(value)
+            }
+            fn __impass_debug<T: ::core::fmt::Debug>(value: &T) -> ::impass::__private::std::string::String {
+                let mut debug: ::impass::__private::std::string::String = ::impass::__private::render_guarded(&::core::format_args!("{:?}", value));
+                if let ::core::option::Option::Some((end, _)) = debug.char_indices().nth(#ENSURE_OK_DEBUG_LIMIT) {
+                    debug.truncate(end);
+                    debug.push_str("...");
+                }
+                debug
+            }
+            #checks
+            ::core::result::Result::Ok(#value)
+        })
+    }
+}
+
+impl Parse for FatalBlock {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+
+        // Parse the markers passed along by `fatal_fn`, if any.
+        let mut returns:   Option<syn::Type> = None;
+        let mut asyncness: bool              = false;
+        let mut in_fn:     bool              = false;
+        while input.peek(syn::Token![@]) {
+            let fork: ParseStream = &input.fork();
+            fork.parse::<syn::Token![@]>()?;
+            match fork.parse::<syn::Ident>().ok() {
+                Some(marker) if marker == RETURN_TYPE_MARKER => {
+                    input.parse::<syn::Token![@]>()?;
+                    input.parse::<syn::Ident>()?;
+
+                    let content;
+                    syn::parenthesized!(content in input);
+                    returns = Some(content.parse()?);
+                },
+                Some(marker) if marker == ASYNC_MARKER => {
+                    input.parse::<syn::Token![@]>()?;
+                    input.parse::<syn::Ident>()?;
+                    asyncness = true;
+                },
+                Some(marker) if marker == FN_MARKER => {
+                    input.parse::<syn::Token![@]>()?;
+                    input.parse::<syn::Ident>()?;
+                    in_fn = true;
+                },
+                _ => break,
+            }
+        }
+
+        // Parse the inner attributes, such as `reason`.
+        let attrs: Attrs = Attrs::parse_inner(input, MacroKind::Fatal)?;
+
+        // Return the parsed block.
+        Ok(FatalBlock {
+            stmts: input.call(syn::Block::parse_within)?,
+            attrs,
+            returns,
+            asyncness,
+            in_fn,
+        })
+    }
+}
+
+/// The input of `fatal!`, which is either a block or the expression form.
+enum FatalInput {
+    Block(FatalBlock),
+    Expr(FatalExpr),
+}
+
+impl FatalInput {
+
+    /// Generates the code evaluating the input.
+    fn expand(self) -> TokenStream2 {
+        match self {
+            FatalInput::Block(block) => block.expand(),
+            FatalInput::Expr(expr)   => expr.expand(),
+        }
+    }
+}
+
+impl Parse for FatalInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+
+        // Markers and inner attributes only ever start a block.
+        if input.peek(syn::Token![@]) || (input.peek(syn::Token![#]) && input.peek2(syn::Token![!])) {
+            return Ok(FatalInput::Block(input.parse()?));
+        }
+
+        // Only a message following the expression makes the expression form.
+        // A lone expression is expanded as a block, whose return type names
+        // the error type of `fatal! { get().map_err(Into::into) }`.
+        let fork: ParseStream = &input.fork();
+        if fork.parse::<syn::Expr>().is_ok() && fork.peek(syn::Token![,]) {
+            return Ok(FatalInput::Expr(input.parse()?));
+        }
+        Ok(FatalInput::Block(input.parse()?))
+    }
+}
+
+
+/// The expression form of `fatal!`: `fatal!(expr)` or
+/// `fatal!(expr, "...", args...)`.
+struct FatalExpr {
+    expr:  syn::Expr,
+    attrs: Attrs,
+}
+
+impl FatalExpr {
+
+    /// Generates the code evaluating the expression.
+    fn expand(self) -> TokenStream2 {
+        let FatalExpr {
+            expr,
+            attrs,
+        } = self;
+
+        // The error is converted into an `anyhow::Error` before it is
+        // reported, just as `?` would within a block.
+        let error:   syn::Ident   = syn::Ident::new("error", proc_macro2::Span::mixed_site());
+        let failure: TokenStream2 = failure_path(&error, &attrs);
+        quote! {
+            ::core::result::Result::unwrap_or_else(#expr, |#error| {
+                let #error: ::impass::__private::anyhow::Error = ::core::convert::Into::into(#error);
+                #failure
+            })
+        }
+    }
+}
+
+impl Parse for FatalExpr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let expr: syn::Expr = input.parse()?;
+        if input.is_empty() {
+            return Ok(FatalExpr { expr, attrs: Attrs::default() });
+        }
+
+        // The message is attached as context, just like a reason.
+        let comma:   syn::Token![,]                         = input.parse()?;
+        let message: Punctuated<syn::Expr, syn::Token![,]> = Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated(input)?
+            .into_iter()
+            .map(attrs::ungroup)
+            .collect();
+        let reason: Reason = Reason::from_format(message).map_err(|message| syn::Error::new_spanned(
+            message.first().map_or(comma.to_token_stream(), ToTokens::to_token_stream),
+            "expected a message after the expression, written as `fatal!(expr, \"...\", args...)`"
+        ))?;
+        Ok(FatalExpr {
+            expr,
+            attrs: Attrs {
+                reason: Some(reason),
+                ..Attrs::default()
+            },
+        })
+    }
+}
+
+/// An attribute macro that wraps a function's body in the `fatal!` macro.
+///
+/// This macro allows you to specify an optional reason for the fatal error
+/// using the attribute argument. Arguments share their syntax with the inner
+/// attributes of `fatal!`, so both `reason = "..."` and `reason("...")` are
+/// accepted here, just like in a `fatal!` block.
+///
+/// ### Example
+/// ```rust
+/// use thiserror::Error;
+/// use impass::{fatal, fatal_fn};
+/// 
+/// // Declare an error type for demonstration purposes.
+/// #[derive(Error, Debug)]
+/// pub enum MyError {
+///     #[error("This operation failed")]
+///     OperationFailed
+/// }
+///
+/// // A fallible function for demonstration.
+/// fn might_fail(value: i32) -> Result<i32, MyError> {
+///     if value < 10 {
+///         Err(MyError::OperationFailed)
+///     } else {
+///         Ok(value * 2)
+///     }
+/// }
+/// 
+/// // Using the `fatal_fn` macro to wrap a function.
+/// #[fatal_fn(reason = "Critical failure in function execution")]
+/// fn example_function() -> i32 {
+///     let value = might_fail(15)?;
+///     Ok(value)
+/// }
+/// 
+/// // The above function is equivalent to:
+/// fn example_function_actual() -> i32 {
+///     fatal! {
+///         #![reason("Critical failure in function execution")]
+///         let value = might_fail(15)?;
+///         Ok(value)
+///     }
+/// }
+/// ```
+///
+/// ### Formatted Reasons
+///
+/// A reason may be followed by format arguments in either form, which can
+/// refer to the function's parameters:
+///
+/// ```rust,should_panic
+/// use impass::fatal_fn;
+///
+/// #[fatal_fn(reason = "Failed to load the user {}", id)]
+/// fn load_user(id: u32) -> String {
+///     let user: String = std::fs::read_to_string(format!("/var/users/{id}"))?;
+///     Ok(user)
+/// }
+///
+/// load_user(42);
+/// ```
+///
+/// In the name-value form, the arguments extend up until the next attribute,
+/// that is anything of the form `name = ...` or the name of a known attribute
+/// such as `no_catch`. Use the list form, `reason("...", args...)`, to pass an
+/// argument which would be mistaken for one.
+///
+/// A reason which is expensive to compute may be given as a closure through
+/// `reason_with = || ...` instead, which is only invoked once the function has
+/// failed, and may borrow its parameters as well:
+///
+/// ```rust
+/// use impass::fatal_fn;
+///
+/// #[fatal_fn(reason_with = || format!("Invalid configuration:\n{}", lines.join("\n")))]
+/// fn parse_port(lines: &[&str]) -> u16 {
+///     let port: u16 = lines[0].trim_start_matches("listen = ").parse()?;
+///     Ok(port)
+/// }
+///
+/// assert_eq!(parse_port(&["listen = 8080"]), 8080);
+/// ```
+///
+/// ### Async Functions
+///
+/// The body of an `async fn` is placed in an async block rather than a
+/// closure, so it may `.await` and borrow its parameters as usual, while `?`
+/// still ends up in the failure path. The function keeps returning the inner
+/// value, and a fallible sibling is generated as an `async fn` as well.
+///
+/// ```rust
+/// use impass::fatal_fn;
+///
+/// async fn read_port() -> Result<String, std::io::Error> {
+///     Ok("8080".to_string())
+/// }
+///
+/// #[fatal_fn(reason = "The port of {} is invalid", service)]
+/// async fn port(service: &str) -> u16 {
+///     let port: u16 = read_port().await?.parse()?;
+///     Ok(port)
+/// }
+/// ```
+///
+/// ### Return Types
+///
+/// The body is checked against the declared return type of the function, so
+/// the values it returns coerce just as they would without the attribute,
+/// e.g. different arms returning `Box::new(...)` for a `Box<dyn Trait>`:
+///
+/// ```rust
+/// use impass::fatal_fn;
+///
+/// #[fatal_fn]
+/// fn make_writer(to_stderr: bool) -> Box<dyn std::io::Write> {
+///     match to_stderr {
+///         true  => Ok(Box::new(std::io::stderr())),
+///         false => Ok(Box::new(std::io::stdout())),
+///     }
+/// }
+/// ```
+///
+/// An `impl Trait` can't be named within the body, so it is inferred from the
+/// body instead. As without the attribute, every path must then produce the
+/// same concrete type:
+///
+/// ```rust,compile_fail
+/// use impass::fatal_fn;
+///
+/// #[fatal_fn]
+/// fn make_writer(to_stderr: bool) -> impl std::io::Write {
+///     match to_stderr {
+///         true  => Ok(std::io::stderr()),
+///         false => Ok(std::io::stdout()),
+///     }
+/// }
+/// ```
+///
+/// ### Result Signatures
+///
+/// With `unwrap_signature`, the function is instead declared as returning the
+/// `Result` its body produces, and the attribute rewrites the signature to
+/// return the success type. The error type of the signature is the one the
+/// body fails with, just as if it had been given through `error`, unless it is
+/// `anyhow::Error`.
+///
+/// ```rust
+/// use impass::fatal_fn;
+///
+/// #[fatal_fn(unwrap_signature, reason = "The port is invalid")]
+/// fn parse_port(input: &str) -> Result<u16, std::num::ParseIntError> {
+///     let port: u16 = input.parse()?;
+///     Ok(port)
+/// }
+///
+/// #[fatal_fn(unwrap_signature)]
+/// fn double(input: &str) -> anyhow::Result<u32> {
+///     let value: u32 = input.parse()?;
+///     Ok(value * 2)
+/// }
+///
+/// let port: u16 = parse_port("8080");
+/// assert_eq!(port, 8080);
+/// assert_eq!(double("21"), 42);
+/// ```
+///
+/// `Result<T, E>`, `std::result::Result<T, E>` and `anyhow::Result<T>` are
+/// recognized. A macro can't resolve a type alias, so any other return type is
+/// rejected, even one which stands for a `Result`:
+///
+/// ```rust,compile_fail
+/// use impass::fatal_fn;
+///
+/// #[fatal_fn(unwrap_signature)]
+/// fn read_config() -> std::io::Result<String> {
+///     let config: String = std::fs::read_to_string("config.toml")?;
+///     Ok(config)
+/// }
+/// ```
+///
+/// ### Fallible Sibling
+///
+/// Passing `also_fallible = "name"` additionally emits a function called
+/// `name` with the same signature and body, but which returns
+/// `anyhow::Result<T>` instead of unwrapping. This lets library consumers
+/// choose between the fatal and the fallible entry point.
+///
+/// ```rust
+/// use impass::fatal_fn;
+///
+/// #[fatal_fn(also_fallible = "try_parse_port")]
+/// fn parse_port(input: &str) -> u16 {
+///     let port: u16 = input.parse()?;
+///     Ok(port)
+/// }
+///
+/// assert_eq!(parse_port("8080"), 8080);
+/// assert!(try_parse_port("not a port").is_err());
+/// ```
+#[proc_macro_attribute]
+pub fn fatal_fn(args: TokenStream, input: TokenStream) -> TokenStream {
+
+    // Parse the attribute arguments and the function.
+    let input_fn: syn::ItemFn = parse_macro_input!(input as syn::ItemFn);
+    let args:     Attrs       = match Attrs::parse_args(args.into(), MacroKind::FatalFn) {
+        Ok(args) => args,
+        Err(e)   => return e.to_compile_error().into(),
+    };
+
+    match expand_fatal_fn(input_fn, &args) {
+        Ok(output) => output.into(),
+        Err(e)     => e.to_compile_error().into(),
+    }
+}
+
+/// Applies the `fatal_fn` transformation to a function, returning it along
+/// with its fallible sibling if one was requested.
+///
+/// This is shared by `#[fatal_fn]` and `fatal_mod!`.
+fn expand_fatal_fn(mut input_fn: syn::ItemFn, args: &Attrs) -> syn::Result<TokenStream2> {
+
+    // A function declared as returning its `Result` is rewritten to return the
+    // success type, with the error type of the signature given to the block.
+    let unwrapped: Attrs;
+    let args:      &Attrs = match args.unwrap_signature {
+        Some(_) => {
+            unwrapped = Attrs { error: unwrap_signature(&mut input_fn.sig)?, ..args.clone() };
+            if let (Some(error), Some(assertion)) = (&unwrapped.error, unwrapped.assertions.first()) {
+                let mut e: syn::Error = syn::Error::new_spanned(
+                    &assertion.predicate,
+                    "`ensure_ok` can't be combined with a signature whose error type isn't `anyhow::Error`, as it raises one"
+                );
+                e.combine(syn::Error::new_spanned(error, "the error type is given here"));
+                return Err(e);
+            }
+            &unwrapped
+        },
+        None => args,
+    };
+
+    // Build the fallible sibling before the body is rewritten, so that both
+    // functions are generated from the very same statements.
+    let sibling: Option<syn::ItemFn> = match &args.also_fallible {
+        Some(name) => Some(fallible_sibling(&input_fn, name, args)?),
+        None       => None,
+    };
+
+    // Get the original function body, and the attributes to forward.
+    let original_body: &[syn::Stmt]  = &input_fn.block.stmts;
+    let inner_attrs:   TokenStream2 = args.to_inner_attrs();
+
+    // Pass the declared return type along, so that it is known while the body
+    // is type checked. An `impl Trait` can't be named within the body, so it
+    // is left to inference.
+    let marker:  syn::Ident           = syn::Ident::new(RETURN_TYPE_MARKER, proc_macro2::Span::call_site());
+    let returns: Option<TokenStream2> = match &input_fn.sig.output {
+        syn::ReturnType::Default                       => Some(quote! { () }),
+        syn::ReturnType::Type(_, ty) if names_impl(ty) => None,
+        syn::ReturnType::Type(_, ty)                   => Some(ty.to_token_stream()),
+    };
+    let mut markers: TokenStream2 = match returns {
+        Some(returns) => quote! { @#marker(#returns) },
+        None          => TokenStream2::new(),
+    };
+
+    // A `return` within the body returns the function's `Result`, rather than
+    // from the function the block would be placed in.
+    let marker: syn::Ident = syn::Ident::new(FN_MARKER, proc_macro2::Span::call_site());
+    markers.extend(quote! { @#marker });
+
+    // The body of an `async fn` may `.await`, so it must stay asynchronous.
+    if input_fn.sig.asyncness.is_some() {
+        let marker: syn::Ident = syn::Ident::new(ASYNC_MARKER, proc_macro2::Span::call_site());
+        markers.extend(quote! { @#marker });
+    }
+
+    // Construct the new body wrapped in the `fatal!` macro. The invocation is
+    // spanned to the `fn` keyword, so that the failure is located at the
+    // function item rather than at the attribute.
+    let new_body: TokenStream2 = quote_spanned! { input_fn.sig.fn_token.span =>
+        ::impass::fatal! {
+            #markers
+            #inner_attrs
+            #(#original_body)*
+        }
+    };
+
+    // Replace the function's body with the new wrapped body.
+    input_fn.block = parse2(quote! { { #new_body } })
+        .expect("Failed to parse the new body into a block.");
+
+    // Return the modified function, followed by its fallible sibling if one
+    // was requested.
+    let mut output: TokenStream2 = input_fn.to_token_stream();
+    if let Some(sibling) = sibling {
+        sibling.to_tokens(&mut output);
+    }
+    Ok(output)
+}
+
+
+/// Rewrites the declared `Result<T, E>` of a function given `unwrap_signature`
+/// to return `T`, returning `E` unless it is an `anyhow::Error`, which is
+/// what the block fails with by default.
+///
+/// Only `Result<T, E>`, its paths in `std` and `core`, and `anyhow::Result<T>`
+/// are recognized, as a type alias can't be resolved by a macro.
+fn unwrap_signature(sig: &mut syn::Signature) -> syn::Result<Option<syn::Type>> {
+    let syn::ReturnType::Type(arrow, ty) = &sig.output else {
+        return Err(syn::Error::new_spanned(
+            &sig.ident,
+            "`unwrap_signature` expects the function to be declared as returning a `Result<T, E>`"
+        ));
+    };
+    // Anything but a path can't be a `Result`, whereas a path to a generic
+    // type might be an alias of one.
+    let path: &syn::Path = match &**ty {
+        syn::Type::Path(ty) if ty.qself.is_none() => &ty.path,
+        _ => return Err(syn::Error::new_spanned(
+            ty,
+            "`unwrap_signature` expects the function to be declared as returning a `Result<T, E>`"
+        )),
+    };
+
+    // The path leading up to `Result` tells a `Result` from an alias of it.
+    let prefix: Vec<String>     = path.segments.iter().rev().skip(1).rev().map(|segment| segment.ident.to_string()).collect();
+    let types:  Vec<&syn::Type> = match &path.segments.last().expect("a path has a segment").arguments {
+        syn::PathArguments::AngleBracketed(args) => args.args.iter()
+            .map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _                              => None,
+            })
+            .collect::<Option<Vec<&syn::Type>>>()
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let is_result: bool = path.segments.last().is_some_and(|segment| segment.ident == "Result");
+    let in_std:    bool = prefix.is_empty() || prefix == ["std", "result"] || prefix == ["core", "result"];
+    let (success, error): (&syn::Type, Option<syn::Type>) = match types.as_slice() {
+        [success, error] if is_result && in_std => {
+            (success, if is_anyhow_error(error) { None } else { Some((*error).clone()) })
+        },
+        [success] if is_result && prefix == ["anyhow"] => (success, None),
+        _ if !is_result && types.is_empty() => return Err(syn::Error::new_spanned(
+            ty,
+            "`unwrap_signature` expects the function to be declared as returning a `Result<T, E>`"
+        )),
+        _ => return Err(syn::Error::new_spanned(
+            ty,
+            format!(
+                "`unwrap_signature` can't see through `{}`, which isn't `Result<T, E>` or `anyhow::Result<T>`; \
+                 if it is a type alias, spell out the `Result<T, E>` it stands for",
+                ty.to_token_stream().to_string().replace(' ', "")
+            )
+        )),
+    };
+
+    // A unit success type is left implicit, just like it would be written.
+    sig.output = match success {
+        syn::Type::Tuple(tuple) if tuple.elems.is_empty() => syn::ReturnType::Default,
+        _                                                 => syn::ReturnType::Type(*arrow, Box::new(success.clone())),
+    };
+    Ok(error)
+}
+
+/// Whether a type is spelled as `anyhow::Error`.
+fn is_anyhow_error(ty: &syn::Type) -> bool {
+    let syn::Type::Path(ty) = ty else {
+        return false;
+    };
+    let segments: Vec<String> = ty.path.segments.iter().map(|segment| segment.ident.to_string()).collect();
+    ty.qself.is_none() && segments == ["anyhow", "Error"]
+}
+
+
+/// Whether a type contains an `impl Trait`, anywhere within it.
+fn names_impl(ty: &syn::Type) -> bool {
+    fn scan(tokens: TokenStream2) -> bool {
+        tokens.into_iter().any(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) => ident == "impl",
+            proc_macro2::TokenTree::Group(group) => scan(group.stream()),
+            _                                    => false,
+        })
+    }
+    scan(ty.to_token_stream())
+}
+
+
+/// Builds the fallible counterpart of a `fatal_fn` function, as requested
+/// through `also_fallible = "name"`.
+///
+/// The sibling keeps the original signature (generics, `async`, receiver and
+/// parameters) but is renamed and returns `anyhow::Result<T>`, where `T` is
+/// the declared return type of the original function. With an `error`
+/// argument, it returns a `Result` of that error type instead. Its body is
+/// the original, unwrapped body.
+fn fallible_sibling(input_fn: &syn::ItemFn, name: &syn::LitStr, args: &Attrs) -> syn::Result<syn::ItemFn> {
+
+    // The new identifier is spanned on the literal, so any collision reported
+    // by the compiler points back at the attribute argument.
+    let ident: syn::Ident = name.parse()
+        .map_err(|_| syn::Error::new(name.span(), "`also_fallible` must name a valid function identifier"))?;
+    if ident == input_fn.sig.ident {
+        return Err(syn::Error::new(
+            name.span(),
+            format!("`also_fallible` cannot reuse the name of the annotated function `{ident}`")
+        ));
+    }
+
+    // Adjust the return type to be the `Result` the body actually produces.
+    let success: TokenStream2 = match &input_fn.sig.output {
+        syn::ReturnType::Default    => quote! { () },
+        syn::ReturnType::Type(_, t) => t.to_token_stream(),
+    };
+
+    let mut sig: syn::Signature = input_fn.sig.clone();
+    sig.ident  = ident;
+    sig.output = match &args.error {
+        Some(error) => parse2(quote! { -> ::core::result::Result<#success, #error> })?,
+        None        => parse2(quote! { -> ::impass::__private::anyhow::Result<#success> })?,
+    };
+
+    let mut block: syn::Block = (*input_fn.block).clone();
+    block.stmts = body::rewrite(block.stmts, args, false)?;
+
+    // The success value is checked just as it is by the fatal function, except
+    // that a failed check is returned as well.
+    if !args.assertions.is_empty() {
+        let stmts:   &[syn::Stmt]  = &block.stmts;
+        let success: TokenStream2 = match &input_fn.sig.output {
+            syn::ReturnType::Type(_, ty) if names_impl(ty) => quote! { _ },
+            _                                              => success,
+        };
+        let result:  TokenStream2 = try_scope(stmts, &success, &error_type(args), input_fn.sig.asyncness.is_some());
+        let checked: TokenStream2 = ensure_ok(result, &args.assertions);
+        block = syn::parse_quote! {{ #checked }};
+    }
+
+    let original: &syn::Ident = &input_fn.sig.ident;
+    let doc:      String      = format!(
+        " Fallible variant of [`{original}`], generated by `#[fatal_fn(also_fallible = ...)]`.\n\n\
+         Returns the error instead of treating it as fatal."
+    );
+
+    // The sibling is generated whether or not it ends up being called, so
+    // leaving it unused must not warn.
+    Ok(syn::ItemFn {
+        attrs: vec![
+            syn::parse_quote! { #[doc = #doc] },
+            syn::parse_quote! { #[allow(dead_code)] },
+        ],
+        vis:   input_fn.vis.clone(),
+        sig,
+        block: Box::new(block),
+    })
+}
+
+
+/// A macro that applies the `fatal_fn` transformation to every free function
+/// of a module.
+///
+/// Inner attribute macros on modules aren't stable, so the module is passed
+/// to `fatal_mod!` as a whole instead. Each function in it is rewritten as if
+/// it had been annotated with `#[fatal_fn]`, while every other item is emitted
+/// unchanged.
+///
+/// - `#[fatal_skip]` leaves a function (or a nested module) untouched.
+/// - `#[fatal_fn(...)]` on a function overrides its arguments, e.g. to give it
+///   a reason of its own.
+/// - Nested modules are only rewritten when the `#![recurse]` flag is given at
+///   the start of the invocation.
+///
+/// ### Example
+/// ```rust
+/// use impass::fatal_mod;
+///
+/// fatal_mod! {
+///     pub mod bootstrap {
+///         pub fn port() -> u16 {
+///             let port: u16 = "8080".parse()?;
+///             Ok(port)
+///         }
+///
+///         #[fatal_fn(reason = "The worker count must be a number")]
+///         pub fn workers() -> usize {
+///             let workers: usize = "4".parse()?;
+///             Ok(workers)
+///         }
+///
+///         #[fatal_skip]
+///         pub fn host() -> &'static str {
+///             "localhost"
+///         }
+///     }
+/// }
+///
+/// assert_eq!(bootstrap::port(), 8080);
+/// assert_eq!(bootstrap::workers(), 4);
+/// assert_eq!(bootstrap::host(), "localhost");
+/// ```
+#[proc_macro]
+pub fn fatal_mod(input: TokenStream) -> TokenStream {
+    let FatalMod { attrs, module } = parse_macro_input!(input as FatalMod);
+    match expand_fatal_mod(module, &attrs) {
+        Ok(module) => module.to_token_stream().into(),
+        Err(e)     => e.to_compile_error().into(),
+    }
+}
+
+/// The input of `fatal_mod!`: its attributes, followed by an inline module.
+struct FatalMod {
+    attrs:  Attrs,
+    module: syn::ItemMod,
+}
+
+impl Parse for FatalMod {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs:  Attrs        = Attrs::parse_inner(input, MacroKind::FatalMod)?;
+        let module: syn::ItemMod = input.parse()?;
+        if module.content.is_none() {
+            return Err(syn::Error::new_spanned(
+                &module,
+                "`fatal_mod!` expects an inline module, e.g. `mod name { ... }`"
+            ));
+        }
+        Ok(FatalMod { attrs, module })
+    }
+}
+
+/// Rewrites the functions of a module, recursing into nested modules if the
+/// `recurse` flag was given.
+fn expand_fatal_mod(mut module: syn::ItemMod, attrs: &Attrs) -> syn::Result<syn::ItemMod> {
+    let (brace, items): (syn::token::Brace, Vec<syn::Item>) = match module.content.take() {
+        Some(content) => content,
+        None          => return Ok(module),
+    };
+
+    let mut output: Vec<syn::Item> = Vec::with_capacity(items.len());
+    for item in items {
+        output.push(match item {
+            syn::Item::Fn(mut item_fn) => {
+                if take_attrs(&mut item_fn.attrs, "fatal_skip").is_empty() {
+                    syn::Item::Verbatim(expand_fatal_fn_item(item_fn)?)
+                } else {
+                    syn::Item::Fn(item_fn)
+                }
+            },
+            syn::Item::Mod(mut item_mod) => {
+                if take_attrs(&mut item_mod.attrs, "fatal_skip").is_empty() && attrs.recurse.is_some() {
+                    syn::Item::Mod(expand_fatal_mod(item_mod, attrs)?)
+                } else {
+                    syn::Item::Mod(item_mod)
+                }
+            },
+            other => other,
+        });
+    }
+
+    module.content = Some((brace, output));
+    Ok(module)
+}
+
+/// Applies the `fatal_fn` transformation to a function within `fatal_mod!`,
+/// taking its arguments from its own `#[fatal_fn(...)]` attribute if it has
+/// one.
+fn expand_fatal_fn_item(mut item_fn: syn::ItemFn) -> syn::Result<TokenStream2> {
+    let overrides: Vec<syn::Attribute> = take_attrs(&mut item_fn.attrs, "fatal_fn");
+    if overrides.len() > 1 {
+        return Err(syn::Error::new_spanned(&overrides[1], "a function may only have a single `fatal_fn` attribute"));
+    }
+
+    // A bare `#[fatal_fn]` has no arguments to parse.
+    let args: Attrs = match overrides.first() {
+        Some(attr) if matches!(attr.meta, syn::Meta::List(_)) => Attrs::parse_args(attr.parse_args()?, MacroKind::FatalFn)?,
+        _                                                     => Attrs::default(),
+    };
+    expand_fatal_fn(item_fn, &args)
+}
+
+/// Removes and returns the attributes whose path ends in `name`.
+fn take_attrs(attrs: &mut Vec<syn::Attribute>, name: &str) -> Vec<syn::Attribute> {
+    let (taken, rest): (Vec<syn::Attribute>, Vec<syn::Attribute>) = std::mem::take(attrs)
+        .into_iter()
+        .partition(|attr| attr.path().segments.last().is_some_and(|segment| segment.ident == name));
+    *attrs = rest;
+    taken
+}
+
+
+
+/// An attribute macro that applies the `fatal_fn` transformation to every
+/// method of an `impl` block.
+///
+/// Each method, associated functions included, is rewritten as if it had been
+/// annotated with `#[fatal_fn]` along with the arguments of `#[fatal_impl]`,
+/// such as a `reason` shared by all of them. Every other item is emitted
+/// unchanged.
+///
+/// - `#[fatal_skip]` leaves a method untouched.
+/// - A method with a `#[fatal_fn(...)]` attribute of its own is left to it,
+///   e.g. to give it a reason of its own.
+///
+/// Methods keep their receivers, generics and where clauses, and an
+/// `async fn` is wrapped just like it would be by `#[fatal_fn]`. As the
+/// arguments apply to every method, those which only make sense for a single
+/// one, `also_fallible` and `ensure_ok`, aren't accepted.
+///
+/// ### Example
+/// ```rust
+/// use impass::{fatal_fn, fatal_impl};
+///
+/// struct Config {
+///     port: u16,
+/// }
+///
+/// #[fatal_impl(reason = "The configuration is invalid")]
+/// impl Config {
+///     fn parse(text: &str) -> Config {
+///         Ok(Config { port: text.parse()? })
+///     }
+///
+///     fn set_port(&mut self, text: &str) {
+///         self.port = text.parse()?;
+///         Ok(())
+///     }
+///
+///     #[fatal_fn(reason = "The admin port is invalid")]
+///     fn admin_port(&self) -> u16 {
+///         Ok(self.port.checked_add(1).ok_or(anyhow::anyhow!("port overflow"))?)
+///     }
+///
+///     #[fatal_skip]
+///     fn port(&self) -> u16 {
+///         self.port
+///     }
+/// }
+///
+/// let mut config: Config = Config::parse("8080");
+/// config.set_port("9090");
+/// assert_eq!((config.port(), config.admin_port()), (9090, 9091));
+/// ```
+#[proc_macro_attribute]
+pub fn fatal_impl(args: TokenStream, input: TokenStream) -> TokenStream {
+
+    // Parse the attribute arguments and the `impl` block.
+    let item_impl: syn::ItemImpl = parse_macro_input!(input as syn::ItemImpl);
+    let args:      Attrs         = match Attrs::parse_args(args.into(), MacroKind::FatalImpl) {
+        Ok(args) => args,
+        Err(e)   => return e.to_compile_error().into(),
+    };
+
+    match expand_fatal_impl(item_impl, &args) {
+        Ok(item_impl) => item_impl.to_token_stream().into(),
+        Err(e)        => e.to_compile_error().into(),
+    }
+}
+
+/// Rewrites the methods of an `impl` block, leaving those marked
+/// `#[fatal_skip]` or with a `#[fatal_fn]` of their own alone.
+fn expand_fatal_impl(mut item_impl: syn::ItemImpl, args: &Attrs) -> syn::Result<syn::ItemImpl> {
+    for item in &mut item_impl.items {
+        let syn::ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let annotated: bool = method.attrs.iter()
+            .any(|attr| attr.path().segments.last().is_some_and(|segment| segment.ident == "fatal_fn"));
+        if !take_attrs(&mut method.attrs, "fatal_skip").is_empty() || annotated {
+            continue;
+        }
+
+        // Only the body is replaced, so that the method keeps everything a
+        // free function can't have, such as `default`.
+        let item_fn: syn::ItemFn = syn::ItemFn {
+            attrs: Vec::new(),
+            vis:   syn::Visibility::Inherited,
+            sig:   method.sig.clone(),
+            block: Box::new(method.block.clone()),
+        };
+        let expanded: syn::ItemFn = parse2(expand_fatal_fn(item_fn, args)?)?;
+        method.block = *expanded.block;
+    }
+    Ok(item_impl)
+}
+
+/// The method through which a site looks up the defaults of its module, as
+/// implemented by `fatal_defaults!` for `impass::__private::DefaultsProbe`.
+const DEFAULTS_METHOD: &str = "__impass_fatal_defaults";
+
+/// The trait `fatal_defaults!` declares the method in.
+const DEFAULTS_TRAIT: &str = "__ImpassFatalDefaults";
+
+/// An item macro which sets the default attributes of every fatal site in a
+/// module.
+///
+/// Attributes which would otherwise be repeated on every `fatal!` block and
+/// `#[fatal_fn]` function of a module can be given once instead, in the same
+/// form as the arguments of `fatal_fn`:
+///
+/// ```rust,should_panic
+/// mod storage {
+///     use impass::{fatal, fatal_defaults};
+///
+///     fatal_defaults! { deterministic, suggest("check that the disk isn't full") }
+///
+///     pub fn flush() {
+///         fatal! {
+///             #![reason("Failed to flush the journal")]
+///             std::fs::write("/", "journal")?;
+///             Ok(())
+///         }
+///     }
+/// }
+///
+/// storage::flush();
+/// ```
+///
+/// The defaults accept `suggest`, `deterministic`, and one of `no_catch`,
+/// `abort` and `exit`. A site falls back to them for whatever it doesn't give
+/// itself, and to the global settings (such as `IMPASS_DETERMINISTIC`) after
+/// that:
+///
+/// - A site given any of `no_catch`, `abort`, `exit` or `recoverable`
+///   terminates as it says, and the module's way of terminating is ignored.
+/// - A site given suggestions of its own only renders those.
+/// - A site is deterministic if either the site or the module asks for it.
+///
+/// ### Scope
+///
+/// The defaults are looked up through a private trait the macro declares,
+/// which is only in scope within the module it is placed in, so each module
+/// needs its own. A module without one, including a child module, keeps the
+/// plain behavior. The trait is private, but is still brought into scope by a
+/// glob import of the module, so a child module starting with `use super::*;`
+/// shares its parent's defaults unless it gives its own.
+///
+/// The macro may be placed anywhere in the module, but only once. The
+/// `FatalExt` methods are plain functions, and don't see the defaults.
+#[proc_macro]
+pub fn fatal_defaults(input: TokenStream) -> TokenStream {
+    let attrs: Attrs = match Attrs::parse_args(input.into(), MacroKind::FatalDefaults) {
+        Ok(attrs) => attrs,
+        Err(e)    => return e.to_compile_error().into(),
+    };
+
+    // The way of terminating is given by at most one of the attributes, as
+    // checked along with the others.
+    let terminate: TokenStream2 = if attrs.no_catch.is_some() {
+        quote! { ::impass::__private::Terminate::NoCatch }
+    } else if attrs.abort.is_some() {
+        quote! { ::impass::__private::Terminate::Abort }
+    } else if let Some(exit) = &attrs.exit {
+        quote! { ::impass::__private::Terminate::Exit(#exit) }
+    } else {
+        quote! { ::impass::__private::Terminate::Panic }
+    };
+    let deterministic: bool              = attrs.deterministic.is_some();
+    let suggestions:   Vec<TokenStream2> = attrs.suggestions.iter().map(|attrs::Suggestion { message, .. }| quote! {
+        ::impass::__private::render_guarded(&::core::format_args!(#message))
+    }).collect();
+
+    // Method resolution finds the trait's method on the probe itself before
+    // the fallback the probe dereferences to.
+    let method: syn::Ident = syn::Ident::new(DEFAULTS_METHOD, proc_macro2::Span::call_site());
+    let trait_: syn::Ident = syn::Ident::new(DEFAULTS_TRAIT, proc_macro2::Span::call_site());
+    quote! {
+        #[doc(hidden)]
+        #[allow(dead_code)]
+        trait #trait_ {
+            fn #method(&self) -> ::impass::__private::Defaults;
+        }
+
+        impl #trait_ for ::impass::__private::DefaultsProbe {
+            fn #method(&self) -> ::impass::__private::Defaults {
+                ::impass::__private::Defaults {
+                    terminate:     #terminate,
+                    deterministic: #deterministic,
+                    suggestions:   ::impass::__private::std::vec![#(#suggestions),*],
+                }
+            }
+        }
+    }.into()
+}
@@ -0,0 +1,77 @@
+//! Checks that `fatal!` and `#[fatal_fn]` expand and link within a
+//! `#![no_std]` crate, which depends on `impass` without its `std` feature.
+//!
+//! Built on its own, with `cargo build -p impass_no_std`, the crate gets the
+//! expansion reporting through `core::panic!`. Built along with the rest of the
+//! workspace, the features of `impass` are unified, so it gets the `std`
+//! expansion instead, which must compile within a `#![no_std]` crate all the
+//! same.
+
+#![no_std]
+
+use core::error::Error;
+use core::fmt::{self, Display};
+
+use impass::{fatal, fatal_fn, fatal_ok, fatal_unreachable};
+
+
+/// A reading of a sensor above the range it can measure.
+#[derive(Debug)]
+pub struct Overheated(pub u16);
+
+impl Display for Overheated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the reading {} is out of range", self.0)
+    }
+}
+
+impl Error for Overheated {}
+
+
+/// Converts a raw reading, in tenths of a degree, into degrees.
+pub fn celsius(raw: u16) -> Result<u16, Overheated> {
+    if raw >= 900 {
+        return Err(Overheated(raw));
+    }
+    Ok(raw / 10)
+}
+
+/// Reads a sensor, through an error type of its own and a formatted reason.
+#[fatal_fn(reason("Sensor {} failed", id), error(Overheated))]
+pub fn read_sensor(id: u8, raw: u16) -> u16 {
+    let celsius: u16 = celsius(raw)?;
+    Ok(celsius)
+}
+
+/// Averages the readings through `anyhow`, ending early on the first reading
+/// of zero.
+pub fn average(readings: &[u16]) -> u16 {
+    fatal! {
+        #![reason("Failed to average the readings")]
+        let first: &u16 = readings.first()?;
+        if *first == 0 {
+            fatal_ok!(0);
+        }
+
+        let mut total: u32 = 0;
+        for &raw in readings {
+            total += u32::from(celsius(raw)?);
+        }
+        let count: u32 = u32::try_from(readings.len())?;
+        Ok(u16::try_from(total / count)?)
+    }
+}
+
+/// Reads a sensor through the expression form, without a reason.
+pub fn read_unchecked(raw: u16) -> u16 {
+    fatal!(celsius(raw))
+}
+
+/// Names the unit of a sensor, of which there are only two.
+pub fn unit(id: u8) -> &'static str {
+    match id {
+        0 => "celsius",
+        1 => "fahrenheit",
+        _ => fatal_unreachable!("sensor {} doesn't exist", id),
+    }
+}
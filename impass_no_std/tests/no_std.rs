@@ -0,0 +1,57 @@
+//! Calls into the `#![no_std]` crate, whose failures end up in the panic
+//! handler of the standard library here.
+//!
+//! The crate is built with the `core::panic!` expansion on its own, and with
+//! the `std` one as part of the workspace, so only the parts of the message
+//! both share are matched.
+
+use std::panic;
+
+use impass_no_std::{average, read_sensor, read_unchecked, unit};
+
+
+// Runs `f`, returning the message of the panic it is expected to raise.
+fn panic_message<T>(f: impl FnOnce() -> T + panic::UnwindSafe) -> String {
+    match panic::catch_unwind(f) {
+        Ok(_)      => panic!("Expected a panic"),
+        Err(error) => *error.downcast::<String>().unwrap(),
+    }
+}
+
+
+// Successful calls return their value.
+#[test]
+fn test_no_std_success() {
+    assert_eq!(read_sensor(1, 215), 21);
+    assert_eq!(average(&[200, 220, 240]), 22);
+    assert_eq!(average(&[0, 950]), 0);
+    assert_eq!(read_unchecked(315), 31);
+    assert_eq!(unit(1), "fahrenheit");
+}
+
+// A formatted reason is followed by the `Debug` rendering of the error type.
+#[test]
+fn test_no_std_error_type() {
+    let message: String = panic_message(|| read_sensor(3, 950));
+    assert!(message.contains("Sensor 3 failed"), "{message}");
+    assert!(message.contains("Overheated(950)"), "{message}");
+}
+
+// An `anyhow::Error` is rendered along with its chain.
+#[test]
+fn test_no_std_anyhow() {
+    let message: String = panic_message(|| average(&[200, 990]));
+    assert!(message.contains("Failed to average the readings"), "{message}");
+    assert!(message.contains("the reading 990 is out of range"), "{message}");
+
+    let message: String = panic_message(|| average(&[]));
+    assert!(message.contains("value was None"), "{message}");
+}
+
+// `fatal_unreachable!` diverges through the same path.
+#[test]
+fn test_no_std_unreachable() {
+    let message: String = panic_message(|| unit(7));
+    assert!(message.contains("sensor 7 doesn't exist"), "{message}");
+    assert!(message.contains("entered unreachable code (this indicates a bug)"), "{message}");
+}
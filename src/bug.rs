@@ -0,0 +1,27 @@
+use core::error::Error;
+use core::fmt::{self, Display};
+
+
+/// The error `fatal_unreachable!`, `fatal_todo!` and `fatal_unimplemented!`
+/// fail with, telling what was reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bug {
+    Unreachable,
+    Todo,
+    Unimplemented,
+}
+
+/// Tells what was reached in the words of the `std` macros, followed by what
+/// it means.
+impl Display for Bug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reached: &str = match self {
+            Bug::Unreachable   => "entered unreachable code",
+            Bug::Todo          => "not yet implemented",
+            Bug::Unimplemented => "not implemented",
+        };
+        write!(f, "{reached} (this indicates a bug)")
+    }
+}
+
+impl Error for Bug {}
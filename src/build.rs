@@ -0,0 +1,21 @@
+/// Forbids fatal sites in the crate whose build script calls it.
+///
+/// Sets `IMPASS_FORBID=1` for the compilation of the crate, which makes every
+/// `fatal!` block and `#[fatal_fn]` function within it fail to compile with an
+/// error explaining the policy. Blocks marked `#![recoverable]` are allowed,
+/// as they return the error instead. The `FatalExt` methods are plain
+/// functions, and can only be caught by the `audit` feature after the fact.
+///
+/// The crates which depend on it are unaffected, as are its dependencies.
+///
+/// ```rust,no_run
+/// // Within `build.rs`, with `impass` listed as a build dependency along with
+/// // its `build-helper` feature.
+/// impass::build::forbid();
+/// ```
+///
+/// Compiling the crate with `--cfg impass_forbid` has the same effect, e.g.
+/// through `cargo:rustc-cfg=impass_forbid`.
+pub fn forbid() {
+    println!("cargo:rustc-env=IMPASS_FORBID=1");
+}
@@ -0,0 +1,94 @@
+#[cfg(feature = "status-file")]
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+#[cfg(feature = "backtrace-budget")]
+use std::time::Duration;
+
+#[cfg(feature = "backtrace-budget")]
+use crate::symbolicate;
+
+
+/// The process-wide settings, as changed through `config()`.
+#[derive(Clone)]
+pub(crate) struct Settings {
+    #[cfg(feature = "status-file")]
+    pub(crate) status_file:       Option<PathBuf>,
+    #[cfg(feature = "status-file")]
+    pub(crate) clean_exit_status: bool,
+    #[cfg(feature = "backtrace-budget")]
+    pub(crate) backtrace_budget:  Duration,
+    pub(crate) ascii_only:        bool,
+}
+
+static SETTINGS: RwLock<Settings> = RwLock::new(Settings {
+    #[cfg(feature = "status-file")]
+    status_file:       None,
+    #[cfg(feature = "status-file")]
+    clean_exit_status: false,
+    #[cfg(feature = "backtrace-budget")]
+    backtrace_budget:  symbolicate::DEFAULT_BUDGET,
+    ascii_only:        false,
+});
+
+/// Returns a copy of the current settings.
+pub(crate) fn settings() -> Settings {
+    SETTINGS.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+
+/// A handle to the process-wide configuration of the failure path, returned by
+/// `config()`. Every setting applies to all fatal sites from then on.
+#[derive(Clone, Copy, Debug)]
+pub struct Config(());
+
+/// Returns the handle through which the failure path is configured.
+///
+/// ```rust
+/// # #[cfg(feature = "status-file")]
+/// impass::config()
+///     .status_file(std::env::temp_dir().join("my-app.status.json"))
+///     .clean_exit_status(true);
+/// ```
+pub fn config() -> Config {
+    Config(())
+}
+
+impl Config {
+
+    /// Writes a status document to `path` whenever a fatal site fails, for
+    /// supervisors which can't capture stderr. See the crate documentation for
+    /// its contents.
+    #[cfg(feature = "status-file")]
+    pub fn status_file(self, path: impl AsRef<Path>) -> Self {
+        self.update(|settings| settings.status_file = Some(path.as_ref().to_path_buf()))
+    }
+
+    /// Whether `mark_clean_exit()` writes a `"status": "ok"` document to the
+    /// status file. Disabled by default.
+    #[cfg(feature = "status-file")]
+    pub fn clean_exit_status(self, enabled: bool) -> Self {
+        self.update(|settings| settings.clean_exit_status = enabled)
+    }
+
+    /// How long resolving the symbols of a backtrace may take on the failure
+    /// path, after which the report lists the raw frames instead. One second
+    /// by default, and overridden by `IMPASS_BACKTRACE_BUDGET_MS`.
+    #[cfg(feature = "backtrace-budget")]
+    pub fn backtrace_budget(self, budget: Duration) -> Self {
+        self.update(|settings| settings.backtrace_budget = budget)
+    }
+
+    /// Restricts the reports written to the console to 7-bit ASCII, for
+    /// terminals which can't display anything else. Typographic characters
+    /// are spelled out, e.g. `…` as `...`, and any other character of a
+    /// message is escaped as `\u{...}`. Disabled by default, and enabled by
+    /// `IMPASS_ASCII=1` as well.
+    pub fn ascii_only(self, enabled: bool) -> Self {
+        self.update(|settings| settings.ascii_only = enabled)
+    }
+
+    fn update(self, f: impl FnOnce(&mut Settings)) -> Self {
+        f(&mut SETTINGS.write().unwrap_or_else(PoisonError::into_inner));
+        self
+    }
+}
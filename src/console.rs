@@ -0,0 +1,63 @@
+//! Output written to the console, which may be restricted to 7-bit ASCII for
+//! terminals and log shippers which mangle anything else.
+
+use std::borrow::Cow;
+
+use crate::config;
+
+
+/// Whether the console output is restricted to ASCII, through
+/// `config().ascii_only(true)` or `IMPASS_ASCII=1`.
+fn ascii_only() -> bool {
+    config::settings().ascii_only || std::env::var_os("IMPASS_ASCII").is_some_and(|value| value == "1")
+}
+
+/// Prepares `text` to be written to the console, which leaves it as it is
+/// unless the output is restricted to ASCII.
+///
+/// The status file and the fatal hook are given the text as it was, which
+/// is always UTF-8.
+pub fn for_console(text: &str) -> Cow<'_, str> {
+    if !ascii_only() || text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(to_ascii(text))
+}
+
+/// Restricts `text` to ASCII.
+///
+/// - The typographic characters a report may contain are replaced by their
+///   closest ASCII spelling, e.g. `…` by `...` and `→` by `->`.
+/// - Box drawing characters are replaced by `-`, `|` or `+`.
+/// - Any other character is escaped as `\u{...}`, just as Rust escapes it.
+fn to_ascii(text: &str) -> String {
+    let mut ascii: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match spelling(c) {
+            Some(spelling)       => ascii.push_str(spelling),
+            None if c.is_ascii() => ascii.push(c),
+            None                 => ascii.extend(c.escape_unicode()),
+        }
+    }
+    ascii
+}
+
+/// The ASCII spelling of a typographic or box drawing character.
+fn spelling(c: char) -> Option<&'static str> {
+    let spelling: &str = match c {
+        '…'                                                 => "...",
+        '→' | '⟶'                                           => "->",
+        '←' | '⟵'                                           => "<-",
+        '⇒'                                                 => "=>",
+        '«'                                                 => "<<",
+        '»'                                                 => ">>",
+        '‘' | '’'                                           => "'",
+        '“' | '”'                                           => "\"",
+        '–' | '—'                                           => "-",
+        '─' | '━' | '┄' | '┅' | '┈' | '┉' | '╌' | '╍' | '═' => "-",
+        '│' | '┃' | '┆' | '┇' | '┊' | '┋' | '╎' | '╏' | '║' => "|",
+        '\u{2500}'..='\u{257F}'                             => "+",
+        _                                                   => return None,
+    };
+    Some(spelling)
+}
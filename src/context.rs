@@ -0,0 +1,25 @@
+use std::fmt::{self, Display};
+
+
+/// The number of characters of an expression's source rendered by
+/// `Evaluating`, beyond which it is cut short with an ellipsis.
+pub const SOURCE_LIMIT: usize = 80;
+
+
+/// The context `#![context]` attaches to the error of a `?`, naming the
+/// expression it was applied to by its source.
+///
+/// The source is given as it is stringified by the compiler, and rendered on
+/// a single line within `SOURCE_LIMIT` characters.
+#[derive(Clone, Copy, Debug)]
+pub struct Evaluating(pub &'static str);
+
+impl Display for Evaluating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source: String = self.0.split_whitespace().collect::<Vec<&str>>().join(" ");
+        match source.char_indices().nth(SOURCE_LIMIT) {
+            Some((end, _)) => write!(f, "while evaluating `{}…`", &source[..end]),
+            None           => write!(f, "while evaluating `{source}`"),
+        }
+    }
+}
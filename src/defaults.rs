@@ -0,0 +1,132 @@
+use std::ops::Deref;
+use std::process;
+
+use crate::status::Termination;
+use crate::unwind;
+
+
+/// How a failing site terminates, as it says itself or as the defaults of its
+/// module say.
+#[derive(Clone, Copy, Debug)]
+pub enum Terminate {
+
+    /// Panics, as a site without any defaults does.
+    Panic,
+
+    /// Aborts without unwinding, even while an unwind-required guard is
+    /// alive, as asked by `no_catch`.
+    NoCatch,
+
+    /// Aborts, as asked by `abort`.
+    Abort,
+
+    /// Exits with the given code, as asked by `exit(code)`.
+    Exit(i32),
+}
+
+impl Terminate {
+
+    /// Whether a site terminating this way panics, either because it is meant
+    /// to or because an unwind-required guard is alive.
+    pub fn unwinds(self) -> bool {
+        match self {
+            Terminate::Panic                      => true,
+            Terminate::NoCatch                    => false,
+            Terminate::Abort | Terminate::Exit(_) => unwind::unwind_required(),
+        }
+    }
+
+    /// How the process ends, as told to the status file.
+    pub fn termination(self) -> Termination {
+        match self {
+            _ if self.unwinds()   => Termination::Panic,
+            Terminate::Exit(code) => Termination::Exit(code),
+            _                     => Termination::Abort,
+        }
+    }
+
+    /// Ends the process without unwinding, printing `report` just like the
+    /// panic would have carried it. Only called once `unwinds()` is `false`,
+    /// as the panic itself is raised at the site, which is where it's located.
+    pub fn terminate(self, report: &str) -> ! {
+        match self {
+            Terminate::NoCatch if unwind::unwind_required() => {
+                eprintln!("{report}{}", unwind::CLEANUP_SKIPPED_NOTE);
+                process::abort();
+            },
+            Terminate::Exit(code) => {
+                eprintln!("{}", report.trim_start_matches('\n'));
+                process::exit(code);
+            },
+            _ => {
+                eprintln!("{report}");
+                process::abort();
+            },
+        }
+    }
+}
+
+
+/// The attributes given to the `fatal_defaults!` item of a module, which its
+/// sites fall back to for whatever they don't give themselves.
+#[derive(Debug)]
+pub struct Defaults {
+    pub terminate:     Terminate,
+    pub deterministic: bool,
+    pub suggestions:   Vec<String>,
+}
+
+impl Defaults {
+
+    /// The defaults of a module without a `fatal_defaults!` item, which leave
+    /// the sites as they are.
+    pub const NONE: Defaults = Defaults {
+        terminate:     Terminate::Panic,
+        deterministic: false,
+        suggestions:   Vec::new(),
+    };
+
+    /// Renders the suggestions as the `try: ...` lines following the report.
+    pub fn render_suggestions(&self) -> String {
+        if self.suggestions.is_empty() {
+            return String::new();
+        }
+
+        let mut lines: String = String::from("\n");
+        for suggestion in &self.suggestions {
+            lines.push_str("\n    try: ");
+            lines.push_str(suggestion);
+        }
+        lines
+    }
+}
+
+
+/// The receiver through which a site looks up the defaults of its module.
+///
+/// A `fatal_defaults!` item implements a trait of its own for the probe, whose
+/// method takes it by reference. Wherever that trait is in scope, calling the
+/// method on `&DefaultsProbe` finds it right away. Anywhere else, the call
+/// dereferences its way to `NoDefaults` instead, so that no name needs to be
+/// resolved which might not exist.
+pub struct DefaultsProbe;
+
+/// The fallback of `DefaultsProbe`, for the modules without defaults.
+pub struct NoDefaults;
+
+impl Deref for DefaultsProbe {
+    type Target = NoDefaults;
+
+    fn deref(&self) -> &NoDefaults {
+        &NoDefaults
+    }
+}
+
+impl NoDefaults {
+
+    /// Returns `Defaults::NONE`. Shares its name with the method of the trait
+    /// implemented by `fatal_defaults!`.
+    pub fn __impass_fatal_defaults(&self) -> Defaults {
+        Defaults::NONE
+    }
+}
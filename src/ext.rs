@@ -0,0 +1,90 @@
+use std::fmt::Display;
+
+use crate::raise::Fatal;
+
+
+/// Unwraps a single `Result` or `Option`, terminating just like a failed
+/// `fatal!` block would.
+///
+/// This is the method form of `fatal!(expr, "...")`, for when a whole block is
+/// overkill. The short stderr line and the panic message follow the same
+/// layout as the macros', with the reason attached as context to the error.
+/// A `None` is reported as `value was None`, as with `?` on an `Option` within
+/// a `fatal!` block.
+///
+/// ```rust
+/// use impass::FatalExt;
+///
+/// let port:    u16 = "8080".parse::<u16>().or_fatal("The port is invalid");
+/// let workers: u8  = Some(4).or_fatal_with(|| format!("No worker count given for port {port}"));
+/// assert_eq!((port, workers), (8080, 4));
+/// ```
+///
+/// ```rust,should_panic
+/// use impass::FatalExt;
+///
+/// let port: u16 = "eighty".parse::<u16>().or_fatal("The port is invalid");
+/// ```
+///
+/// Both methods are `#[track_caller]`, so the report points at the call
+/// rather than at this crate.
+pub trait FatalExt<T> {
+
+    /// Returns the success value, or terminates with `reason`.
+    #[track_caller]
+    fn or_fatal<R: Display>(self, reason: R) -> T;
+
+    /// Returns the success value, or terminates with the reason returned by
+    /// `reason`, which is only called on failure.
+    #[track_caller]
+    fn or_fatal_with<R: Display>(self, reason: impl FnOnce() -> R) -> T;
+}
+
+impl<T, E: Into<anyhow::Error>> FatalExt<T> for Result<T, E> {
+    #[inline]
+    #[track_caller]
+    fn or_fatal<R: Display>(self, reason: R) -> T {
+        match self {
+            Ok(value)  => value,
+            Err(error) => fail(error.into(), reason),
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn or_fatal_with<R: Display>(self, reason: impl FnOnce() -> R) -> T {
+        match self {
+            Ok(value)  => value,
+            Err(error) => fail(error.into(), reason()),
+        }
+    }
+}
+
+impl<T> FatalExt<T> for Option<T> {
+    #[inline]
+    #[track_caller]
+    fn or_fatal<R: Display>(self, reason: R) -> T {
+        match self {
+            Some(value) => value,
+            None        => fail(anyhow::Error::msg("value was None"), reason),
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn or_fatal_with<R: Display>(self, reason: impl FnOnce() -> R) -> T {
+        match self {
+            Some(value) => value,
+            None        => fail(anyhow::Error::msg("value was None"), reason()),
+        }
+    }
+}
+
+
+/// Reports `error` with `reason` attached, and panics, just like a block with
+/// nothing but a reason.
+#[cold]
+#[track_caller]
+fn fail(error: anyhow::Error, reason: impl Display) -> ! {
+    Fatal::new(error).reason(reason).raise()
+}
@@ -0,0 +1,334 @@
+use std::env;
+use std::cell::Cell;
+use std::fmt::{Debug, Display};
+use std::hint;
+use std::panic::{self, Location};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::console;
+use crate::defaults::Terminate;
+use crate::guard;
+use crate::hook;
+use crate::status::{self, Termination};
+use crate::symbolicate;
+
+
+/// Begins the backtrace rendered after the chain of an error, be it by anyhow
+/// or by the symbolication budget.
+const BACKTRACE_HEADER: &str = "\n\nStack backtrace";
+
+/// The version of the algorithm behind `fingerprint()`, written along with
+/// every fingerprint, as those of different versions never match.
+#[cfg(feature = "status-file")]
+pub(crate) const FINGERPRINT_VERSION: u32 = 1;
+
+/// Written after the short line of a failure raised while another one is
+/// being handled on the same thread.
+const NESTED_NOTE: &str = "fatal raised while handling a fatal";
+
+
+thread_local! {
+    /// How many failures the current thread is handling at once.
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Counts a failure as being handled on the current thread for as long as it
+/// lives, unwinding included.
+struct Depth(u32);
+
+impl Depth {
+    fn enter() -> Depth {
+        Depth(DEPTH.with(|depth| {
+            depth.set(depth.get() + 1);
+            depth.get()
+        }))
+    }
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+
+/// How a failure is reported and ends, besides its location, its reason and
+/// its error.
+#[derive(Clone, Copy, Debug)]
+pub struct FailureOptions<'a> {
+
+    /// The error code rendered along with the location, if any.
+    pub code:          Option<&'a str>,
+
+    /// How the process ends once the report is written.
+    pub terminate:     Terminate,
+
+    /// Whether the site asks for the deterministic mode, which
+    /// `IMPASS_DETERMINISTIC=1` asks for on behalf of all of them.
+    pub deterministic: bool,
+
+    /// Whether the backtrace of the error says anything about the failure,
+    /// which it doesn't when it was captured on another thread than the
+    /// failing one.
+    pub backtrace:     bool,
+
+    /// The marker static of the site, with the `audit` feature.
+    pub marker:        Option<&'static u8>,
+
+    /// The class given through `Fatal::class()`, which replaces the one
+    /// following from how the process ends.
+    pub class:         Option<&'a str>,
+
+    /// The rendered values given through `Fatal::field()`, by name.
+    pub fields:        &'a [(String, String)],
+}
+
+
+/// An error a site may fail with, as far as its report is concerned.
+pub trait SiteError {
+
+    /// Calls the fatal hook with the error.
+    fn run_hook(&self, reason: &str, location: &'static Location<'static>, options: &FailureOptions<'_>);
+
+    /// Renders the error like anyhow would render it with `reason` as its
+    /// context.
+    fn render(self, reason: &str) -> String;
+
+    /// The kind of the root cause of the error, for `fingerprint()`.
+    fn root_kind(&self) -> String;
+}
+
+impl SiteError for anyhow::Error {
+    fn run_hook(&self, reason: &str, location: &'static Location<'static>, options: &FailureOptions<'_>) {
+        hook::run_fatal_hook_at(&reason, self, location, options);
+    }
+
+    fn render(self, reason: &str) -> String {
+        symbolicate::render_error(self.context(reason.to_string()))
+    }
+
+    fn root_kind(&self) -> String {
+        debug_kind(&format_args!("{:?}", self.root_cause()))
+    }
+}
+
+/// An error of the type given through `error`, which is only known to
+/// implement `Debug`, and is rendered through it.
+pub struct DebugError<E>(pub E);
+
+impl<E: Debug> SiteError for DebugError<E> {
+    fn run_hook(&self, reason: &str, location: &'static Location<'static>, options: &FailureOptions<'_>) {
+        hook::run_fatal_hook_debug_at(&reason, &self.0, location, options);
+    }
+
+    fn render(self, reason: &str) -> String {
+        format!("{reason}\n\nCaused by:\n    {}", guard::render_guarded(&format_args!("{:?}", self.0)))
+    }
+
+    fn root_kind(&self) -> String {
+        debug_kind(&format_args!("{:?}", self.0))
+    }
+}
+
+
+/// Reports the failure of a site at `location` on `error`, and terminates.
+///
+/// Every failure goes through here, be it of a site expanded by the macros, of
+/// `Fatal::raise()` or of a watchdog, so that its report is laid out in one
+/// place. The current layout (a leading newline followed by anyhow's `{:?}`
+/// rendering of the error with the reason attached as context) is the one
+/// pinned by the `legacy-format` feature; any new layout must only be used
+/// when that feature is disabled. Without it, the report has no leading
+/// newline, and its first line is the reason prefixed with
+/// `fatal error at <file>:<line>:<column>: `, followed by the chain.
+///
+/// Before anything else, the short line documented on `fatal!` is written to
+/// stderr, so that something is emitted even if assembling the report never
+/// finishes. The status file, the suggestions rendered by `suggest` and the
+/// fatal hook follow, all of which are given the error before it is consumed
+/// by the report.
+///
+/// A failure raised while another one is handled on the same thread, such as
+/// by a fatal hook calling a failing site, is reported by its short line and
+/// a note only: neither the hook, the status file nor the suggestions are
+/// run again, as any of them may be what failed. Should a third one be raised
+/// all the same, the process aborts right away.
+///
+/// A panic is located at the caller, which the sites make sure is themselves.
+#[cold]
+#[track_caller]
+pub fn fail<E: SiteError>(
+    location: &'static Location<'static>,
+    options:  FailureOptions<'_>,
+    reason:   &str,
+    error:    E,
+    suggest:  impl FnOnce(&E) -> String,
+) -> ! {
+    let depth: Depth = Depth::enter();
+    if depth.0 >= 3 {
+        process::abort();
+    }
+
+    if let Some(marker) = options.marker {
+        hint::black_box(marker);
+    }
+
+    // The deterministic mode is either requested by the site, or by the
+    // environment.
+    let deterministic: bool     = options.deterministic || deterministic();
+    let unwinds:       bool     = options.terminate.unwinds();
+    let time:          Duration = if deterministic {
+        Duration::ZERO
+    } else {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+    };
+    let termination: Termination = options.terminate.termination();
+
+    let summary: String = format!(
+        "fatal: {}.{:03} {}:{}:{}: {}",
+        time.as_secs(), time.subsec_millis(), location.file(), location.line(), location.column(), reason
+    );
+    eprintln!("{}", console::for_console(&summary));
+
+    // A nested failure terminates as its site says, without a report. Its
+    // panic carries the short line, and is caught by the failure it's nested
+    // in when raised from the hook.
+    if depth.0 == 2 {
+        if unwinds {
+            eprintln!("{NESTED_NOTE}");
+            panic::resume_unwind(Box::new(format!("{summary}\n{NESTED_NOTE}")));
+        }
+        options.terminate.terminate(NESTED_NOTE);
+    }
+
+    status::write_fatal_status(time, termination, &options, &summary, || fingerprint(reason, location, &error.root_kind()));
+    let suggestions: String = suggest(&error);
+    error.run_hook(reason, location, &options);
+
+    // The backtrace is the only part of anyhow's rendering which varies
+    // between runs, so the deterministic mode rewrites it into a stable form.
+    let mut report: String = error.render(reason);
+    if !options.backtrace && let Some(backtrace) = report.find(BACKTRACE_HEADER) {
+        report.truncate(backtrace);
+    } else if deterministic {
+        report = stabilize_backtrace(report);
+    }
+
+    // An error code is rendered as a header line before the report, and the
+    // suggestions (if any) after it. Without the legacy layout, the report
+    // begins right away with the location of the site instead, along with the
+    // code, so that its first line names both the site and the reason.
+    let report: String = match (cfg!(feature = "legacy-format"), options.code) {
+        (true, Some(code))  => format!("\nfatal error [{code}]\n{report}{suggestions}"),
+        (true, None)        => format!("\n{report}{suggestions}"),
+        (false, Some(code)) => format!(
+            "fatal error [{code}] at {}:{}:{}: {report}{suggestions}", location.file(), location.line(), location.column()
+        ),
+        (false, None)       => format!(
+            "fatal error at {}:{}:{}: {report}{suggestions}", location.file(), location.line(), location.column()
+        ),
+    };
+
+    // Any value whose formatting panicked is noted once, at the end. The
+    // report is only ever written to the console from then on.
+    let report: String = console::for_console(&guard::note_guarded(report)).into_owned();
+
+    // In the deterministic mode, the panic hook is skipped, as it prints the
+    // thread's ID.
+    if unwinds {
+        if deterministic {
+            eprintln!("{report}");
+            panic::resume_unwind(Box::new(report));
+        }
+        panic!("{report}");
+    }
+    options.terminate.terminate(&report);
+}
+
+
+/// Whether the deterministic mode is requested by the environment, through
+/// `IMPASS_DETERMINISTIC=1`.
+pub(crate) fn deterministic() -> bool {
+    env::var_os("IMPASS_DETERMINISTIC").is_some_and(|value| value == "1")
+}
+
+/// Identifies a failure across runs and builds, from the reason, the file and
+/// line of the site, and the kind of the root cause of the error.
+///
+/// The hash is the 64-bit FNV-1a of those fields, each followed by a zero
+/// byte, so that it never depends on the standard library's hasher. Any
+/// change to it must bump `FINGERPRINT_VERSION`.
+fn fingerprint(reason: &str, location: &Location<'_>, root_kind: &str) -> u64 {
+    let line:   String    = location.line().to_string();
+    let fields: [&str; 4] = [reason, location.file(), &line, root_kind];
+    fields.iter().fold(0xcbf2_9ce4_8422_2325, |hash: u64, field| {
+        field.bytes().chain([0]).fold(hash, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+    })
+}
+
+/// The kind of an error rendered through `Debug`, which is the name it begins
+/// with: that of its type or variant for a derived implementation. The root
+/// cause of an anyhow error is only known as a trait object, so its name is
+/// the closest to its type there is. A message, rendered as a string literal,
+/// has none.
+fn debug_kind(debug: &dyn Display) -> String {
+    let debug: String = guard::render_guarded(debug);
+    let end:   usize  = debug.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':')).unwrap_or(debug.len());
+    debug[..end].to_string()
+}
+
+
+/// Rewrites the backtrace at the end of `report`, if any, so that it is the
+/// same on every run of the same binary.
+///
+/// The addresses of the frames left unresolved by the symbolication budget
+/// are left out, as they move with every run, while their offsets within
+/// their module are kept. The paths within the package are made relative to
+/// its manifest directory, which Cargo gives through `CARGO_MANIFEST_DIR`, or
+/// else to the current directory, as the standard library only shortens those
+/// within the current directory. Anything else, such as the frames of the
+/// standard library, is already the same from one run to the next.
+fn stabilize_backtrace(report: String) -> String {
+    let Some(start) = report.find(BACKTRACE_HEADER) else {
+        return report;
+    };
+
+    let current: PathBuf     = env::current_dir().unwrap_or_default();
+    let root:    PathBuf     = env::var_os("CARGO_MANIFEST_DIR").map_or_else(|| current.clone(), PathBuf::from);
+    let lines:   Vec<String> = report[start..].split('\n').map(|text| stabilize_line(text, &current, &root)).collect();
+
+    let mut stable: String = report[..start].to_string();
+    stable.push_str(&lines.join("\n"));
+    stable
+}
+
+/// Rewrites a single line of a backtrace for `stabilize_backtrace()`.
+fn stabilize_line(text: &str, current: &Path, root: &Path) -> String {
+    let (indent, rest): (&str, &str) = text.split_at(text.len() - text.trim_start().len());
+
+    // A location, `at <path>:<line>:<column>`.
+    if let Some(location) = rest.strip_prefix("at ")
+        && let Some((path, column)) = location.rsplit_once(':')
+        && let Some((path, line)) = path.rsplit_once(':')
+    {
+        let path: PathBuf = match path.strip_prefix("./") {
+            Some(relative) => current.join(relative),
+            None           => PathBuf::from(path),
+        };
+        if let Ok(relative) = path.strip_prefix(root) {
+            return format!("{indent}at {}:{line}:{column}", relative.display());
+        }
+    }
+
+    // An unresolved frame, `<index>: 0x<address> (<module> + <offset>)`.
+    if let Some((index, frame)) = rest.split_once(": ")
+        && index.bytes().all(|byte| byte.is_ascii_digit())
+        && let Some(address) = frame.strip_prefix("0x")
+    {
+        let end: usize = address.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(address.len());
+        return format!("{indent}{index}:{}", &address[end..]);
+    }
+    text.to_string()
+}
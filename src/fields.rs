@@ -0,0 +1,137 @@
+//! Wrappers which render values of any size within a bounded length, for use
+//! in reasons and `#[ctx(...)]` messages.
+//!
+//! A failure is best reported along with the data it failed on, but a byte
+//! buffer formatted through `Debug` is both unreadable and as large as the
+//! buffer itself. Each wrapper implements both `Display` and `Debug` within
+//! the same bounds, so they can be used with either placeholder.
+//!
+//! ```rust,should_panic
+//! use impass::fatal;
+//! use impass::fields::{Hex, Redacted, Snip};
+//!
+//! let frame: Vec<u8> = vec![0xde, 0xad, 0xbe, 0xef];
+//! let token: &str    = "0123456789abcdef";
+//! fatal! {
+//!     #![reason("Failed to decode the frame {} signed with {}", Hex(&frame), Redacted(token))]
+//!     #[ctx("decoding the body of {:?}", Snip("Content-Type: application/octet-stream", 12))]
+//!     let text: &str = std::str::from_utf8(&frame)?;
+//!     Ok(text.len())
+//! };
+//! ```
+
+use std::fmt::{self, Debug, Display};
+
+
+/// The number of bytes `Hex` renders, beyond which the rest are only counted.
+pub const HEX_LIMIT: usize = 64;
+
+/// Rendered by `Redacted` in place of the value.
+pub const REDACTED: &str = "«redacted»";
+
+
+/// Renders a byte buffer as a hex dump of its first `HEX_LIMIT` bytes,
+/// preceded by its length, e.g. `4 bytes: deadbeef`.
+///
+/// A longer buffer is cut short with an ellipsis, so the output never exceeds
+/// `2 * HEX_LIMIT` digits along with the length.
+#[derive(Clone, Copy)]
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl Hex<'_> {
+
+    /// Whether the buffer is longer than what is rendered of it.
+    pub fn truncated(&self) -> bool {
+        self.0.len() > HEX_LIMIT
+    }
+}
+
+impl Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.len() {
+            1 => f.write_str("1 byte")?,
+            n => write!(f, "{n} bytes")?,
+        }
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        f.write_str(": ")?;
+        for byte in &self.0[..self.0.len().min(HEX_LIMIT)] {
+            write!(f, "{byte:02x}")?;
+        }
+        if self.truncated() {
+            f.write_str("…")?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+
+/// Renders the first `max` characters of a string, followed by an ellipsis and
+/// the length of the whole string in bytes if that cuts it short, e.g.
+/// `Authorization: B… (38 bytes)`.
+#[derive(Clone, Copy)]
+pub struct Snip<'a>(pub &'a str, pub usize);
+
+impl<'a> Snip<'a> {
+
+    /// The part of the string which is rendered.
+    pub fn prefix(&self) -> &'a str {
+        match self.0.char_indices().nth(self.1) {
+            Some((end, _)) => &self.0[..end],
+            None           => self.0,
+        }
+    }
+
+    /// Whether the string is longer than what is rendered of it.
+    pub fn truncated(&self) -> bool {
+        self.prefix().len() < self.0.len()
+    }
+}
+
+impl Display for Snip<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.prefix())?;
+        if self.truncated() {
+            write!(f, "… ({} bytes)", self.0.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders the prefix quoted and escaped just like a `&str`, which may take
+/// up to ten characters for each character of the prefix.
+impl Debug for Snip<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.prefix(), f)?;
+        if self.truncated() {
+            write!(f, "… ({} bytes)", self.0.len())?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Renders `REDACTED` whatever the value is, so that a secret can be passed
+/// along without it ever being formatted.
+#[derive(Clone, Copy)]
+pub struct Redacted<T>(pub T);
+
+impl<T> Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
@@ -0,0 +1,17 @@
+/// What ended the closure a `fatal!` block is placed in, once the `return`,
+/// `break` and `continue` leaving the block have been rewritten to end it.
+///
+/// The block carries out whatever isn't a `Value` right after the closure has
+/// returned, where it reaches the enclosing function or loop.
+pub enum Flow<T, R> {
+
+    /// The body evaluated to `Ok(value)`, or called `fatal_ok!(value)`.
+    Value(T),
+
+    /// The body returned from the enclosing function with the given value.
+    Return(R),
+
+    /// The body broke out of (or continued) an enclosing loop, given as the
+    /// index of the `break` or `continue` among those of the block.
+    Jump(usize),
+}
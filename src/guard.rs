@@ -0,0 +1,40 @@
+use std::fmt::Display;
+use std::panic::{self, AssertUnwindSafe};
+
+
+/// Substituted for a value whose formatting panicked.
+pub const FORMAT_PANICKED: &str = "«format panicked»";
+
+/// Noted once at the end of a report with any substituted values.
+const FORMAT_PANICKED_NOTE: &str = "\n\nnote: values shown as «format panicked» panicked while being formatted";
+
+
+/// Formats `value`, substituting `FORMAT_PANICKED` if its formatting panics.
+///
+/// The values formatted on the failure path are the user's, and their
+/// implementations may well assert the very invariants whose violation caused
+/// the failure. Each message is rendered on its own, so a single panicking
+/// value only replaces the message it is part of, leaving the rest of the
+/// report intact. `Debug` values are passed in as
+/// `&format_args!("{:?}", value)`.
+pub fn render_guarded(value: &dyn Display) -> String {
+    panic::catch_unwind(AssertUnwindSafe(|| value.to_string())).unwrap_or_else(|_| String::from(FORMAT_PANICKED))
+}
+
+/// Formats a reason like `render_guarded()`, stripping its trailing whitespace,
+/// such as a newline captured from a template, which the report would render
+/// as a blank line.
+pub fn render_reason(reason: &dyn Display) -> String {
+    let mut reason: String = render_guarded(reason);
+    reason.truncate(reason.trim_end().len());
+    reason
+}
+
+/// Appends the note explaining the substitutions of `render_guarded` to
+/// `report`, if it contains any.
+pub fn note_guarded(mut report: String) -> String {
+    if report.contains(FORMAT_PANICKED) {
+        report.push_str(FORMAT_PANICKED_NOTE);
+    }
+    report
+}
@@ -0,0 +1,150 @@
+use std::fmt::{Debug, Display};
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::failure::FailureOptions;
+use crate::guard;
+
+
+/// A hook called with every failure, as set through `set_fatal_hook()`.
+pub type FatalHook = Box<dyn Fn(&FatalReport<'_>) + Send + Sync>;
+
+/// The hook currently set, shared so that it is called without the lock held.
+static HOOK: RwLock<Option<Arc<FatalHook>>> = RwLock::new(None);
+
+
+/// A failure, as passed to the hook set through `set_fatal_hook()`.
+///
+/// Unlike the panic message seen by a panic hook, it keeps the error itself
+/// around, so that crash reporters may inspect its structure.
+#[derive(Debug)]
+pub struct FatalReport<'a> {
+    reason:   String,
+    error:    Option<&'a anyhow::Error>,
+    chain:    Vec<String>,
+    location: &'static Location<'static>,
+    class:    Option<&'a str>,
+    fields:   &'a [(String, String)],
+}
+
+impl<'a> FatalReport<'a> {
+
+    /// The reason of the failing site, or the default message if it has none.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// The error the site failed with, which the reason isn't attached to yet.
+    /// `None` for a block given its own error type through `#![error(...)]`,
+    /// whose `Debug` rendering is the only entry of the `chain()` instead.
+    pub fn error(&self) -> Option<&'a anyhow::Error> {
+        self.error
+    }
+
+    /// The rendered causes of the error, from the outermost context down to
+    /// the root cause.
+    pub fn chain(&self) -> &[String] {
+        &self.chain
+    }
+
+    /// The location of the failing site: the invocation of `fatal!`, the
+    /// function item of `#[fatal_fn]`, or the call of a `FatalExt` method.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// The class given through `Fatal::class()`, if any. `None` for the sites,
+    /// whose class follows from how they terminate.
+    pub fn class(&self) -> Option<&'a str> {
+        self.class
+    }
+
+    /// The values given through `Fatal::field()`, in order, as their names
+    /// along with their `Display` renderings. Empty for the sites.
+    pub fn fields(&self) -> &'a [(String, String)] {
+        self.fields
+    }
+}
+
+
+/// Sets the hook called whenever a fatal site fails, returning the previous
+/// one.
+///
+/// The hook is called on the failing thread, after the short line is written
+/// to stderr and before the process panics, exits or aborts. It is called for
+/// every site, including those given `no_catch`, `abort` or `exit`, which
+/// skip the panic hook. It may be set from any thread, and applies to all of
+/// them from then on.
+///
+/// A panic within the hook is caught, and the failure carries on as if it had
+/// returned. So does a site failing within the hook, which is only reported
+/// by its short line and a note rather than calling the hook again. The hook may set another one, which is called from the next
+/// failure on, while a failure already running the previous one carries on
+/// with it.
+///
+/// ```rust
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static REPORTED: AtomicUsize = AtomicUsize::new(0);
+///
+/// impass::set_fatal_hook(Box::new(|report| {
+///     eprintln!("reporting `{}` at {}", report.reason(), report.location());
+///     REPORTED.fetch_add(1, Ordering::Relaxed);
+/// }));
+/// ```
+pub fn set_fatal_hook(hook: FatalHook) -> Option<FatalHook> {
+    let previous: Option<Arc<FatalHook>> = HOOK.write().unwrap_or_else(PoisonError::into_inner).replace(Arc::new(hook));
+
+    // The previous hook may still be running on another thread, in which case
+    // it is handed back as a hook calling it.
+    previous.map(|previous| Arc::try_unwrap(previous).unwrap_or_else(|previous| Box::new(move |report| previous(report))))
+}
+
+
+/// Calls the hook, if one is set, with the failure of a site at `location` on
+/// `error`.
+pub(crate) fn run_fatal_hook_at(
+    reason:   &dyn Display,
+    error:    &anyhow::Error,
+    location: &'static Location<'static>,
+    options:  &FailureOptions<'_>,
+) {
+    run(reason, Some(error), location, options, || error.chain().map(|cause| guard::render_guarded(cause)).collect());
+}
+
+/// Calls the hook, if one is set, with the failure of a site at `location` on
+/// an error of its own type, which is only known to implement `Debug`.
+pub(crate) fn run_fatal_hook_debug_at(
+    reason:   &dyn Display,
+    error:    &dyn Debug,
+    location: &'static Location<'static>,
+    options:  &FailureOptions<'_>,
+) {
+    run(reason, None, location, options, || vec![guard::render_guarded(&format_args!("{error:?}"))]);
+}
+
+fn run(
+    reason:   &dyn Display,
+    error:    Option<&anyhow::Error>,
+    location: &'static Location<'static>,
+    options:  &FailureOptions<'_>,
+    chain:    impl FnOnce() -> Vec<String>,
+) {
+    // The hook is taken out of the lock before it is called, so that a slow
+    // hook doesn't hold up `set_fatal_hook()`, and a hook setting another one
+    // doesn't deadlock.
+    let hook: Option<Arc<FatalHook>> = HOOK.read().unwrap_or_else(PoisonError::into_inner).clone();
+    let Some(hook) = hook else {
+        return;
+    };
+
+    let report: FatalReport<'_> = FatalReport {
+        reason: guard::render_guarded(reason),
+        error,
+        chain:  chain(),
+        location,
+        class:  options.class,
+        fields: options.fields,
+    };
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(&report)));
+}
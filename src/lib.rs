@@ -60,230 +60,303 @@
 //! }
 //! ```
 //!
+//! ### Cargo Features
+//!
+//! - `std` *(default)*: Everything but `fatal!`, `#[fatal_fn]` and the
+//!   attributes they can honor with `core::panic!` alone. Every other feature
+//!   but the legacy ones and `audit` implies it. See
+//!   [Without `std`](#without-std).
+//! - `legacy-format` *(default)*: Pins the panic message to its original
+//!   layout — a leading newline, followed by anyhow's `{:?}` rendering of the
+//!   error with the reason (or `"An unrecoverable error occurred"`) attached as
+//!   context. Tests matching on the message with
+//!   `#[should_panic(expected = "...")]` keep working while this is enabled.
+//!   New report formats are only used once it is disabled, and it will stop
+//!   being a default feature in a future release. Without it, the report
+//!   doesn't start with a newline, and its first line is the reason, prefixed
+//!   with `fatal error at <file>:<line>:<column>: `. The error chain follows on
+//!   the next lines.
+//! - `legacy-default-message` *(default, implied by `legacy-format`)*: Keeps
+//!   `"An unrecoverable error occurred"` as the context of blocks without a
+//!   reason. Once disabled, the context becomes
+//!   `"fatal error at <file>:<line> (no reason provided)"` instead, which
+//!   points back at the failing block.
+//! - `audit`: Embeds a marker static in every fatal site, named after the crate
+//!   it was expanded in. The `impass_audit` example counts the markers left in
+//!   a built artifact for each crate, and fails if a crate passed through
+//!   `--deny <crate>` contains any. The markers cost one byte and a
+//!   `black_box` per site, so the feature is best kept to audit builds.
+//! - `status-file`: Adds `impass::config().status_file(...)` and
+//!   `impass::mark_clean_exit()`, through which a status document is written
+//!   whenever a fatal site fails. See [Status File](#status-file).
+//! - `backtrace-budget`: Bounds the time spent resolving the symbols of a
+//!   backtrace on the failure path, which may take seconds for a binary
+//!   without debug symbols. The frames are captured right away, and once the
+//!   budget runs out (one second by default) the report lists them as raw
+//!   addresses and module offsets instead, to be resolved offline. The budget
+//!   is set through `impass::config().backtrace_budget(...)`, or the
+//!   `IMPASS_BACKTRACE_BUDGET_MS` environment variable which takes precedence.
+//!   Pulls in the `backtrace` crate, which `anyhow` already depends on.
+//! - `build-helper`: Adds `impass::build::forbid()`, which a build script calls
+//!   to forbid fatal sites in its crate. See
+//!   [Forbidding Fatal Sites](#forbidding-fatal-sites).
+//!
+//! Without any of them, only the macros and `FatalExt` are compiled, whose
+//! reports need nothing but `anyhow` and the standard library. Every optional
+//! feature is additive. The code generated by the macros doesn't depend on
+//! the features of `impass`, as the runtime hooks it calls into compile down
+//! to nothing when their feature is disabled. Each feature should build and
+//! pass the tests on its own, on top of `--no-default-features --features std`.
+//!
+//! ### Without `std`
+//!
+//! A `#![no_std]` crate depends on `impass` with `default-features = false`,
+//! which leaves `fatal!`, `#[fatal_fn]`, `fatal_mod!`, `#[fatal_impl]`,
+//! `fatal_ok!` and `fatal_unreachable!` along with its siblings, with `anyhow`
+//! in its `no_std` mode. A failure is then a
+//! bare `core::panic!("{}: {:?}", reason, error)`, handled by whatever panic
+//! handler the crate is linked with, such as `panic-halt`'s. The error is
+//! either an `anyhow::Error`, or of the type given through `#![error(...)]`,
+//! which only needs to implement `Debug`.
+//!
+//! ```rust,ignore
+//! #![no_std]
+//!
+//! use impass::fatal_fn;
+//!
+//! #[derive(Debug)]
+//! pub struct Overheated(pub u16);
+//!
+//! #[fatal_fn(reason("Sensor {} failed", id), error(Overheated))]
+//! pub fn read_sensor(id: u8, raw: u16) -> u16 {
+//!     if raw >= 900 {
+//!         return Err(Overheated(raw));
+//!     }
+//!     Ok(raw / 10)
+//! }
+//! ```
+//!
+//! The attributes which add to the report, such as `code`, `suggest`,
+//! `context` and `#[ctx(...)]`, or which terminate in some other way, such as
+//! `abort`, `exit` and `recoverable`, need the `std` feature, and fail to
+//! compile without it. The `impass_no_std` crate of the workspace checks that
+//! the rest expands and links without `std`.
+//!
+//! ### Status File
+//!
+//! With the `status-file` feature, supervisors which can't capture stderr may
+//! read a status file instead, configured through
+//! `impass::config().status_file(path)`. Whenever a fatal
+//! site fails, a single-line JSON document is written to it:
+//!
+//! ```text
+//! {"schema_version": 2, "status": "fatal", "timestamp": 1700000000.123, "exit_code": 101, "class": "panic", "code": "E0042", "fingerprint": "9f3a0c6e1b2d4f58", "fingerprint_version": 1, "fields": {}, "summary": "fatal: ..."}
+//! ```
+//!
+//! - `class` is how the process ends: `"panic"`, `"abort"` (for
+//!   `#![no_catch]` and `#![abort]` sites) or `"exit"` (for `#![exit(code)]`
+//!   sites). A site which panics instead because of an unwind-required guard
+//!   is reported as a `"panic"`. The `exit_code` is the matching exit status,
+//!   with an abort reported as `134` like a shell would. A failure raised
+//!   through `Fatal` may be given its own class instead, such as
+//!   `"runtime"`.
+//! - `code` is the site's error code, or `null`.
+//! - `fingerprint` identifies the failure across runs and builds, as long as
+//!   its reason, the file and line of its site and the kind of the root
+//!   cause of its error stay the same. The kind is the name the root cause's
+//!   `Debug` rendering begins with, which is that of its type or variant when
+//!   derived. Fingerprints are only comparable for the same
+//!   `fingerprint_version`.
+//! - `fields` holds the values given to a failure raised through `Fatal`,
+//!   rendered as strings, and is empty for the sites.
+//! - `summary` is the short line written to stderr.
+//! - `raw_frames` is only present with the `backtrace-budget` feature, and
+//!   lists the unresolved frames of the failing site as strings.
+//!
+//! With `.clean_exit_status(true)`, calling `impass::mark_clean_exit()` right
+//! before a normal exit writes `{"schema_version": 2, "status": "ok", ...}`
+//! instead, so a missing document means the process never got that far.
+//! Documents are written to a temporary file which is then renamed over the
+//! status file, so they are never seen half-written. A failed write is
+//! ignored on the failure path, and never keeps the process from ending.
+//!
+//! ### Fatal Hook
+//!
+//! Crash reporters may install a hook through `impass::set_fatal_hook()`,
+//! which every failing site calls before it terminates. Rather than the
+//! flattened message a panic hook sees, the hook is given a `FatalReport`
+//! with the reason, the `anyhow::Error` itself, its cause chain and the
+//! location of the site:
+//!
+//! ```rust,ignore
+//! impass::set_fatal_hook(Box::new(|report| {
+//!     eprintln!("uploading a crash report for `{}` at {}", report.reason(), report.location());
+//!     for cause in report.chain() {
+//!         eprintln!("    caused by: {cause}");
+//!     }
+//! }));
+//! ```
+//!
+//! ### Raising Without a Site
+//!
+//! An error received from elsewhere, such as from a channel, can be raised
+//! through the `Fatal` builder, which takes the attributes of a `fatal!`
+//! block and reports them the same way, located at the call of `raise()`.
+//! It may also be given a class and named fields, which the fatal hook and
+//! the status file are given along with the failure:
+//!
+//! ```rust,ignore
+//! # let error = std::io::Error::other("the last worker hung up");
+//! impass::Fatal::new(error.into())
+//!     .reason("The worker pool collapsed")
+//!     .code("E0070")
+//!     .class("runtime")
+//!     .field("workers_lost", 4)
+//!     .raise();
+//! ```
+//!
+//! ### ASCII Output
+//!
+//! Consoles which only display 7-bit ASCII, such as a legacy Windows console
+//! on an OEM code page, may have the reports restricted to it through
+//! `impass::config().ascii_only(true)` or `IMPASS_ASCII=1`. Typographic
+//! characters are spelled out (`...` for `…`, `->` for `→`), box drawing
+//! characters are replaced by `-`, `|` and `+`, and anything else beyond
+//! ASCII, such as an emoji or CJK text within a message, is escaped as
+//! `\u{...}`. Only the console output is affected: the short line, the
+//! report, and the panic message. The status file and the fatal hook are
+//! still given UTF-8.
+//!
+//! ### Forbidding Fatal Sites
+//!
+//! A library crate may make sure it never terminates the process on its own,
+//! by building with `IMPASS_FORBID=1`, which its build script sets through
+//! `impass::build::forbid()` or `cargo:rustc-env=IMPASS_FORBID=1`. Every
+//! `fatal!` block and `#[fatal_fn]` function of the crate then fails to
+//! compile, pointing at the site, while `#![recoverable]` blocks are still
+//! allowed. Building the crate with `--cfg impass_forbid` does the same.
+//!
+//! Unlike the `audit` feature, the policy is enforced while the crate is being
+//! written, but it can't see the `FatalExt` methods, which are plain
+//! functions.
+//!
+//! ### Performance
+//!
+//! A `fatal!` block expands to an immediately invoked closure followed by
+//! `unwrap_or_else`, so the success path costs no more than the `?`s written
+//! within it. Nothing is allocated, and no global state is read. The failure
+//! path is free to allocate, as it ends the program anyway.
+//!
+//! | Form                      | Zero-cost on success | Notes                                        |
+//! |---------------------------|----------------------|----------------------------------------------|
+//! | `fatal!` / `#[fatal_fn]`  | Yes                  |                                              |
+//! | `fatal!(expr, "...")`     | Yes                  | The message is only formatted on failure.    |
+//! | `#![reason("...")]`       | Yes                  |                                              |
+//! | `#![reason("...", args)]` | Yes                  | The message is only formatted on failure.    |
+//! | `#![reason(expr)]`        | Yes                  | The expression is only evaluated on failure. |
+//! | `#![reason_with(...)]`    | Yes                  | The closure only runs on failure.            |
+//! | `#![code("...")]`         | Yes                  |                                              |
+//! | `#![must_use]`            | Yes                  | An inlined identity function.                |
+//! | `#[ctx("...", args...)]`  | Yes                  | The message is only formatted on failure.    |
+//! | `#![suggest(...)]`        | Yes                  | Suggestions are only formatted on failure.   |
+//! | `#![ensure_ok(...)]`      | Yes                  | Only the predicates run on success.          |
+//! | `fatal_ok!(value)`        | Yes                  | An early `return` from the closure.          |
+//! | `?` on an `Option`        | Yes                  | An inlined conversion into a `Result`.       |
+//! | `.or_fatal(...)`          | Yes                  | The reason is only formatted on failure.     |
+//! | `.or_fatal_with(...)`     | Yes                  | The closure only runs on failure.            |
+//! | `fatal_lazy!`             | No                   | Every access goes through a `LazyLock`.      |
+//!
+//! The forms marked as zero-cost are a tested contract: `tests/overhead.rs`
+//! asserts that none of them allocate when the block succeeds.
+//!
 //! ---
 
-extern crate proc_macro;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
-use quote::{quote, ToTokens};
-use syn::parse_macro_input;
-use syn::parse::{Parse, ParseStream};
-use syn::parse2;
+#[cfg(feature = "build-helper")]
+pub mod build;
+mod bug;
+#[cfg(feature = "std")]
+mod config;
+#[cfg(feature = "std")]
+mod console;
+#[cfg(feature = "std")]
+mod context;
+#[cfg(feature = "std")]
+mod defaults;
+#[cfg(feature = "std")]
+mod ext;
+#[cfg(feature = "std")]
+mod failure;
+#[cfg(feature = "std")]
+pub mod fields;
+mod flow;
+#[cfg(feature = "std")]
+mod guard;
+#[cfg(feature = "std")]
+mod hook;
+#[cfg(feature = "std")]
+mod raise;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "std")]
+mod status;
+#[cfg(feature = "std")]
+mod symbolicate;
+mod tail;
+#[cfg(feature = "std")]
+mod unwind;
+#[cfg(feature = "std")]
+mod watchdog;
 
+pub use impass_macros::{fatal, fatal_fn, fatal_impl, fatal_mod, fatal_ok, fatal_todo, fatal_unimplemented, fatal_unreachable};
+#[cfg(feature = "std")]
+pub use impass_macros::{fatal_defaults, fatal_lazy, fatal_watchdog};
+#[cfg(feature = "std")]
+pub use config::{config, Config};
+#[cfg(feature = "std")]
+pub use ext::FatalExt;
+#[cfg(feature = "std")]
+pub use hook::{set_fatal_hook, FatalHook, FatalReport};
+#[cfg(feature = "std")]
+pub use raise::Fatal;
+#[cfg(feature = "status-file")]
+pub use status::mark_clean_exit;
+#[cfg(feature = "std")]
+pub use unwind::{register_unwind_required_guard, UnwindRequiredGuard};
+#[cfg(feature = "std")]
+pub use watchdog::Heartbeat;
 
-/// A declarative macro for handling critical, unrecoverable errors.
-///
-/// The `fatal!` macro wraps a block of code, allowing for a clean and
-/// expressive "fail-fast" pattern. It provides a highly visible boundary for
-/// code that is expected to be infallible, and it signals a critical bug if a
-/// `Result::Err` is returned.
-///
-/// This macro is a more ergonomic and readable alternative to
-/// ```rust,ignore
-/// (|| -> Result<_, anyhow::Error> { ... })().unwrap()
-/// ```
-///
-/// ### Behavior
-///
-/// 1.  **Allows `?` Operator:** The macro wraps the provided block in a
-///     closure, enabling the use of the `?` operator for seamless error
-///     propagation.
-/// 2.  **Detailed Panic:** If the block returns an `Err`, the macro catches the
-///     error, prints a detailed error message which includes any context. For
-///     this reason it is recommended to use the `context` function from
-///     `anyhow`.
-/// 3.  **Returns a Value:** The macro returns the inner value of the `Ok`
-///     variant, allowing it to be used in assignments.
-///
-/// ### Usage
-///
-/// The macro accepts a code block that must return a `Result` type.
-///
-/// ```rust,should_panic
-/// use thiserror::Error;
-/// use impass::fatal;
-/// 
-/// // Declare an error type for demonstration purposes.
-/// #[derive(Error, Debug)]
-/// pub enum MyError {
-///     #[error("This operation failed")]
-///     OperationFailed
-/// }
-///
-/// // A fallible function for demonstration.
-/// fn might_fail(value: i32) -> Result<i32, MyError> {
-///     if value < 10 {
-///         Err(MyError::OperationFailed)
-///     } else {
-///         Ok(value * 2)
-///     }
-/// }
-///
-/// fn main() {
-///     // Simple usage without a custom message.
-///     let final_value = fatal! {
-///         let value = might_fail(15)?;
-///         Ok(value)
-///     };
-///     println!("Successfully computed: {}", final_value);
-///
-///     // Usage with a custom message using the `#![reason]` attribute.
-///     fatal! {
-///         #![reason("Failed to initialize system-critical component.")]
-///         let result = might_fail(5)?; // This will cause a panic.
-///         Ok(())
-///     }
-/// }
-/// ```
-#[proc_macro]
-pub fn fatal(input: TokenStream) -> TokenStream {
 
-    // Parse the input into a `FatalBlock` struct.
-    let FatalBlock {
-        stmts,
-        reason_message,
-    } = parse_macro_input!(input as FatalBlock);
-
-    // The block is placed inside a closure that returns a `Result`.
-    let result: TokenStream2 = quote! {
-        (|| -> std::result::Result<_, anyhow::Error> {
-            #(#stmts)*
-        })()
-    };
-
-    // We generate an unwrap_or_else that formats the anyhow error and panics.
-    let generated_code: TokenStream2 = if let Some(msg) = reason_message {
-        quote! {
-            #result.unwrap_or_else(|e| {
-                panic!("\n{:?}", e.context(#msg));
-            })
-        }
-    } else {
-        quote! {
-            #result.unwrap_or_else(|e| {
-                panic!("\n{:?}", e.context("An unrecoverable error occurred"));
-            })
-        }
-    };
-
-    generated_code.into()
-}
+/// The context attached to the error of a site without a reason, as long as
+/// the `legacy-default-message` feature is enabled. Without it, the context is
+/// `"fatal error at <file>:<line> (no reason provided)"` instead.
+pub const DEFAULT_MESSAGE: &str = "An unrecoverable error occurred";
 
 
-/// Handles the parsing of the `fatal!` macro's input.
-struct FatalBlock {
-    stmts:          Vec<syn::Stmt>,
-    reason_message: Option<syn::LitStr>,
-}
-
-impl Parse for FatalBlock {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-
-        // Find the `reason` attribute, if it exists.
-        let mut reason_message: Option<syn::LitStr> = None;
-        let     attribs:        Vec<syn::Attribute> = input.call(syn::Attribute::parse_inner)?;
-
-        for attr in attribs {
-            if attr.path.is_ident("reason") {
-                if let Ok(value) = attr.parse_args::<syn::LitStr>() {
-                    reason_message = Some(value);
-                }
-            }
-        }
-
-        // Return the parsed block.
-        Ok(FatalBlock {
-            stmts: input.call(syn::Block::parse_within)?,
-            reason_message,
-        })
-    }
-}
-
-/// An attribute macro that wraps a function's body in the `fatal!` macro.
-///
-/// This macro allows you to specify an optional reason for the fatal error
-/// using the attribute argument.
+/// Items the expansions of the macros refer to, which aren't part of the public
+/// API. Depending on `anyhow` directly still works alongside it, as both names
+/// refer to the same crate.
 ///
-/// ### Example
-/// ```rust
-/// use thiserror::Error;
-/// use impass::{fatal, fatal_fn};
-/// 
-/// // Declare an error type for demonstration purposes.
-/// #[derive(Error, Debug)]
-/// pub enum MyError {
-///     #[error("This operation failed")]
-///     OperationFailed
-/// }
-///
-/// // A fallible function for demonstration.
-/// fn might_fail(value: i32) -> Result<i32, MyError> {
-///     if value < 10 {
-///         Err(MyError::OperationFailed)
-///     } else {
-///         Ok(value * 2)
-///     }
-/// }
-/// 
-/// // Using the `fatal_fn` macro to wrap a function.
-/// #[fatal_fn(reason = "Critical failure in function execution")]
-/// fn example_function() -> i32 {
-///     let value = might_fail(15)?;
-///     Ok(value)
-/// }
-/// 
-/// // The above function is equivalent to:
-/// fn example_function_actual() -> i32 {
-///     fatal! {
-///         #![reason("Critical failure in function execution")]
-///         let value = might_fail(15)?;
-///         Ok(value)
-///     }
-/// }
-/// ```
-#[proc_macro_attribute]
-pub fn fatal_fn(args: TokenStream, input: TokenStream) -> TokenStream {
-
-    // Parse the attribute arguments and the function.
-    let     args:     syn::AttributeArgs = parse_macro_input!(args as syn::AttributeArgs);
-    let mut input_fn: syn::ItemFn        = parse_macro_input!(input as syn::ItemFn);
-
-    // Extract the reason argument, if provided.
-    let reason_message = args.iter().find_map(|arg| {
-        if let syn::NestedMeta::Meta(syn::Meta::NameValue(meta)) = arg {
-            if meta.path.is_ident("reason") {
-                if let syn::Lit::Str(lit_str) = &meta.lit {
-                    return Some(lit_str.value());
-                }
-            }
-        }
-        None
-    });
-
-    // Get the original function body.
-    let original_body: &[syn::Stmt] = &input_fn.block.stmts;
-
-    // Construct the new body wrapped in the `fatal!` macro.
-    let new_body: TokenStream2 = if let Some(reason) = reason_message {
-        quote! {
-            impass::fatal! {
-                #![reason(#reason)]
-                #(#original_body)*
-            }
-        }
-    } else {
-        quote! {
-            impass::fatal! {
-                #(#original_body)*
-            }
-        }
-    };
-
-    // Replace the function's body with the new wrapped body.
-    input_fn.block = parse2(quote! { { #new_body } })
-        .expect("Failed to parse the new body into a block.");
-
-    // Return the modified function as a TokenStream.
-    TokenStream::from(input_fn.to_token_stream())
-}
\ No newline at end of file
+/// The standard library is reached through here as well, so that a
+/// `#![no_std]` crate still compiles when another crate of its build enables
+/// the `std` feature.
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+    #[cfg(feature = "std")]
+    pub use std;
+    pub use crate::bug::Bug;
+    #[cfg(feature = "std")]
+    pub use crate::context::Evaluating;
+    #[cfg(feature = "std")]
+    pub use crate::defaults::{Defaults, DefaultsProbe, NoDefaults, Terminate};
+    #[cfg(feature = "std")]
+    pub use crate::failure::{fail, DebugError, FailureOptions};
+    pub use crate::flow::Flow;
+    #[cfg(feature = "std")]
+    pub use crate::guard::{render_guarded, render_reason, FORMAT_PANICKED};
+    pub use crate::tail::{ConvertTail, Tail};
+    #[cfg(feature = "std")]
+    pub use crate::watchdog::Watchdog;
+}
@@ -0,0 +1,118 @@
+use std::fmt::Display;
+use std::panic::Location;
+
+use crate::defaults::Terminate;
+use crate::failure::{self, FailureOptions};
+use crate::guard;
+
+
+/// A failure raised from an error at hand rather than from a fatal site, for
+/// the code which receives its errors from elsewhere, such as a channel.
+///
+/// The builder takes the attributes a `fatal!` block would, and `raise()`
+/// reports and terminates just like a block given the same ones: the short
+/// line, the status file, the fatal hook, the report and the way the process
+/// ends are all the same, located at the call of `raise()`. The global
+/// configuration applies as usual. The defaults of a module given through
+/// `fatal_defaults!` don't, as they are only known to the sites within it.
+///
+/// Unlike a site, a failure may also be given a class and named fields,
+/// which are passed to the fatal hook and written to the status file, but
+/// left out of the report.
+///
+/// ```rust,should_panic
+/// use impass::Fatal;
+///
+/// let error: std::io::Error = std::io::Error::other("the last worker hung up");
+/// Fatal::new(error.into())
+///     .reason("The worker pool collapsed")
+///     .code("E0070")
+///     .class("runtime")
+///     .field("workers_lost", 4)
+///     .raise();
+/// ```
+#[must_use = "a failure only terminates once it is raised"]
+#[derive(Debug)]
+pub struct Fatal {
+    error:     anyhow::Error,
+    reason:    Option<String>,
+    code:      Option<String>,
+    class:     Option<String>,
+    fields:    Vec<(String, String)>,
+    exit_code: Option<i32>,
+}
+
+impl Fatal {
+
+    /// Creates a failure on `error`, which panics with the default reason
+    /// unless told otherwise.
+    pub fn new(error: anyhow::Error) -> Fatal {
+        Fatal { error, reason: None, code: None, class: None, fields: Vec::new(), exit_code: None }
+    }
+
+    /// Attaches a reason, as `#![reason(...)]` does.
+    pub fn reason(mut self, reason: impl Display) -> Fatal {
+        self.reason = Some(guard::render_reason(&reason));
+        self
+    }
+
+    /// Attaches an error code, as `#![code(...)]` does.
+    pub fn code(mut self, code: impl Into<String>) -> Fatal {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Attaches a class, which replaces the one written to the status file
+    /// and following from how the failure ends, such as `"panic"`.
+    pub fn class(mut self, class: impl Into<String>) -> Fatal {
+        self.class = Some(class.into());
+        self
+    }
+
+    /// Attaches a named value, rendered through `Display`. Fields are kept in
+    /// the order they are attached in.
+    pub fn field(mut self, name: impl Into<String>, value: impl Display) -> Fatal {
+        self.fields.push((name.into(), guard::render_guarded(&value)));
+        self
+    }
+
+    /// Exits with `code` rather than panicking, as `#![exit(code)]` does. An
+    /// unwind-required guard still makes it panic instead.
+    pub fn exit_code(mut self, code: i32) -> Fatal {
+        self.exit_code = Some(code);
+        self
+    }
+
+    /// Reports the failure at the caller, and terminates.
+    #[cold]
+    #[track_caller]
+    pub fn raise(self) -> ! {
+        let location:  &'static Location<'static> = Location::caller();
+        let reason:    String                     = self.reason.unwrap_or_else(|| default_reason(location));
+        let terminate: Terminate                  = match self.exit_code {
+            Some(code) => Terminate::Exit(code),
+            None       => Terminate::Panic,
+        };
+
+        let options: FailureOptions<'_> = FailureOptions {
+            code:          self.code.as_deref(),
+            terminate,
+            deterministic: false,
+            backtrace:     true,
+            marker:        None,
+            class:         self.class.as_deref(),
+            fields:        &self.fields,
+        };
+        failure::fail(location, options, &reason, self.error, |_| String::new())
+    }
+}
+
+/// The context attached to the error when no reason is given, which is the
+/// same as that of a block without one.
+fn default_reason(location: &Location<'_>) -> String {
+    if cfg!(feature = "legacy-default-message") {
+        String::from(crate::DEFAULT_MESSAGE)
+    } else {
+        format!("fatal error at {}:{} (no reason provided)", location.file(), location.line())
+    }
+}
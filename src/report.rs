@@ -0,0 +1,239 @@
+//! Signatures of the cause chain of a failure, which tell whether a known
+//! failure still fails the same way.
+//!
+//! A scenario which is expected to fail is best checked against the shape of
+//! its failure rather than its exact report, which changes with every path,
+//! port and identifier along the way. `chain_signature()` describes each cause
+//! by its type where it is a well-known one, and by its masked message
+//! otherwise, and `ChainDiff` tells which causes were inserted, removed or
+//! changed from one signature to another.
+//!
+//! ```rust
+//! use std::sync::Mutex;
+//! use impass::report::{self, ChainDiff};
+//!
+//! static SIGNATURE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+//!
+//! impass::set_fatal_hook(Box::new(|failure| {
+//!     *SIGNATURE.lock().unwrap() = report::chain_signature(failure);
+//! }));
+//! let _ = std::panic::catch_unwind(|| impass::fatal! {
+//!     #[ctx("reading /etc/app/config.toml")]
+//!     let port: u16 = "80a".parse()?;
+//!     Ok(port)
+//! });
+//!
+//! let expected: Vec<String> = vec![String::from("reading /*/*/*"), String::from("std::num::ParseIntError(InvalidDigit)")];
+//! let diff:     ChainDiff   = ChainDiff::between(&expected, &SIGNATURE.lock().unwrap());
+//! assert!(diff.is_empty(), "{diff}");
+//! ```
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+use std::net::AddrParseError;
+use std::num::{ParseFloatError, ParseIntError, TryFromIntError};
+use std::str::{ParseBoolError, Utf8Error};
+use std::string::FromUtf8Error;
+
+use crate::hook::FatalReport;
+
+
+/// Tells whether a cause is of a given type.
+type IsType = fn(&(dyn Error + 'static)) -> bool;
+
+/// The error types described by their name rather than by their message, as
+/// nothing but their type tells them apart from an unrelated error.
+const KNOWN_TYPES: &[(IsType, &str)] = &[
+    (<dyn Error>::is::<ParseFloatError>, "std::num::ParseFloatError"),
+    (<dyn Error>::is::<TryFromIntError>, "std::num::TryFromIntError"),
+    (<dyn Error>::is::<ParseBoolError>,  "std::str::ParseBoolError"),
+    (<dyn Error>::is::<Utf8Error>,       "std::str::Utf8Error"),
+    (<dyn Error>::is::<FromUtf8Error>,   "std::string::FromUtf8Error"),
+    (<dyn Error>::is::<AddrParseError>,  "std::net::AddrParseError"),
+    (<dyn Error>::is::<fmt::Error>,      "std::fmt::Error"),
+];
+
+
+/// Describes each cause of a failure, from the outermost context down to the
+/// root cause.
+///
+/// A cause of a well-known type, such as `std::io::Error` or
+/// `std::num::ParseIntError`, is described by its type along with its kind
+/// where it has one, e.g. `std::io::Error(NotFound)`. Any other cause is
+/// described by its message, as masked by `mask()`.
+pub fn chain_signature(report: &FatalReport<'_>) -> Vec<String> {
+    match report.error() {
+        Some(error) => error.chain()
+            .zip(report.chain())
+            .map(|(cause, message)| type_name(cause).unwrap_or_else(|| mask(message)))
+            .collect(),
+        None => report.chain().iter().map(|message| mask(message)).collect(),
+    }
+}
+
+/// Names the type of a cause, if it is a well-known one.
+fn type_name(cause: &(dyn Error + 'static)) -> Option<String> {
+    if let Some(error) = cause.downcast_ref::<io::Error>() {
+        return Some(format!("std::io::Error({:?})", error.kind()));
+    }
+    if let Some(error) = cause.downcast_ref::<ParseIntError>() {
+        return Some(format!("std::num::ParseIntError({:?})", error.kind()));
+    }
+    KNOWN_TYPES.iter().find(|(is, _)| is(cause)).map(|(_, name)| name.to_string())
+}
+
+
+/// Masks the parts of a message which differ from one run to another.
+///
+/// - Whitespace is collapsed into single spaces.
+/// - Every segment of a path, that is of a word containing `/` or `\`, is
+///   replaced by `*`, keeping the separators and any quotes or brackets
+///   around the path, e.g. `"/var/lib/app.db"` becomes `"/*/*/*"`.
+/// - Every run of digits elsewhere is replaced by `#`, e.g. `port 8080`
+///   becomes `port #`.
+pub fn mask(message: &str) -> String {
+    message.split_whitespace().map(mask_word).collect::<Vec<String>>().join(" ")
+}
+
+/// Masks a single word of a message.
+fn mask_word(word: &str) -> String {
+
+    // Quotes, brackets and punctuation around the word are kept as they are.
+    let is_delimiter = |c: char| matches!(c, '"' | '\'' | '`' | '(' | ')' | '[' | ']' | '{' | '}' | '<' | '>' | ',' | ';' | ':');
+    let trimmed: &str  = word.trim_start_matches(is_delimiter);
+    let core:    &str  = trimmed.trim_end_matches(is_delimiter);
+    let start:   usize = word.len() - trimmed.len();
+    let end:     usize = start + core.len();
+
+    // A path is masked segment by segment, and anything else run of digits by
+    // run of digits.
+    let     is_path: bool   = core.contains(['/', '\\']);
+    let     mark:    char   = if is_path { '*' } else { '#' };
+    let mut masked:  String = String::from(&word[..start]);
+    let mut in_run:  bool   = false;
+    for c in core.chars() {
+        let is_masked: bool = if is_path { !matches!(c, '/' | '\\') } else { c.is_ascii_digit() };
+        if !is_masked {
+            masked.push(c);
+        } else if !in_run {
+            masked.push(mark);
+        }
+        in_run = is_masked;
+    }
+    masked.push_str(&word[end..]);
+    masked
+}
+
+
+/// A single difference between two chain signatures.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameChange {
+
+    /// A cause which only the actual chain has, at its index within it.
+    Inserted { at: usize, frame: String },
+
+    /// A cause which only the expected chain has, at its index within it.
+    Removed { at: usize, frame: String },
+
+    /// A cause which differs between the chains, at its index within the
+    /// actual chain.
+    Changed { at: usize, expected: String, actual: String },
+}
+
+/// The differences between an expected chain signature and an actual one, as
+/// the causes inserted, removed or changed to turn the former into the latter.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChainDiff {
+    changes: Vec<FrameChange>,
+}
+
+impl ChainDiff {
+
+    /// Compares two chain signatures, aligning the causes they share.
+    ///
+    /// Removed causes directly followed by inserted ones are paired up as
+    /// changed ones, as a cause whose type or message changed.
+    pub fn between(expected: &[String], actual: &[String]) -> ChainDiff {
+
+        // The length of the longest common subsequence of each pair of
+        // suffixes, which the alignment follows.
+        let mut common: Vec<Vec<usize>> = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+        for i in (0..expected.len()).rev() {
+            for j in (0..actual.len()).rev() {
+                common[i][j] = if expected[i] == actual[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+
+        let mut diff:     ChainDiff  = ChainDiff::default();
+        let mut removed:  Vec<usize> = Vec::new();
+        let mut inserted: Vec<usize> = Vec::new();
+        let (mut i, mut j): (usize, usize) = (0, 0);
+        while i < expected.len() || j < actual.len() {
+            if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+                diff.flush(expected, actual, &mut removed, &mut inserted);
+                i += 1;
+                j += 1;
+            } else if j < actual.len() && (i == expected.len() || common[i][j + 1] >= common[i + 1][j]) {
+                inserted.push(j);
+                j += 1;
+            } else {
+                removed.push(i);
+                i += 1;
+            }
+        }
+        diff.flush(expected, actual, &mut removed, &mut inserted);
+        diff
+    }
+
+    /// Records a run of removed and inserted causes, pairing them up first.
+    fn flush(&mut self, expected: &[String], actual: &[String], removed: &mut Vec<usize>, inserted: &mut Vec<usize>) {
+        let paired: usize = removed.len().min(inserted.len());
+        for (&i, &j) in removed.iter().zip(inserted.iter()) {
+            self.changes.push(FrameChange::Changed { at: j, expected: expected[i].clone(), actual: actual[j].clone() });
+        }
+        for &i in &removed[paired..] {
+            self.changes.push(FrameChange::Removed { at: i, frame: expected[i].clone() });
+        }
+        for &j in &inserted[paired..] {
+            self.changes.push(FrameChange::Inserted { at: j, frame: actual[j].clone() });
+        }
+        removed.clear();
+        inserted.clear();
+    }
+
+    /// The differences, in the order of the chains.
+    pub fn changes(&self) -> &[FrameChange] {
+        &self.changes
+    }
+
+    /// Whether both chains have the same signature.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Lists the differences one per line, or tells that there are none.
+impl Display for ChainDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return f.write_str("the chains have the same signature");
+        }
+
+        for (index, change) in self.changes.iter().enumerate() {
+            if index > 0 {
+                f.write_str("\n")?;
+            }
+            match change {
+                FrameChange::Inserted { at, frame }           => write!(f, "cause {at} inserted: {frame}")?,
+                FrameChange::Removed { at, frame }            => write!(f, "cause {at} removed: {frame}")?,
+                FrameChange::Changed { at, expected, actual } => write!(f, "cause {at} changed: {expected} => {actual}")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use std::time::Duration;
+#[cfg(feature = "status-file")]
+use std::fs;
+#[cfg(feature = "status-file")]
+use std::io;
+#[cfg(feature = "status-file")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "status-file")]
+use std::process;
+#[cfg(feature = "status-file")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "status-file")]
+use crate::config;
+use crate::failure::FailureOptions;
+#[cfg(feature = "status-file")]
+use crate::failure::FINGERPRINT_VERSION;
+#[cfg(all(feature = "status-file", feature = "backtrace-budget"))]
+use crate::symbolicate;
+
+
+/// The version of the status document's layout, bumped on any change to it.
+#[cfg(feature = "status-file")]
+const SCHEMA_VERSION: u32 = 2;
+
+
+/// How a failing fatal site terminates the process.
+#[derive(Clone, Copy, Debug)]
+pub enum Termination {
+
+    /// Unwinds, which ends the process with the exit code of an uncaught
+    /// panic unless it is caught.
+    Panic,
+
+    /// Aborts the process, whose exit code is reported as a shell would.
+    Abort,
+
+    /// Exits the process with the given code.
+    Exit(i32),
+}
+
+#[cfg(feature = "status-file")]
+impl Termination {
+    fn class(self) -> &'static str {
+        match self {
+            Termination::Panic   => "panic",
+            Termination::Abort   => "abort",
+            Termination::Exit(_) => "exit",
+        }
+    }
+
+    fn exit_code(self) -> i32 {
+        match self {
+            Termination::Panic      => 101,
+            Termination::Abort      => 134,
+            Termination::Exit(code) => code,
+        }
+    }
+}
+
+
+/// Writes the status document of a failure to the configured status file, if
+/// any. A failed write is ignored, as it must not keep the process from
+/// terminating.
+///
+/// The fingerprint is only computed once the document is known to be
+/// written, and is written as 16 hexadecimal digits, along with the version
+/// of the algorithm which computed it. The class and the fields given
+/// through `Fatal`, if any, are taken from `options` along with the code.
+#[cfg(feature = "status-file")]
+pub fn write_fatal_status(
+    time:        Duration,
+    termination: Termination,
+    options:     &FailureOptions<'_>,
+    summary:     &str,
+    fingerprint: impl FnOnce() -> u64,
+) {
+    let Some(path) = config::settings().status_file else {
+        return;
+    };
+
+    let code:        String      = options.code.map_or_else(|| String::from("null"), json_string);
+    let class:       &str        = options.class.unwrap_or(termination.class());
+    let fields:      Vec<String> = options.fields.iter().map(|(name, value)| format!("{}: {}", json_string(name), json_string(value))).collect();
+    let fingerprint: u64         = fingerprint();
+    let _ = write_atomically(&path, &format!(
+        "{{\"schema_version\": {SCHEMA_VERSION}, \"status\": \"fatal\", \"timestamp\": {}.{:03}, \"exit_code\": {}, \"class\": {}, \"code\": {code}, \"fingerprint\": \"{fingerprint:016x}\", \"fingerprint_version\": {FINGERPRINT_VERSION}, \"fields\": {{{}}}, \"summary\": {}{}}}\n",
+        time.as_secs(), time.subsec_millis(), termination.exit_code(), json_string(class), fields.join(", "), json_string(summary), raw_frames()
+    ));
+}
+
+/// Renders the raw frames of the failing site as the `raw_frames` array of a
+/// status document, which is only present with the `backtrace-budget`
+/// feature.
+#[cfg(feature = "status-file")]
+fn raw_frames() -> String {
+    #[cfg(feature = "backtrace-budget")]
+    {
+        let frames: Vec<String> = symbolicate::raw_frames().iter().map(|frame| json_string(&frame.to_string())).collect();
+        format!(", \"raw_frames\": [{}]", frames.join(", "))
+    }
+    #[cfg(not(feature = "backtrace-budget"))]
+    String::new()
+}
+
+/// Does nothing, as there is no status file without the `status-file`
+/// feature.
+#[cfg(not(feature = "status-file"))]
+#[inline(always)]
+pub fn write_fatal_status(_: Duration, _: Termination, _: &FailureOptions<'_>, _: &str, _: impl FnOnce() -> u64) {}
+
+/// Writes a `"status": "ok"` document to the status file, so a supervisor can
+/// tell a clean exit from a process which never got that far. Call it right
+/// before exiting normally.
+///
+/// Nothing is written unless both a status file and
+/// `Config::clean_exit_status` are configured.
+#[cfg(feature = "status-file")]
+pub fn mark_clean_exit() -> io::Result<()> {
+    let settings: config::Settings = config::settings();
+    let path:     PathBuf          = match settings.status_file {
+        Some(path) if settings.clean_exit_status => path,
+        _                                        => return Ok(()),
+    };
+
+    let time: Duration = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    write_atomically(&path, &format!(
+        "{{\"schema_version\": {SCHEMA_VERSION}, \"status\": \"ok\", \"timestamp\": {}.{:03}}}\n",
+        time.as_secs(), time.subsec_millis()
+    ))
+}
+
+
+/// Writes `contents` to a temporary file next to `path`, then renames it over
+/// `path`, so a reader never sees a partially written document.
+#[cfg(feature = "status-file")]
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let mut temp: PathBuf = path.to_path_buf();
+    temp.as_mut_os_string().push(format!(".{}.tmp", process::id()));
+
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+/// Renders `value` as a JSON string literal.
+#[cfg(feature = "status-file")]
+fn json_string(value: &str) -> String {
+    let mut json: String = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"'          => json.push_str("\\\""),
+            '\\'         => json.push_str("\\\\"),
+            '\n'         => json.push_str("\\n"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c            => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
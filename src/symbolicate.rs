@@ -0,0 +1,174 @@
+#[cfg(feature = "backtrace-budget")]
+use std::backtrace::BacktraceStatus;
+#[cfg(feature = "backtrace-budget")]
+use std::fmt::{self, Display, Write};
+#[cfg(feature = "backtrace-budget")]
+use std::fs;
+#[cfg(feature = "backtrace-budget")]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(feature = "backtrace-budget")]
+use std::thread;
+#[cfg(feature = "backtrace-budget")]
+use std::time::Duration;
+
+#[cfg(feature = "backtrace-budget")]
+use crate::config;
+
+
+/// The time symbolication may take unless configured otherwise.
+#[cfg(feature = "backtrace-budget")]
+pub(crate) const DEFAULT_BUDGET: Duration = Duration::from_secs(1);
+
+
+/// A frame whose symbols haven't been resolved, along with the module it
+/// belongs to if known.
+#[cfg(feature = "backtrace-budget")]
+pub(crate) struct RawFrame {
+    address: usize,
+    module:  Option<(String, usize)>,
+}
+
+#[cfg(feature = "backtrace-budget")]
+impl Display for RawFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.address)?;
+        if let Some((path, offset)) = &self.module {
+            write!(f, " ({path} + {offset:#x})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A file mapped into the process, as listed in `/proc/self/maps`.
+#[cfg(feature = "backtrace-budget")]
+struct Module {
+    start:  usize,
+    end:    usize,
+    offset: usize,
+    path:   String,
+}
+
+
+/// Captures the frames of the current thread, without resolving any of them.
+#[cfg(feature = "backtrace-budget")]
+pub(crate) fn raw_frames() -> Vec<RawFrame> {
+    let modules: Vec<Module> = modules();
+    backtrace::Backtrace::new_unresolved().frames().iter().map(|frame| {
+        let address: usize                  = frame.ip() as usize;
+        let module:  Option<(String, usize)> = modules.iter()
+            .find(|module| (module.start..module.end).contains(&address))
+            .map(|module| (module.path.clone(), address - module.start + module.offset));
+        RawFrame { address, module }
+    }).collect()
+}
+
+/// Lists the files mapped into the process, so that frames can be given as
+/// offsets into them. Only Linux lists them, elsewhere frames are left as bare
+/// addresses.
+#[cfg(feature = "backtrace-budget")]
+fn modules() -> Vec<Module> {
+    let Ok(maps) = fs::read_to_string("/proc/self/maps") else {
+        return Vec::new();
+    };
+
+    maps.lines().filter_map(|line| {
+        let mut fields = line.split_whitespace();
+        let (start, end) = fields.next()?.split_once('-')?;
+        let offset: &str = fields.nth(1)?;
+        let path:   &str = fields.nth(2)?;
+        if !path.starts_with('/') {
+            return None;
+        }
+        Some(Module {
+            start:  usize::from_str_radix(start, 16).ok()?,
+            end:    usize::from_str_radix(end, 16).ok()?,
+            offset: usize::from_str_radix(offset, 16).ok()?,
+            path:   path.to_string(),
+        })
+    }).collect()
+}
+
+/// The time symbolication may take, which `IMPASS_BACKTRACE_BUDGET_MS`
+/// overrides.
+#[cfg(feature = "backtrace-budget")]
+fn budget() -> Duration {
+    std::env::var("IMPASS_BACKTRACE_BUDGET_MS").ok()
+        .and_then(|millis| millis.parse().ok())
+        .map_or_else(|| config::settings().backtrace_budget, Duration::from_millis)
+}
+
+
+/// Renders `error` as anyhow would with `{:?}`, giving up on resolving its
+/// backtrace once the budget runs out.
+///
+/// The frames of the failing site are captured up front, which is cheap, and
+/// the error is then rendered on a thread of its own. If that takes longer
+/// than the budget, the chain is rendered without the backtrace, followed by
+/// the raw frames as addresses and module offsets which can be resolved
+/// offline. The rendering thread is left to finish in the background.
+#[cfg(feature = "backtrace-budget")]
+pub fn render_error(error: anyhow::Error) -> String {
+    if error.backtrace().status() != BacktraceStatus::Captured {
+        return format!("{error:?}");
+    }
+
+    let frames: Vec<RawFrame> = raw_frames();
+    let chain:  String        = render_chain(&error);
+    let budget: Duration      = budget();
+
+    let (sender, receiver): (Sender<String>, Receiver<String>) = mpsc::channel();
+    let rendered: Option<String> = thread::Builder::new()
+        .name(String::from("impass-symbolicate"))
+        .spawn(move || {
+            let _ = sender.send(format!("{error:?}"));
+        })
+        .ok()
+        .and_then(|_| receiver.recv_timeout(budget).ok());
+    if let Some(rendered) = rendered {
+        return rendered;
+    }
+
+    let mut report: String = format!(
+        "{chain}\n\nStack backtrace (unresolved, as symbolication ran out of its {}ms budget):",
+        budget.as_millis()
+    );
+    for (index, frame) in frames.iter().enumerate() {
+        let _ = write!(report, "\n{index:>4}: {frame}");
+    }
+    report
+}
+
+/// Renders the chain of `error` just like its `{:?}` rendering, but without
+/// the backtrace.
+#[cfg(feature = "backtrace-budget")]
+fn render_chain(error: &anyhow::Error) -> String {
+    let mut chain:  String                                  = error.to_string();
+    let     causes: Vec<&(dyn std::error::Error + 'static)> = error.chain().skip(1).collect();
+    if !causes.is_empty() {
+        chain.push_str("\n\nCaused by:");
+    }
+    for (index, cause) in causes.iter().enumerate() {
+        let (first, rest): (String, &str) = match causes.len() {
+            1 => (String::from("    "), "    "),
+            _ => (format!("{index:>5}: "), "       "),
+        };
+        for (line_index, line) in cause.to_string().lines().enumerate() {
+            chain.push('\n');
+            if line_index == 0 {
+                chain.push_str(&first);
+            } else if !line.is_empty() {
+                chain.push_str(rest);
+            }
+            chain.push_str(line);
+        }
+    }
+    chain
+}
+
+/// Renders `error` as anyhow would with `{:?}`, as there is no budget without
+/// the `backtrace-budget` feature.
+#[cfg(not(feature = "backtrace-budget"))]
+#[inline]
+pub fn render_error(error: anyhow::Error) -> String {
+    format!("{error:?}")
+}
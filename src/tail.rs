@@ -0,0 +1,27 @@
+/// The value of a `fatal!` block made up of a lone call, such as
+/// `fatal!(do_thing())`, before it is returned from the block.
+///
+/// `into_result()` is an inherent method for a `Result` whose error is an
+/// `anyhow::Error`, and a method of `ConvertTail` for any other. The inherent
+/// one is picked whenever it may apply, so that an error type left to
+/// inference is taken to be an `anyhow::Error`, while any other is converted.
+pub struct Tail<R>(pub R);
+
+impl<T> Tail<Result<T, anyhow::Error>> {
+    #[inline(always)]
+    pub fn into_result(self) -> Result<T, anyhow::Error> {
+        self.0
+    }
+}
+
+/// Converts the error of a `Tail` into an `anyhow::Error`.
+pub trait ConvertTail<T> {
+    fn into_result(self) -> Result<T, anyhow::Error>;
+}
+
+impl<T, E: Into<anyhow::Error>> ConvertTail<T> for Tail<Result<T, E>> {
+    #[inline(always)]
+    fn into_result(self) -> Result<T, anyhow::Error> {
+        self.0.map_err(Into::into)
+    }
+}
@@ -0,0 +1,73 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+
+/// Noted at the end of the report of a `no_catch` site which fails while an
+/// `UnwindRequiredGuard` is alive.
+pub const CLEANUP_SKIPPED_NOTE: &str = "\n\nnote: an unwind-required guard is alive, but this site is `no_catch`, so its cleanup was skipped";
+
+
+thread_local! {
+    /// The number of guards alive on the current thread.
+    static GUARDS: Cell<usize> = const { Cell::new(0) };
+}
+
+
+/// Declares that the current scope relies on unwinding for its cleanup, for as
+/// long as it is alive. Returned by `register_unwind_required_guard()`.
+///
+/// While any guard is alive on a thread, a fatal site on that thread which
+/// would otherwise exit (`#![exit(code)]`) or abort (`#![abort]`) panics
+/// instead, after emitting its report just the same. The unwind then runs the
+/// `Drop` implementations of the frames it passes through, such as the
+/// rollback of a transaction. `#![no_catch]` sites still abort, as they must
+/// never be caught, and note in their report that the cleanup was skipped.
+///
+/// The guard is tied to the thread it was registered on, as only that thread
+/// unwinds.
+#[must_use = "the guard only has an effect while it is alive"]
+#[derive(Debug)]
+pub struct UnwindRequiredGuard {
+    _thread: PhantomData<*const ()>,
+}
+
+/// Registers an `UnwindRequiredGuard` on the current thread, which lasts until
+/// it is dropped.
+///
+/// ```rust,should_panic
+/// use impass::fatal;
+///
+/// struct Transaction;
+///
+/// impl Drop for Transaction {
+///     fn drop(&mut self) {
+///         println!("Rolled back");
+///     }
+/// }
+///
+/// let _guard:       impass::UnwindRequiredGuard = impass::register_unwind_required_guard();
+/// let _transaction: Transaction                 = Transaction;
+///
+/// // Panics rather than exiting, so the transaction is rolled back.
+/// fatal! {
+///     #![exit(2)]
+///     let rows: u32 = "many".parse::<u32>()?;
+///     Ok(rows)
+/// };
+/// ```
+pub fn register_unwind_required_guard() -> UnwindRequiredGuard {
+    GUARDS.with(|guards| guards.set(guards.get() + 1));
+    UnwindRequiredGuard { _thread: PhantomData }
+}
+
+impl Drop for UnwindRequiredGuard {
+    fn drop(&mut self) {
+        GUARDS.with(|guards| guards.set(guards.get() - 1));
+    }
+}
+
+
+/// Whether an `UnwindRequiredGuard` is alive on the current thread.
+pub fn unwind_required() -> bool {
+    GUARDS.with(|guards| guards.get() > 0)
+}
@@ -0,0 +1,195 @@
+use std::fmt::{self, Display};
+use std::panic::Location;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::defaults::Terminate;
+use crate::failure::{self, FailureOptions};
+use crate::guard;
+
+
+/// The handle a `fatal_watchdog!` body pings at its progress points, which
+/// keeps the watchdog from considering it stalled.
+///
+/// It is created by the watchdog and named by its `heartbeat` argument. It is
+/// cheap to clone, so that it may be handed to the tasks doing the work.
+#[derive(Clone, Debug)]
+pub struct Heartbeat {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state:   Mutex<State>,
+    changed: Condvar,
+}
+
+/// The last beat, and whether the watchdog is still waiting for the next one.
+#[derive(Debug)]
+struct State {
+    instant: Instant,
+    time:    SystemTime,
+    label:   Option<String>,
+    armed:   bool,
+}
+
+impl Heartbeat {
+
+    /// Creates a handle, whose first beat is its creation.
+    fn new() -> Self {
+        Heartbeat {
+            shared: Arc::new(Shared {
+                state:   Mutex::new(State {
+                    instant: Instant::now(),
+                    time:    SystemTime::now(),
+                    label:   None,
+                    armed:   false,
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Records progress, restarting the stall window.
+    pub fn beat(&self) {
+        self.record(None);
+    }
+
+    /// Records progress along with a label naming it, which the report of a
+    /// stall includes if it was the last one.
+    pub fn beat_with(&self, label: impl Into<String>) {
+        self.record(Some(label.into()));
+    }
+
+    fn record(&self, label: Option<String>) {
+        let mut state: MutexGuard<'_, State> = self.shared.lock();
+        state.instant = Instant::now();
+        state.time    = SystemTime::now();
+        state.label   = label;
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+
+/// Monitors a `Heartbeat` on a thread of its own for as long as it is alive,
+/// as armed by `fatal_watchdog!` around its body.
+///
+/// If no beat arrives within the stall window, the process is reported and
+/// aborted from the monitoring thread, as the stalled body can't be made to
+/// unwind. Dropping the watchdog, once the body completes or unwinds, disarms
+/// it.
+#[derive(Debug)]
+pub struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+impl Watchdog {
+
+    /// Creates the heartbeat of a `fatal_watchdog!` body, which only the
+    /// watchdog can.
+    pub fn heartbeat() -> Heartbeat {
+        Heartbeat::new()
+    }
+
+    /// Arms a watchdog over `heartbeat`, whose stall is reported with `reason`
+    /// and located at the caller. The stall window starts right away.
+    #[track_caller]
+    pub fn arm(heartbeat: &Heartbeat, stall: Duration, reason: &dyn Display) -> Watchdog {
+        let location: &'static Location<'static> = Location::caller();
+        let reason:   String                     = guard::render_reason(reason);
+        let shared:   Arc<Shared>                = Arc::clone(&heartbeat.shared);
+        {
+            let mut state: MutexGuard<'_, State> = shared.lock();
+            state.instant = Instant::now();
+            state.time    = SystemTime::now();
+            state.armed   = true;
+        }
+
+        // Without a thread, the body is merely left unmonitored.
+        let monitored: Arc<Shared> = Arc::clone(&shared);
+        let _ = thread::Builder::new()
+            .name(String::from("impass-watchdog"))
+            .spawn(move || monitor(&monitored, stall, &reason, location));
+        Watchdog { shared }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.lock().armed = false;
+        self.shared.changed.notify_all();
+    }
+}
+
+
+/// Waits for the window of the last beat to pass, until the watchdog is
+/// disarmed or the body stalls.
+fn monitor(shared: &Shared, stall: Duration, reason: &str, location: &'static Location<'static>) {
+    let mut state: MutexGuard<'_, State> = shared.lock();
+    while state.armed {
+        let elapsed: Duration = state.instant.elapsed();
+        if elapsed >= stall {
+            let stalled: Stalled = Stalled { stall, time: state.time, label: state.label.take() };
+            drop(state);
+            fail(stalled, reason, location);
+        }
+        state = shared.changed.wait_timeout(state, stall - elapsed).unwrap_or_else(PoisonError::into_inner).0;
+    }
+}
+
+
+/// The error a stalled body fails with.
+#[derive(Debug)]
+struct Stalled {
+    stall: Duration,
+    time:  SystemTime,
+    label: Option<String>,
+}
+
+impl Display for Stalled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time: Duration = timestamp(self.time);
+        write!(f, "no heartbeat within {:?}, the last one was at {}.{:03}", self.stall, time.as_secs(), time.subsec_millis())?;
+        if let Some(label) = &self.label {
+            write!(f, " ({label})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Stalled {}
+
+/// The time since the epoch of `time`, which the deterministic mode zeroes.
+fn timestamp(time: SystemTime) -> Duration {
+    if failure::deterministic() {
+        return Duration::ZERO;
+    }
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+
+/// Reports a stall at `location`, and aborts.
+///
+/// This is the failure of a site with nothing but a reason, which aborts
+/// rather than panics, as it is reported from the monitoring thread.
+fn fail(stalled: Stalled, reason: &str, location: &'static Location<'static>) -> ! {
+
+    // The backtrace is the one of the monitoring thread, which says nothing
+    // about the stalled body, so it is always left out.
+    let options: FailureOptions<'_> = FailureOptions {
+        code:          None,
+        terminate:     Terminate::Abort,
+        deterministic: false,
+        backtrace:     false,
+        marker:        None,
+        class:         None,
+        fields:        &[],
+    };
+    failure::fail(location, options, reason, anyhow::Error::new(stalled), |_| String::new())
+}
@@ -0,0 +1,65 @@
+//! Restricts the reports to ASCII through `config().ascii_only(true)`, which
+//! every test of this binary relies on.
+
+use std::fmt::{self, Display};
+use std::panic;
+
+use impass::{fatal, FatalExt};
+
+
+// Runs `f` with the reports restricted to ASCII, returning the message of the
+// panic it is expected to raise.
+fn panic_message<T>(f: impl FnOnce() -> T + panic::UnwindSafe) -> String {
+    impass::config().ascii_only(true);
+    match panic::catch_unwind(f) {
+        Ok(_)      => panic!("Expected a panic"),
+        Err(error) => *error.downcast::<String>().unwrap(),
+    }
+}
+
+// Fails with `message`.
+fn fail_with(message: &'static str) -> Result<(), anyhow::Error> {
+    Err(anyhow::Error::msg(message))
+}
+
+
+// Emoji and CJK text are escaped, just as Rust escapes them.
+#[test]
+fn test_ascii_escapes() {
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to upload the photo")]
+        fail_with("写真のアップロード 😞")?;
+        Ok(())
+    });
+    assert!(message.is_ascii(), "{message}");
+    assert!(message.contains(
+        "Failed to upload the photo\n\nCaused by:\n    \\u{5199}\\u{771f}\\u{306e}\\u{30a2}\\u{30c3}\\u{30d7}\\u{30ed}\\u{30fc}\\u{30c9} \\u{1f61e}"
+    ), "{message}");
+}
+
+// Typographic and box drawing characters are spelled out.
+#[test]
+fn test_ascii_spelled_out() {
+    let message: String = panic_message(|| fail_with("step 1 → step 2 … «done» ─┼─ │").or_fatal("The “migration” failed — again"));
+    assert!(message.contains("The \"migration\" failed - again\n\nCaused by:\n    step 1 -> step 2 ... <<done>> -+- |"), "{message}");
+}
+
+// A value whose formatting panicked is noted in ASCII as well.
+#[test]
+fn test_ascii_format_panicked() {
+    struct Panics;
+
+    impl Display for Panics {
+        fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+            panic!("the value can't be formatted")
+        }
+    }
+
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed with {}", Panics)]
+        fail_with("the cause")?;
+        Ok(())
+    });
+    assert!(message.contains("<<format panicked>>\n\nCaused by:\n    the cause"), "{message}");
+    assert!(message.contains("note: values shown as <<format panicked>> panicked while being formatted"), "{message}");
+}
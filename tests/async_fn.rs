@@ -0,0 +1,206 @@
+//! Uses `fatal_fn` on `async fn`s and `fatal!` blocks which await, driven by a
+//! minimal executor rather than pulling in a runtime.
+
+mod common;
+
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+
+use impass::{fatal, fatal_fn, fatal_impl, fatal_ok};
+use common::{panic_message, MyError};
+
+
+// A dummy fallible function to test with, which suspends once before
+// completing.
+async fn might_fail(should_fail: bool) -> Result<i32, MyError> {
+    YieldOnce(false).await;
+    if should_fail {
+        Err(MyError::OperationFailed)
+    } else {
+        Ok(42)
+    }
+}
+
+
+// A future which is pending the first time it is polled.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+// Polls `future` to completion on the current thread.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future: Pin<&mut F> = pin!(future);
+    let mut cx:     Context<'_> = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+
+#[fatal_fn(reason = "Failed to measure {}", text, also_fallible = "try_measure")]
+async fn measure(text: &str, should_fail: bool) -> usize {
+    if text.is_empty() {
+        fatal_ok!(0);
+    }
+    let value: i32 = might_fail(should_fail).await?;
+    Ok(text.len() + value as usize)
+}
+
+#[fatal_fn(ensure_ok(|word| !word.is_empty(), "the first word of {:?} is empty", text))]
+async fn first_word(text: &str) -> &str {
+    might_fail(false).await?;
+    Ok(text.split(' ').next()?)
+}
+
+
+// An async function evaluates to the inner value, and may borrow its
+// parameters.
+#[test]
+fn test_async_fatal_fn_success() {
+    assert_eq!(block_on(measure("text", false)), 46);
+    assert_eq!(block_on(measure("", true)), 0);
+    assert_eq!(block_on(first_word("hello world")), "hello");
+}
+
+// A failing async function reports its reason just like a synchronous one.
+#[test]
+fn test_async_fatal_fn_panic() {
+    let message: String = panic_message(|| block_on(measure("text", true)));
+    assert!(message.contains("Failed to measure text\n\nCaused by:\n    This operation failed"), "{message}");
+
+    let message: String = panic_message(|| block_on(first_word(" world")));
+    assert!(message.contains("the first word of \" world\" is empty"), "{message}");
+}
+
+// The fallible sibling of an async function is async as well.
+#[test]
+fn test_async_fatal_fn_also_fallible() {
+    assert_eq!(block_on(try_measure("text", false)).unwrap(), 46);
+    assert_eq!(block_on(try_measure("text", true)).unwrap_err().to_string(), "This operation failed");
+}
+
+// A block which awaits is evaluated in place, borrowing the locals it uses.
+#[test]
+fn test_async_fatal_block_success() {
+    let value: usize = block_on(async {
+        let text:  String = "text".to_string();
+        let value: usize  = fatal! {
+            #![reason("Failed to measure {}", text)]
+            let value: i32 = might_fail(false).await?;
+            Ok(text.len() + value as usize)
+        };
+        value + text.len()
+    });
+    assert_eq!(value, 50);
+
+    // The expression form awaits in place as well.
+    assert_eq!(block_on(async { fatal!(might_fail(false).await) }), 42);
+}
+
+// A failing block which awaits reports its reason.
+#[test]
+fn test_async_fatal_block_panic() {
+    let message: String = panic_message(|| block_on(async {
+        fatal! {
+            #![reason("Failed to await the value")]
+            let value: i32 = might_fail(true).await?;
+            Ok(value)
+        }
+    }));
+    assert!(message.contains("Failed to await the value\n\nCaused by:\n    This operation failed"), "{message}");
+}
+
+// Awaits within nested async blocks don't make the block itself async, and a
+// block may evaluate to a future.
+#[test]
+fn test_async_fatal_block_nested() {
+    let future = fatal! {
+        let value: i32 = "42".parse()?;
+        Ok(async move { might_fail(false).await.unwrap() + value })
+    };
+    assert_eq!(block_on(future), 84);
+
+    let value: i32 = block_on(async {
+        fatal! {
+            let future = async { might_fail(false).await };
+            let value:  i32 = future.await?;
+            let double: i32 = async { value * 2 }.await;
+            Ok(double)
+        }
+    });
+    assert_eq!(value, 84);
+}
+
+// Awaits within the arguments of other macros are found as well.
+#[test]
+fn test_async_fatal_block_macro() {
+    let value: i32 = block_on(async {
+        fatal! {
+            assert_eq!(might_fail(false).await?, 42);
+            Ok(1)
+        }
+    });
+    assert_eq!(value, 1);
+}
+
+// A block which awaits may return from the enclosing async function, or move
+// on to the next iteration of its loop.
+#[test]
+fn test_async_fatal_block_control_flow() {
+    async fn total(flags: &[Option<bool>]) -> i32 {
+        let mut total: i32 = 0;
+        for flag in flags {
+            total += fatal! {
+                let Some(should_fail) = *flag else {
+                    continue;
+                };
+                let value: i32 = might_fail(should_fail).await?;
+                if total > 0 {
+                    return -value;
+                }
+                Ok(value)
+            };
+        }
+        total
+    }
+
+    assert_eq!(block_on(total(&[None, Some(false), None])), 42);
+    assert_eq!(block_on(total(&[Some(false), Some(false)])), -42);
+}
+
+struct Meter {
+    scale: usize,
+}
+
+#[fatal_impl(reason = "Failed to measure")]
+impl Meter {
+    async fn measure(&self, should_fail: bool) -> usize {
+        let value: i32 = might_fail(should_fail).await?;
+        Ok(value as usize * self.scale)
+    }
+}
+
+// Async methods within a `#[fatal_impl]` block are wrapped like async `fatal_fn`
+// functions.
+#[test]
+fn test_async_fatal_impl() {
+    let meter: Meter = Meter { scale: 2 };
+    assert_eq!(block_on(meter.measure(false)), 84);
+
+    let message: String = panic_message(|| block_on(meter.measure(true)));
+    assert!(message.contains("Failed to measure\n\nCaused by:\n    This operation failed"), "{message}");
+}
@@ -0,0 +1,89 @@
+//! Reports impossible states through `fatal_unreachable!`, `fatal_todo!` and
+//! `fatal_unimplemented!`, which diverge just like their `std` counterparts.
+
+mod common;
+
+use impass::{fatal_todo, fatal_unimplemented, fatal_unreachable};
+use common::panic_message;
+
+
+// What a reason is followed by before the error, which is only a separator
+// without the `std` feature.
+const CAUSED_BY: &str = if cfg!(feature = "std") { "\n\nCaused by:\n    " } else { ": " };
+
+
+// The states of a dummy state machine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Idle,
+    Running(u32),
+    Stopped,
+}
+
+// The progress of the machine, which is never asked for once it stopped.
+fn progress(state: State) -> u32 {
+    match state {
+        State::Idle          => 0,
+        State::Running(done) => done,
+        State::Stopped       => fatal_unreachable!("state machine in state {:?}", state),
+    }
+}
+
+// The next state of the machine, which can't resume yet.
+fn resume(state: State) -> State {
+    match state {
+        State::Idle          => State::Running(0),
+        State::Running(done) => State::Running(done + 1),
+        State::Stopped       => fatal_todo!("resuming from {state:?}"),
+    }
+}
+
+// The name of a state, which only the idle one has.
+fn name(state: State) -> &'static str {
+    match state {
+        State::Idle => "idle",
+        _           => fatal_unimplemented!(),
+    }
+}
+
+// Never returns.
+fn halt() -> ! {
+    fatal_unreachable!("halted")
+}
+
+
+// The other arms are left alone.
+#[test]
+fn test_bug_arms() {
+    assert_eq!(progress(State::Running(3)), 3);
+    assert_eq!(resume(State::Idle), State::Running(0));
+    assert_eq!(name(State::Idle), "idle");
+}
+
+// The formatted state is the reason, followed by what was reached.
+#[test]
+fn test_fatal_unreachable() {
+    let message: String = panic_message(|| progress(State::Stopped));
+    assert!(message.contains(&format!("state machine in state Stopped{CAUSED_BY}entered unreachable code (this indicates a bug)")), "{message}");
+}
+
+// The arguments may be captured from the format string.
+#[test]
+fn test_fatal_todo() {
+    let message: String = panic_message(|| resume(State::Stopped));
+    assert!(message.contains(&format!("resuming from Stopped{CAUSED_BY}not yet implemented (this indicates a bug)")), "{message}");
+}
+
+// Without a format string, the reason is the default one.
+#[test]
+fn test_fatal_unimplemented() {
+    let message: String = panic_message(|| name(State::Running(1)));
+    assert!(message.contains(&format!("{CAUSED_BY}not implemented (this indicates a bug)")), "{message}");
+}
+
+// The invocation has the type `!`.
+#[test]
+fn test_fatal_unreachable_diverges() {
+    let message: String = panic_message(|| halt());
+    assert!(message.contains("halted"), "{message}");
+}
@@ -0,0 +1,39 @@
+//! The helpers shared by the integration tests, each of which only uses some
+//! of them.
+
+#![allow(dead_code)]
+
+use std::any::Any;
+use std::hint::black_box;
+use std::panic::{self, AssertUnwindSafe};
+
+use thiserror::Error;
+
+
+// Declare an error type for demonstration purposes.
+#[derive(Error, Debug)]
+pub enum MyError {
+    #[error("This operation failed")]
+    OperationFailed
+}
+
+
+// A dummy fallible function to test with. The flag goes through `black_box`,
+// so that neither path is optimised away from the overhead tests.
+pub fn might_fail(should_fail: bool) -> Result<i32, MyError> {
+    if black_box(should_fail) {
+        Err(MyError::OperationFailed)
+    } else {
+        Ok(42)
+    }
+}
+
+
+// Runs `f`, returning the message of the panic it is expected to raise.
+pub fn panic_message<T>(f: impl FnOnce() -> T) -> String {
+    let result: Result<T, Box<dyn Any + Send>> = panic::catch_unwind(AssertUnwindSafe(f));
+    match result {
+        Ok(_)      => panic!("Expected a panic"),
+        Err(error) => *error.downcast::<String>().unwrap(),
+    }
+}
@@ -0,0 +1,245 @@
+//! Pins the panic message layout that downstream `should_panic` tests rely on.
+//!
+//! The tests in `legacy` only hold with the `legacy-format` feature enabled,
+//! and the tests in `current` only with it disabled, while the tests in
+//! `portable` must hold regardless of the feature set. The
+//! tests in `default_message` cover both states of the
+//! `legacy-default-message` feature. Run every configuration with
+//! `cargo test`, `cargo test --no-default-features --features std` and
+//! `cargo test --no-default-features --features std,<feature>` for each feature.
+
+mod common;
+
+use impass::{fatal, fatal_fn};
+use common::{might_fail, panic_message};
+
+
+#[cfg(feature = "legacy-format")]
+mod legacy {
+    use super::*;
+
+    // The message starts with a newline, followed by the default context.
+    #[test]
+    #[should_panic(expected = "\nAn unrecoverable error occurred\n\nCaused by:\n    This operation failed")]
+    fn test_legacy_default_message() {
+        fatal! {
+            let _: i32 = might_fail(true)?;
+            Ok(())
+        }
+    }
+
+    // The default context is the one exported by the crate.
+    #[test]
+    fn test_legacy_default_message_exported() {
+        assert_eq!(impass::DEFAULT_MESSAGE, "An unrecoverable error occurred");
+    }
+
+    // The reason replaces the default context line.
+    #[test]
+    #[should_panic(expected = "\nFailed with a specific error\n\nCaused by:\n    This operation failed")]
+    fn test_legacy_reason_message() {
+        fatal! {
+            #![reason("Failed with a specific error")]
+            let _: i32 = might_fail(true)?;
+            Ok(())
+        }
+    }
+
+    // `fatal_fn` produces the exact same layout.
+    #[test]
+    #[should_panic(expected = "\nFailed with a specific error\n\nCaused by:\n    This operation failed")]
+    #[fatal_fn(reason = "Failed with a specific error")]
+    fn test_legacy_fatal_fn_message() {
+        let _: i32 = might_fail(true)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "legacy-format"))]
+mod current {
+    use super::*;
+    use impass::FatalExt;
+
+    // Runs `f`, returning the first line of the panic it is expected to raise.
+    fn first_line<T>(f: impl FnOnce() -> T + std::panic::UnwindSafe) -> String {
+        match std::panic::catch_unwind(f) {
+            Ok(_)      => panic!("Expected a panic"),
+            Err(error) => error.downcast::<String>().unwrap().lines().next().unwrap().to_string(),
+        }
+    }
+
+    // Splits `first` into the line and the column following `prefix`, and
+    // the rest of it, asserting that both of them are numbers.
+    fn split_location<'a>(first: &'a str, prefix: &str) -> (u32, u32, &'a str) {
+        let rest:   &str = first.strip_prefix(prefix).unwrap_or_else(|| panic!("{first:?} should begin with {prefix:?}"));
+        let mut parts    = rest.splitn(3, ':');
+        let line:   u32  = parts.next().and_then(|line| line.parse().ok()).unwrap_or_else(|| panic!("{first:?} should have a line"));
+        let column: u32  = parts.next().and_then(|column| column.parse().ok()).unwrap_or_else(|| panic!("{first:?} should have a column"));
+        (line, column, parts.next().unwrap_or_default())
+    }
+
+    // The first line is the reason, prefixed with the location of the block.
+    #[test]
+    fn test_current_reason_first() {
+        let line:  u32    = line!() + 1;
+        let first: String = first_line(|| fatal! {
+            #![reason("Failed with a specific error")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        });
+        let (at, _, rest) = split_location(&first, &format!("fatal error at {}:", file!()));
+        assert_eq!((at, rest), (line, " Failed with a specific error"));
+    }
+
+    // The chain follows the reason on the next lines, without a leading
+    // newline.
+    #[test]
+    #[should_panic(expected = ": Failed with a specific error\n\nCaused by:\n    This operation failed")]
+    fn test_current_chain_after() {
+        fatal! {
+            #![reason("Failed with a specific error")]
+            let _: i32 = might_fail(true)?;
+            Ok(())
+        }
+    }
+
+    // The line `fails_with_code` is declared on.
+    const FAILS_WITH_CODE_LINE: u32 = line!() + 3;
+
+    #[fatal_fn(reason = "Failed with a specific error", code = "E0042")]
+    fn fails_with_code() -> i32 {
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    }
+
+    // An error code joins the location, on the same line as the reason.
+    #[test]
+    fn test_current_code_first() {
+        let first: String = first_line(fails_with_code);
+        let (at, _, rest) = split_location(&first, &format!("fatal error [E0042] at {}:", file!()));
+        assert_eq!((at, rest), (FAILS_WITH_CODE_LINE, " Failed with a specific error"));
+    }
+
+    // A block without a reason puts the default message first.
+    #[test]
+    fn test_current_default_first() {
+        let line:  u32    = line!() + 1;
+        let first: String = first_line(|| fatal! {
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        });
+        let (at, _, rest) = split_location(&first, &format!("fatal error at {}:", file!()));
+        assert_eq!((at, rest), (line, format!(" {}", super::default_message::default_message(line)).as_str()));
+    }
+
+    // `FatalExt` puts the reason first just the same.
+    #[test]
+    fn test_current_or_fatal_first() {
+        let line:  u32    = line!() + 1;
+        let first: String = first_line(|| might_fail(true).or_fatal("Failed with a specific error"));
+        let (at, _, rest) = split_location(&first, &format!("fatal error at {}:", file!()));
+        assert_eq!((at, rest), (line, " Failed with a specific error"));
+    }
+}
+
+mod portable {
+    use super::*;
+
+    // Matching on the reason alone works with every format.
+    #[test]
+    #[should_panic(expected = "Failed with a specific error")]
+    fn test_portable_reason() {
+        fatal! {
+            #![reason("Failed with a specific error")]
+            let _: i32 = might_fail(true)?;
+            Ok(())
+        }
+    }
+
+    // Matching on the root cause alone works with every format.
+    #[test]
+    #[should_panic(expected = "This operation failed")]
+    #[fatal_fn]
+    fn test_portable_cause() {
+        let _: i32 = might_fail(true)?;
+        Ok(())
+    }
+
+    // A third-party panic hook, such as `human-panic`'s, extracts the payload
+    // as either a `&str` or a `String`, and so finds the whole report.
+    #[test]
+    fn test_portable_panic_hook() {
+        use std::panic::{self, PanicHookInfo};
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        let seen:     Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+        let recorder: Arc<Mutex<Option<String>>> = Arc::clone(&seen);
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info: &PanicHookInfo<'_>| {
+            if thread::current().name() != Some("impass-compat-hook") {
+                return previous(info);
+            }
+            let payload: Option<String> = match info.payload().downcast_ref::<&str>() {
+                Some(message) => Some(message.to_string()),
+                None          => info.payload().downcast_ref::<String>().cloned(),
+            };
+            *recorder.lock().unwrap() = payload;
+        }));
+
+        let failed: bool = thread::Builder::new()
+            .name(String::from("impass-compat-hook"))
+            .spawn(|| fatal! {
+                #![reason("Failed with a specific error")]
+                let value: i32 = might_fail(true)?;
+                Ok(value)
+            })
+            .unwrap()
+            .join()
+            .is_err();
+        assert!(failed);
+
+        let seen: Option<String> = seen.lock().unwrap().take();
+        let seen: String         = seen.expect("The payload should be a string");
+        assert!(seen.contains("Failed with a specific error\n\nCaused by:\n    This operation failed"), "{seen}");
+    }
+}
+
+mod default_message {
+    use super::*;
+
+    // The message used by blocks without a reason.
+    #[cfg(feature = "legacy-default-message")]
+    pub(super) fn default_message(_line: u32) -> String {
+        impass::DEFAULT_MESSAGE.to_string()
+    }
+
+    // The message used by blocks without a reason.
+    #[cfg(not(feature = "legacy-default-message"))]
+    pub(super) fn default_message(line: u32) -> String {
+        format!("fatal error at {}:{line} (no reason provided)", file!())
+    }
+
+    // Blocks without a reason use the default message as their context.
+    #[test]
+    fn test_default_message() {
+        let line:    u32    = line!() + 1;
+        let message: String = panic_message(|| fatal! {
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        });
+        assert!(message.contains(&format!("{}\n\nCaused by:\n    This operation failed", default_message(line))), "{message}");
+    }
+
+    // A reason replaces the default message in either state.
+    #[test]
+    fn test_default_message_replaced() {
+        let line:    u32    = line!() + 1;
+        let message: String = panic_message(|| fatal! {
+            #![reason("Failed with a specific error")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        });
+        assert!(!message.contains(&default_message(line)), "{message}");
+    }
+}
@@ -0,0 +1,154 @@
+//! Gives modules their own defaults through `fatal_defaults!`, which the sites
+//! within them fall back to for whatever they don't give themselves.
+
+mod common;
+
+use std::any::Any;
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+use common::might_fail;
+
+
+
+thread_local! {
+    /// Whether the panic hook was called on the current thread.
+    static HOOKED: Cell<bool> = const { Cell::new(false) };
+}
+
+// Runs `f`, returning the message of the panic it is expected to raise, and
+// whether it went through the panic hook. A deterministic site skips it. The
+// backtrace captured with `RUST_BACKTRACE` set is left out of the message.
+fn panic_message<T>(f: impl FnOnce() -> T) -> (String, bool) {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            HOOKED.with(|hooked| hooked.set(true));
+            default(info);
+        }));
+    });
+
+    HOOKED.with(|hooked| hooked.set(false));
+    let result: Result<T, Box<dyn Any + Send>> = panic::catch_unwind(AssertUnwindSafe(f));
+    let mut message: String = match result {
+        Ok(_)      => panic!("Expected a panic"),
+        Err(error) => *error.downcast::<String>().unwrap(),
+    };
+    if let Some(start) = message.find("\n\nStack backtrace:") {
+        let end: usize = message[start..].find("\n\n    try:").map_or(message.len(), |end| start + end);
+        message.replace_range(start..end, "");
+    }
+    (message, HOOKED.with(Cell::get))
+}
+
+
+mod storage {
+    use impass::{fatal, fatal_defaults, fatal_fn};
+    use super::might_fail;
+
+    fatal_defaults! { deterministic, suggest("check the disk at {}", ROOT) }
+
+    const ROOT: &str = "/var/lib/app";
+
+    pub fn read() -> i32 {
+        fatal! {
+            #![reason("Failed to read the journal")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        }
+    }
+
+    pub fn read_suggesting() -> i32 {
+        fatal! {
+            #![reason("Failed to read the index")]
+            #![suggest("rebuild the index")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        }
+    }
+
+    #[fatal_fn(reason = "Failed to write the journal")]
+    pub fn write() -> i32 {
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    }
+
+    // A child module doesn't see the trait declaring the defaults, unless it
+    // glob-imports its parent.
+    pub mod plain {
+        use impass::fatal;
+        use super::super::might_fail;
+
+        pub fn read() -> i32 {
+            fatal! {
+                #![reason("Failed to read the plain file")]
+                let value: i32 = might_fail(true)?;
+                Ok(value)
+            }
+        }
+    }
+
+    pub mod inherited {
+        use super::*;
+
+        pub fn read() -> i32 {
+            fatal! {
+                #![reason("Failed to read the inherited file")]
+                let value: i32 = might_fail(true)?;
+                Ok(value)
+            }
+        }
+    }
+
+    pub mod own {
+        use super::*;
+
+        fatal_defaults! { suggest("ask the owner") }
+
+        pub fn read() -> i32 {
+            fatal! {
+                #![reason("Failed to read the owned file")]
+                let value: i32 = might_fail(true)?;
+                Ok(value)
+            }
+        }
+    }
+}
+
+
+// A site without attributes of its own takes those of its module, whether it
+// is a block or a function.
+#[test]
+fn test_defaults_apply() {
+    for (message, hooked) in [panic_message(storage::read), panic_message(storage::write)] {
+        assert!(message.ends_with("Caused by:\n    This operation failed\n\n    try: check the disk at /var/lib/app"), "{message}");
+        assert!(!hooked, "A deterministic site should skip the panic hook");
+    }
+}
+
+// Suggestions given by the site replace those of the module.
+#[test]
+fn test_defaults_overridden() {
+    let (message, hooked) = panic_message(storage::read_suggesting);
+    assert!(message.ends_with("This operation failed\n\n    try: rebuild the index"), "{message}");
+    assert!(!hooked);
+}
+
+// A module without defaults keeps the plain report, while a glob import of
+// the parent shares its defaults, and a module's own defaults replace them.
+#[test]
+fn test_defaults_scope() {
+    let (message, hooked) = panic_message(storage::plain::read);
+    assert!(message.contains("Failed to read the plain file") && !message.contains("try:"), "{message}");
+    assert!(hooked);
+
+    let (message, hooked) = panic_message(storage::inherited::read);
+    assert!(message.ends_with("try: check the disk at /var/lib/app"), "{message}");
+    assert!(!hooked);
+
+    let (message, hooked) = panic_message(storage::own::read);
+    assert!(message.ends_with("\n\n    try: ask the owner"), "{message}");
+    assert!(!message.contains("check the disk"), "{message}");
+    assert!(hooked);
+}
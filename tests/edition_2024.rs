@@ -0,0 +1,120 @@
+//! Uses syntax and semantics introduced by the 2024 edition, which this
+//! crate is written in, within fatal bodies and signatures.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+
+use impass::{fatal, fatal_fn, fatal_ok};
+
+
+// Polls `future` to completion on the current thread.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future: Pin<&mut F> = pin!(future);
+    let mut cx:     Context<'_> = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+
+// A return type capturing its lifetime precisely through `use<..>`.
+#[fatal_fn]
+fn first_chars<'a>(text: &'a str, count: &str) -> impl Iterator<Item = char> + use<'a> {
+    let count: usize = count.parse()?;
+    Ok(text.chars().take(count))
+}
+
+// An async function calling an async closure, whose own `?` stays within it.
+#[fatal_fn(reason = "Failed to sum {}", text)]
+async fn sum(text: &str) -> i32 {
+    let parse = async |word: &str| -> Result<i32, std::num::ParseIntError> {
+        let value: i32 = word.trim().parse()?;
+        Ok(value * 10)
+    };
+    let mut total: i32 = 0;
+    for word in text.split(',') {
+        total += parse(word).await?;
+    }
+    Ok(total)
+}
+
+
+// Precise capturing in the return type of a `fatal_fn` function.
+#[test]
+fn test_precise_capturing() {
+    let text: String = String::from("hello");
+    assert_eq!(first_chars(&text, "3").collect::<String>(), "hel");
+}
+
+// Let chains may be used within a block.
+#[test]
+fn test_let_chains() {
+    let value: Option<&str> = Some("42");
+    let parsed: i32 = fatal! {
+        if let Some(text) = value && !text.is_empty() {
+            let parsed: i32 = text.parse()?;
+            fatal_ok!(parsed);
+        }
+        Ok(0)
+    };
+    assert_eq!(parsed, 42);
+}
+
+// Async closures may be called and awaited within async bodies.
+#[test]
+fn test_async_closures() {
+    assert_eq!(block_on(sum("1, 2, 3")), 60);
+
+    let total: i32 = block_on(async {
+        let double = async |value: i32| value * 2;
+        fatal! {
+            let value: i32 = "21".parse()?;
+            Ok(double(value).await)
+        }
+    });
+    assert_eq!(total, 42);
+}
+
+// Raw borrows are left as they are.
+#[test]
+fn test_raw_borrows() {
+    let value: i32 = 42;
+    let read:  i32 = fatal! {
+        let pointer: *const i32 = &raw const value;
+        let offset:  i32        = "0".parse()?;
+        Ok(unsafe { *pointer } + offset)
+    };
+    assert_eq!(read, 42);
+}
+
+// The temporaries of the final `Ok(...)` are dropped before the block's
+// locals, so it may borrow from them, whichever form the block is expanded
+// into.
+#[test]
+fn test_tail_temporaries() {
+    let length: usize = fatal! {
+        let cell: RefCell<Vec<i32>> = RefCell::new(vec!["1".parse()?]);
+        Ok(cell.borrow().len())
+    };
+    assert_eq!(length, 1);
+
+    let length: usize = fatal! {
+        #![ensure_ok(|length| *length > 0, "the list is empty")]
+        let cell: RefCell<Vec<i32>> = RefCell::new(vec!["1".parse()?]);
+        Ok(cell.borrow().len())
+    };
+    assert_eq!(length, 1);
+
+    let length: usize = block_on(async {
+        fatal! {
+            let cell: RefCell<Vec<i32>> = RefCell::new(vec![async { 1 }.await]);
+            let first: i32 = "1".parse()?;
+            Ok(cell.borrow().len() + first as usize)
+        }
+    });
+    assert_eq!(length, 2);
+}
@@ -0,0 +1,338 @@
+//! Runs the binaries in `examples/` as subprocesses, asserting on how they
+//! terminate.
+//!
+//! The examples are built by `cargo test` alongside the integration tests, and
+//! are located next to the test binary in `target/<profile>/examples`.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::time::{Duration, Instant};
+
+
+/// Runs the example `name`, capturing its exit status and output.
+fn run_example(name: &str) -> Output {
+    run_example_with(name, &[])
+}
+
+/// Runs the example `name` with the additional environment variables `envs`,
+/// capturing its exit status and output.
+fn run_example_with(name: &str, envs: &[(&str, &str)]) -> Output {
+    let exe:  PathBuf = env::current_exe().expect("Failed to locate the test binary");
+    let path: PathBuf = exe
+        .parent().and_then(|deps| deps.parent())
+        .expect("The test binary should be located in `target/<profile>/deps`")
+        .join("examples")
+        .join(format!("{name}{}", env::consts::EXE_SUFFIX));
+
+    assert!(
+        path.exists(),
+        "The example `{name}` was not built; run the suite through `cargo test`, or `cargo build --examples` first"
+    );
+    Command::new(&path)
+        .env_remove("RUST_BACKTRACE")
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap_or_else(|e| panic!("Failed to run the example `{name}`: {e}"))
+}
+
+
+// A succeeding block exits normally and prints its output.
+#[test]
+fn test_example_success() {
+    let output: Output = run_example("success");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Listening on port 8080\n");
+    assert!(output.stderr.is_empty());
+}
+
+// A failing block panics with the reason and the cause chain on stderr.
+#[test]
+fn test_example_panic_default() {
+    let output: Output = run_example("panic_default");
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert_eq!(output.status.code(), Some(101));
+    assert!(output.stdout.is_empty());
+    assert!(stderr.contains("panicked at"), "{stderr}");
+    assert!(stderr.contains("Failed to load the configuration"), "{stderr}");
+    assert!(stderr.contains("Caused by:\n    The configuration file is missing"), "{stderr}");
+}
+
+// The short line is written before anything else.
+#[test]
+fn test_example_short_line() {
+    let output: Output = run_example("panic_default");
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    let line:   &str   = stderr.lines().next().unwrap_or_default();
+
+    // fatal: <seconds>.<millis> <file>:<line>:<column>: <reason>
+    let (time, rest): (&str, &str) = line
+        .strip_prefix("fatal: ")
+        .and_then(|line| line.split_once(' '))
+        .unwrap_or_else(|| panic!("Unexpected first line: {stderr}"));
+    let (secs, millis): (&str, &str) = time.split_once('.').unwrap_or_else(|| panic!("Unexpected time: {line}"));
+    assert!(secs.parse::<u64>().is_ok_and(|secs| secs > 0), "{line}");
+    assert!(millis.len() == 3 && millis.parse::<u32>().is_ok(), "{line}");
+    assert!(rest.contains("panic_default.rs:"), "{line}");
+    assert!(rest.ends_with(": Failed to load the configuration"), "{line}");
+
+    // The full report only follows afterwards.
+    let report: usize = stderr.find("panicked at").expect("The report should be emitted");
+    assert!(report > line.len(), "{stderr}");
+}
+
+// A `no_catch` block aborts, even under `catch_unwind`.
+#[test]
+fn test_example_no_catch() {
+    let output: Output = run_example("no_catch");
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert!(!output.status.success());
+    assert_ne!(output.status.code(), Some(101), "The block should abort rather than panic");
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(!stderr.contains("panicked at"), "{stderr}");
+    assert!(stderr.contains("The plugin host cannot continue"), "{stderr}");
+    assert!(stderr.contains("Caused by:\n    The plugin state is corrupted"), "{stderr}");
+}
+
+// An `abort` block within an FFI callback aborts, printing the same report as
+// the panic would carry.
+#[test]
+fn test_example_abort() {
+    let output: Output = run_example("abort");
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert!(!output.status.success());
+    assert_ne!(output.status.code(), Some(101), "The block should abort rather than panic");
+    #[cfg(unix)]
+    assert_eq!(std::os::unix::process::ExitStatusExt::signal(&output.status), Some(6), "The process should die of `SIGABRT`");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n");
+    assert!(!stderr.contains("panicked at"), "{stderr}");
+    assert!(stderr.contains("Received an invalid event\n\nCaused by:\n    Unknown(-1)\n"), "{stderr}");
+}
+
+// An `exit` block prints the report starting with the reason, and exits with
+// its code.
+#[test]
+fn test_example_exit_code() {
+    let output: Output    = run_example("exit_code");
+    let stderr: String    = String::from_utf8_lossy(&output.stderr).into_owned();
+    let lines:  Vec<&str> = stderr.lines().collect();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(!stderr.contains("panicked at"), "{stderr}");
+    assert!(lines[0].starts_with("fatal: ") && lines[0].ends_with(": Failed to read the input"), "{stderr}");
+    assert!(lines[1].ends_with("Failed to read the input"), "{stderr}");
+    assert_eq!(lines[2..], ["", "Caused by:", "    The input file is missing"]);
+
+    let output: Output = run_example_with("exit_code", &[("EXIT_CODE_FORM", "fn")]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr.lines().nth(1).is_some_and(|line| line.ends_with("The output can't be written")), "{stderr}");
+}
+
+// A failure raised through `Fatal` is reported byte for byte like a block with
+// the same attributes, apart from its location.
+#[test]
+fn test_example_raise() {
+    let block:   Output = run_example_with("raise", &[("IMPASS_DETERMINISTIC", "1")]);
+    let builder: Output = run_example_with("raise", &[("IMPASS_DETERMINISTIC", "1"), ("RAISE_FORM", "builder")]);
+    let block:   String = String::from_utf8_lossy(&block.stderr).replace("examples/raise.rs:24:5", "<location>");
+    let raised:  String = String::from_utf8_lossy(&builder.stderr).replace("examples/raise.rs:21:106", "<location>");
+
+    assert_eq!(builder.status.code(), Some(70));
+    assert!(block.starts_with("fatal: 0.000 <location>: The worker pool collapsed\n"), "{block}");
+    assert_eq!(raised, block);
+}
+
+// The sites of a module with `fatal_defaults!` exit with its code, unless they
+// give their own, while a child module without defaults keeps panicking.
+#[test]
+fn test_example_defaults() {
+    let output: Output = run_example("defaults");
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert_eq!(output.status.code(), Some(4), "{stderr}");
+    assert!(!stderr.contains("panicked at"), "{stderr}");
+    assert!(stderr.contains("Failed to replay the journal\n\nCaused by:\n    The journal is corrupted"), "{stderr}");
+
+    let output: Output = run_example_with("defaults", &[("DEFAULTS_SITE", "site")]);
+    assert_eq!(output.status.code(), Some(5));
+
+    let output: Output = run_example_with("defaults", &[("DEFAULTS_SITE", "plain")]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert_eq!(output.status.code(), Some(101));
+    assert!(stderr.contains("panicked at"), "{stderr}");
+}
+
+// A body which stops beating is reported with its last beat, and aborts the
+// process long before it would have finished.
+#[test]
+fn test_example_watchdog() {
+    let start:  Instant = Instant::now();
+    let output: Output  = run_example("watchdog");
+    let stderr: String  = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert!(start.elapsed() < Duration::from_secs(30), "The stall took {:?} to be caught", start.elapsed());
+    assert!(!output.status.success());
+    assert_ne!(output.status.code(), Some(101), "The watchdog should abort rather than panic");
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(stderr.starts_with("fatal: "), "{stderr}");
+    assert!(stderr.lines().next().unwrap_or_default().ends_with(": The compactor stalled"), "{stderr}");
+    assert!(stderr.contains("The compactor stalled\n\nCaused by:\n    no heartbeat within 200ms, the last one was at "), "{stderr}");
+    assert!(stderr.contains(" (compacting level 2)"), "{stderr}");
+
+    let output: Output = run_example_with("watchdog", &[("WATCHDOG_MODE", "healthy")]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Compacted 4 levels\n");
+}
+
+// With the `backtrace-budget` feature, running out of the budget lists the raw
+// frames instead of the resolved backtrace, without delaying the panic.
+#[cfg(feature = "backtrace-budget")]
+#[test]
+fn test_example_backtrace_budget() {
+    let start:  Instant = Instant::now();
+    let output: Output  = run_example_with("panic_default", &[("RUST_BACKTRACE", "1"), ("IMPASS_BACKTRACE_BUDGET_MS", "0")]);
+    let stderr: String  = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert_eq!(output.status.code(), Some(101));
+    assert!(start.elapsed() < Duration::from_secs(10), "The panic took {:?}", start.elapsed());
+    assert!(stderr.contains("Caused by:\n    The configuration file is missing\n\nStack backtrace (unresolved, as symbolication ran out of its 0ms budget):\n   0: 0x"), "{stderr}");
+
+    // Within the budget, the backtrace is resolved as usual.
+    let output: Output = run_example_with("panic_default", &[("RUST_BACKTRACE", "1"), ("IMPASS_BACKTRACE_BUDGET_MS", "600000")]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(stderr.contains("Caused by:\n    The configuration file is missing\n\nStack backtrace:\n"), "{stderr}");
+
+    // The deterministic mode leaves the addresses of the raw frames out, and
+    // keeps their offsets within their module.
+    let envs:   [(&str, &str); 3] = [("IMPASS_DETERMINISTIC", "1"), ("RUST_BACKTRACE", "1"), ("IMPASS_BACKTRACE_BUDGET_MS", "0")];
+    let output: Output            = run_example_with("panic_default", &envs);
+    let stderr: String            = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(stderr.contains("(unresolved, as symbolication ran out of its 0ms budget):\n   0: ("), "{stderr}");
+}
+
+// Without an unwind-required guard, `exit` and `abort` blocks skip the
+// transaction's rollback.
+#[test]
+fn test_example_rollback_unguarded() {
+    let output: Output = run_example_with("rollback", &[("ROLLBACK_MODE", "exit")]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+
+    let output: Output = run_example_with("rollback", &[("ROLLBACK_MODE", "abort")]);
+    assert!(!output.status.success());
+    assert_ne!(output.status.code(), Some(101), "The block should abort rather than panic");
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+}
+
+// With an unwind-required guard, `exit` and `abort` blocks panic after their
+// report, so the transaction is rolled back.
+#[test]
+fn test_example_rollback_guarded() {
+    for mode in ["exit", "abort"] {
+        let output: Output = run_example_with("rollback", &[("ROLLBACK_MODE", mode), ("ROLLBACK_GUARD", "1")]);
+        let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert_eq!(output.status.code(), Some(101), "{mode}: {stderr}");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "Rolled back\n", "{mode}");
+        assert!(stderr.contains("Failed to update the row\n\nCaused by:\n    The row is locked"), "{stderr}");
+    }
+}
+
+// A `no_catch` block aborts even with an unwind-required guard, noting that
+// the cleanup was skipped.
+#[test]
+fn test_example_rollback_no_catch() {
+    let output: Output = run_example_with("rollback", &[("ROLLBACK_MODE", "no_catch"), ("ROLLBACK_GUARD", "1")]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert_ne!(output.status.code(), Some(101), "The block should abort rather than panic");
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(stderr.contains("note: an unwind-required guard is alive, but this site is `no_catch`"), "{stderr}");
+
+    let output: Output = run_example_with("rollback", &[("ROLLBACK_MODE", "no_catch")]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(!stderr.contains("unwind-required"), "{stderr}");
+}
+
+// The deterministic mode produces byte-identical output for identical failures,
+// even with backtraces enabled, whose paths within the package are relative to
+// its manifest directory.
+#[test]
+fn test_example_deterministic() {
+    let envs:   [(&str, &str); 2] = [("IMPASS_DETERMINISTIC", "1"), ("RUST_BACKTRACE", "1")];
+    let first:  Output            = run_example_with("panic_default", &envs);
+    let second: Output            = run_example_with("panic_default", &envs);
+    let stderr: String            = String::from_utf8_lossy(&first.stderr).into_owned();
+
+    assert_eq!(first.status.code(), Some(101));
+    assert_eq!(first.stderr, second.stderr);
+    assert!(stderr.starts_with("fatal: 0.000 "), "{stderr}");
+    assert!(stderr.contains("Caused by:\n    The configuration file is missing\n\nStack backtrace:\n"), "{stderr}");
+    assert!(stderr.contains("\n             at examples/panic_default.rs:"), "{stderr}");
+    assert!(!stderr.contains(env!("CARGO_MANIFEST_DIR")), "{stderr}");
+    assert!(!stderr.contains("thread"), "{stderr}");
+}
+
+// The console output is restricted to ASCII with `IMPASS_ASCII=1`, which spells
+// out typographic characters and escapes everything else.
+#[test]
+fn test_example_ascii() {
+    let output: Output = run_example_with("unicode", &[("IMPASS_ASCII", "1"), ("IMPASS_DETERMINISTIC", "1")]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    let location: &str   = "examples/unicode.rs:19:5";
+    let report:   String = if cfg!(feature = "legacy-format") { String::from("\n") } else { format!("fatal error at {location}: ") };
+    assert_eq!(output.status.code(), Some(101));
+    assert_eq!(stderr, format!(
+        "fatal: 0.000 {location}: Failed to sync the album \"Tokyo\" ...\n\
+         {report}Failed to sync the album \"Tokyo\" ...\n\
+         \n\
+         Caused by:\n    \
+         \\u{{30a2}}\\u{{30c3}}\\u{{30d7}}\\u{{30ed}}\\u{{30fc}}\\u{{30c9}}\\u{{306b}}\\u{{5931}}\\u{{6557}}\\u{{3057}}\\u{{307e}}\\u{{3057}}\\u{{305f}} \
+         \\u{{1f61e}} (\\u{{5199}}\\u{{771f}} -> \\u{{6771}}\\u{{4eac}})\n"
+    ));
+
+    // Without it, the report is left as it is.
+    let output: Output = run_example_with("unicode", &[("IMPASS_DETERMINISTIC", "1")]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(stderr.contains("Failed to sync the album “Tokyo” …\n\nCaused by:\n    アップロードに失敗しました 😞 (写真 → 東京)"), "{stderr}");
+}
+
+// Runs the audit example against the example `name`, with `args` following
+// the artifact's path.
+fn run_audit(name: &str, args: &[&str]) -> Output {
+    let exe:      PathBuf = env::current_exe().expect("Failed to locate the test binary");
+    let examples: PathBuf = exe.parent().and_then(|deps| deps.parent()).unwrap().join("examples");
+    Command::new(examples.join(format!("impass_audit{}", env::consts::EXE_SUFFIX)))
+        .arg(examples.join(format!("{name}{}", env::consts::EXE_SUFFIX)))
+        .args(args)
+        .output()
+        .expect("Failed to run the audit example")
+}
+
+// With the `audit` feature, every site is counted for the crate it is in, and
+// a denied crate containing any fails the audit.
+#[cfg(feature = "audit")]
+#[test]
+fn test_example_audit() {
+    let output: Output = run_audit("panic_default", &[]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "panic_default: 1\n");
+
+    let output: Output = run_audit("no_catch", &["--deny", "success", "--deny", "no-catch"]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "no_catch: 1\n");
+    assert!(stderr.contains("the crate `no_catch` is denied fatal sites, but contains 1"), "{stderr}");
+}
+
+// Without the `audit` feature, no markers are emitted at all.
+#[cfg(not(feature = "audit"))]
+#[test]
+fn test_example_audit_disabled() {
+    let output: Output = run_audit("panic_default", &["--deny", "panic_default"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+}
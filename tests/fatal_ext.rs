@@ -0,0 +1,98 @@
+//! Uses the `FatalExt` methods, which unwrap a single `Result` or `Option`
+//! without a `fatal!` block.
+
+mod common;
+
+use std::cell::{Cell, RefCell};
+use std::panic;
+
+use impass::FatalExt;
+use common::{might_fail, panic_message};
+
+
+// Both methods evaluate to the success value.
+#[test]
+fn test_or_fatal_success() {
+    assert_eq!(might_fail(false).or_fatal("Failed to compute the value"), 42);
+    assert_eq!(Some(42).or_fatal("The value is missing"), 42);
+    assert_eq!("42".parse::<i32>().or_fatal_with(|| "Failed to parse the value"), 42);
+}
+
+// A failed `Result` is reported like a `fatal!` block with the same reason.
+#[test]
+fn test_or_fatal_result() {
+    let message: String = panic_message(|| might_fail(true).or_fatal("Failed to compute the value"));
+    let block:   String = panic_message(|| impass::fatal! {
+        #![reason("Failed to compute the value")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to compute the value\n\nCaused by:\n    This operation failed"), "{message}");
+
+    // Both reports only differ in the location they may begin with.
+    let report = |message: &str| message[message.find("Failed").unwrap()..].split("Stack backtrace:").next().unwrap().to_string();
+    assert_eq!(report(&message), report(&block));
+}
+
+// A `None` is reported as such, just like `?` on an `Option` within a block.
+#[test]
+fn test_or_fatal_option() {
+    let message: String = panic_message(|| None::<i32>.or_fatal(format_args!("The value {} is missing", 7)));
+    assert!(message.contains("The value 7 is missing\n\nCaused by:\n    value was None"), "{message}");
+}
+
+// The lazy variant only builds its reason on failure.
+#[test]
+fn test_or_fatal_with() {
+    let calls: Cell<usize> = Cell::new(0);
+    let reason = || {
+        calls.set(calls.get() + 1);
+        format!("Failed after {} calls", calls.get())
+    };
+
+    assert_eq!(might_fail(false).or_fatal_with(reason), 42);
+    assert_eq!(Some(1).or_fatal_with(reason), 1);
+    assert_eq!(calls.get(), 0);
+
+    let message: String = panic_message(|| might_fail(true).or_fatal_with(reason));
+    assert!(message.contains("Failed after 1 calls\n\nCaused by:\n    This operation failed"), "{message}");
+    let message: String = panic_message(|| None::<i32>.or_fatal_with(reason));
+    assert!(message.contains("Failed after 2 calls\n\nCaused by:\n    value was None"), "{message}");
+}
+
+// The panic points at the caller rather than at `impass`.
+#[test]
+fn test_or_fatal_location() {
+    thread_local! {
+        static LOCATION: RefCell<Option<(String, u32)>> = const { RefCell::new(None) };
+    }
+
+    // The hook is global, but each panic is recorded by its own thread.
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(location) = info.location() {
+            LOCATION.with(|cell| *cell.borrow_mut() = Some((location.file().to_string(), location.line())));
+        }
+        default(info);
+    }));
+
+    let line: u32 = line!();
+    let _ = panic::catch_unwind(|| might_fail(true).or_fatal("Failed at the call site"));
+    assert_eq!(LOCATION.with(|cell| cell.take()), Some((file!().to_string(), line + 1)));
+}
+
+// A reason whose formatting panics is replaced, rather than ending the report.
+#[test]
+fn test_or_fatal_guarded() {
+    struct Invariant;
+
+    impl std::fmt::Display for Invariant {
+        fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            panic!("the invariant was violated")
+        }
+    }
+
+    let message: String = panic_message(|| might_fail(true).or_fatal(Invariant));
+    assert!(message.contains("«format panicked»\n\nCaused by:\n    This operation failed"), "{message}");
+    assert!(message.ends_with("panicked while being formatted"), "{message}");
+}
@@ -0,0 +1,75 @@
+//! Renders byte buffers, long strings and secrets through the wrappers of
+//! `impass::fields`, whose output stays bounded whatever the input.
+
+mod common;
+
+use impass::fatal;
+use impass::fields::{Hex, Redacted, Snip, HEX_LIMIT, REDACTED};
+use common::panic_message;
+
+
+// A buffer of several megabytes.
+fn large_buffer() -> Vec<u8> {
+    (0..4 * 1024 * 1024).map(|index| index as u8).collect()
+}
+
+
+// A buffer is rendered along with its length, and cut short past the limit.
+#[test]
+fn test_hex() {
+    assert_eq!(Hex(&[]).to_string(), "0 bytes");
+    assert_eq!(Hex(&[0x0a]).to_string(), "1 byte: 0a");
+    assert_eq!(format!("{:?}", Hex(&[0xde, 0xad, 0xbe, 0xef])), "4 bytes: deadbeef");
+    assert!(!Hex(&[0; HEX_LIMIT]).truncated());
+
+    let buffer:   Vec<u8> = large_buffer();
+    let rendered: String  = Hex(&buffer).to_string();
+    assert!(Hex(&buffer).truncated());
+    assert!(rendered.starts_with("4194304 bytes: 000102"), "{rendered}");
+    assert!(rendered.ends_with("3e3f…"), "{rendered}");
+    assert!(rendered.len() <= 2 * HEX_LIMIT + 32, "{}", rendered.len());
+    assert_eq!(format!("{:?}", Hex(&buffer)), rendered);
+}
+
+// A string is cut short at a character boundary, followed by its length.
+#[test]
+fn test_snip() {
+    assert_eq!(Snip("", 8).to_string(), "");
+    assert_eq!(Snip("short", 8).to_string(), "short");
+    assert_eq!(format!("{:?}", Snip("short", 8)), "\"short\"");
+    assert_eq!(Snip("Authorization: Bearer", 13).to_string(), "Authorization… (21 bytes)");
+    assert_eq!(Snip("ééé", 2).to_string(), "éé… (6 bytes)");
+    assert_eq!(format!("{:?}", Snip("a\nb\nc", 3)), "\"a\\nb\"… (5 bytes)");
+
+    let text:     String = "x".repeat(4 * 1024 * 1024);
+    let rendered: String = Snip(&text, 100).to_string();
+    assert!(Snip(&text, 100).truncated());
+    assert_eq!(rendered, format!("{}… (4194304 bytes)", "x".repeat(100)));
+    assert!(format!("{:?}", Snip(&text, 100)).len() <= 10 * 100 + 32);
+}
+
+// A secret is never rendered, whatever it is.
+#[test]
+fn test_redacted() {
+    assert_eq!(Redacted("hunter2").to_string(), REDACTED);
+    assert_eq!(format!("{:?}", Redacted(large_buffer())), REDACTED);
+    assert_eq!(format!("{:?}", Redacted(())), REDACTED);
+}
+
+// The wrappers bound the report of a failing block, in its reason as well as
+// its statement context.
+#[test]
+fn test_fields_in_report() {
+    let frame:   Vec<u8> = large_buffer();
+    let message: String  = panic_message(|| fatal! {
+        #![reason("Failed to decode the frame {} signed with {:?}", Hex(&frame), Redacted("hunter2"))]
+        #[ctx("decoding the body of {}", Snip("Content-Type: application/octet-stream", 12))]
+        let text: &str = std::str::from_utf8(&frame[128..])?;
+        Ok(text.len())
+    });
+    assert!(message.contains("Failed to decode the frame 4194304 bytes: 000102"), "{message}");
+    assert!(message.contains(&format!("… signed with {REDACTED}\n")), "{message}");
+    assert!(message.contains("decoding the body of Content-Type… (38 bytes)"), "{message}");
+    assert!(!message.contains("hunter2"), "{message}");
+    assert!(message.len() < frame.len() / 100, "{}", message.len());
+}
@@ -0,0 +1,10 @@
+// Forbids fatal sites in the crate, in the way selected through
+// `FORBID_FIXTURE_MODE`: `env` (the default), `cfg` or `none`.
+fn main() {
+    println!("cargo:rerun-if-env-changed=FORBID_FIXTURE_MODE");
+    match std::env::var("FORBID_FIXTURE_MODE").as_deref() {
+        Ok("cfg")  => println!("cargo:rustc-cfg=impass_forbid"),
+        Ok("none") => {},
+        _          => impass::build::forbid(),
+    }
+}
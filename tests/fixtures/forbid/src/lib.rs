@@ -0,0 +1,24 @@
+//! A library with a recoverable block, which is always allowed, and a fatal
+//! one behind the `fatal` feature.
+
+use impass::fatal;
+
+
+pub fn try_parse_port(input: &str) -> Result<u16, Box<dyn std::error::Error + Send + Sync>> {
+    let port: u16 = fatal! {
+        #![recoverable]
+        #![reason("The port is invalid")]
+        let port: u16 = input.parse()?;
+        Ok(port)
+    };
+    Ok(port)
+}
+
+#[cfg(feature = "fatal")]
+pub fn parse_port(input: &str) -> u16 {
+    fatal! {
+        #![reason("The port is invalid")]
+        let port: u16 = input.parse()?;
+        Ok(port)
+    }
+}
@@ -0,0 +1,55 @@
+//! Builds the fixture crate in `tests/fixtures/forbid`, which forbids fatal
+//! sites through its build script.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+
+/// Builds the fixture with the given forbidding mode and features, capturing
+/// the output of Cargo.
+///
+/// The fixture only depends on the crates the tests are built with, so the
+/// build runs offline, in a target directory of its own.
+fn build_fixture(mode: &str, features: &[&str]) -> Output {
+    let root:     &Path   = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let manifest: PathBuf = root.join("tests").join("fixtures").join("forbid").join("Cargo.toml");
+    let target:   PathBuf = Path::new(env!("CARGO_TARGET_TMPDIR")).join("forbid");
+    Command::new(env!("CARGO"))
+        .args(["build", "--offline", "--quiet", "--features", &features.join(",")])
+        .arg("--manifest-path").arg(&manifest)
+        .arg("--target-dir").arg(&target)
+        .env("FORBID_FIXTURE_MODE", mode)
+        .env_remove("IMPASS_FORBID")
+        .output()
+        .expect("Failed to run Cargo")
+}
+
+
+// A fatal site in a crate forbidding them fails to compile, pointing at the
+// site, whichever way the crate forbids them.
+#[test]
+fn test_forbid_fatal() {
+    let output: Output = build_fixture("env", &["fatal"]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(!output.status.success(), "{stderr}");
+    assert!(stderr.contains("error: fatal sites are forbidden in this crate, which is built with `IMPASS_FORBID=1`"), "{stderr}");
+    assert!(stderr.contains("src/lib.rs:19:5"), "{stderr}");
+    assert_eq!(stderr.matches("error: fatal sites are forbidden").count(), 1, "{stderr}");
+
+    let output: Output = build_fixture("cfg", &["fatal"]);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(!output.status.success(), "{stderr}");
+    assert!(stderr.contains("error: fatal sites are forbidden in this crate, which is built with `--cfg impass_forbid`"), "{stderr}");
+    assert!(!stderr.contains("warning"), "{stderr}");
+}
+
+// Recoverable blocks are allowed, and so is everything once the crate no
+// longer forbids them.
+#[test]
+fn test_forbid_allowed() {
+    let output: Output = build_fixture("env", &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let output: Output = build_fixture("none", &["fatal"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
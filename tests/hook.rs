@@ -0,0 +1,276 @@
+//! Installs a fatal hook, which is given the structured failure of every site
+//! before it terminates.
+//!
+//! The hook is process-wide, so the tests take turns through a lock.
+
+mod common;
+
+use std::env;
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use impass::{fatal, fatal_fn, FatalExt, FatalReport};
+use common::{might_fail, panic_message, MyError};
+
+
+// Set for the tests run on their own by `test_hook_nested`, which only fail
+// when it is.
+const CHILD_ENV: &str = "IMPASS_HOOK_CHILD";
+
+
+/// What the hook observed of a single failure.
+#[derive(Debug, PartialEq)]
+struct Observed {
+    reason:    String,
+    chain:     Vec<String>,
+    has_error: bool,
+    file:      String,
+    line:      u32,
+}
+
+impl Observed {
+    fn new(report: &FatalReport<'_>) -> Self {
+        Self {
+            reason:    report.reason().to_string(),
+            chain:     report.chain().to_vec(),
+            has_error: report.error().is_some(),
+            file:      report.location().file().to_string(),
+            line:      report.location().line(),
+        }
+    }
+}
+
+// Takes the hook for the current test, installing one which records every
+// failure it is called with.
+fn install() -> (MutexGuard<'static, ()>, Arc<Mutex<Vec<Observed>>>) {
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    let guard:    MutexGuard<'static, ()>  = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let observed: Arc<Mutex<Vec<Observed>>> = Arc::new(Mutex::new(Vec::new()));
+    let recorder: Arc<Mutex<Vec<Observed>>> = Arc::clone(&observed);
+    impass::set_fatal_hook(Box::new(move |report| recorder.lock().unwrap().push(Observed::new(report))));
+    (guard, observed)
+}
+
+
+// The hook sees the reason, the cause chain and the location of the block.
+#[test]
+fn test_hook_fatal() {
+    let (_guard, observed) = install();
+    let line:    u32    = line!() + 1;
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to load the state")]
+        #[ctx("reading the snapshot")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to load the state"), "{message}");
+    assert_eq!(*observed.lock().unwrap(), [Observed {
+        reason:    String::from("Failed to load the state"),
+        chain:     vec![String::from("reading the snapshot"), String::from("This operation failed")],
+        has_error: true,
+        file:      file!().to_string(),
+        line,
+    }]);
+}
+
+// The line `fails_within_fn` is declared on.
+const FAILS_WITHIN_FN_LINE: u32 = line!() + 3;
+
+#[fatal_fn(reason = "Failed within a function")]
+fn fails_within_fn() -> i32 {
+    let value: i32 = might_fail(true)?;
+    Ok(value)
+}
+
+// A function's failure is located at the function item, and a `FatalExt`
+// method's at its call.
+#[test]
+fn test_hook_location() {
+    let (_guard, observed) = install();
+    let _ = panic_message(fails_within_fn);
+    let line: u32 = line!() + 1;
+    let _ = panic_message(|| might_fail(true).or_fatal("Failed at the call site"));
+
+    let observed = observed.lock().unwrap();
+    assert_eq!((observed[0].reason.as_str(), observed[0].line), ("Failed within a function", FAILS_WITHIN_FN_LINE));
+    assert_eq!((observed[1].reason.as_str(), observed[1].line), ("Failed at the call site", line));
+    assert_eq!(observed[1].chain, ["This operation failed"]);
+}
+
+// A block with its own error type passes its `Debug` rendering instead.
+#[test]
+fn test_hook_error_type() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum ConfigError {
+        Missing(&'static str),
+    }
+
+    let (_guard, observed) = install();
+    let _ = panic_message(|| fatal! {
+        #![error(ConfigError)]
+        let port: u16 = Err(ConfigError::Missing("host"))?;
+        Ok(port)
+    });
+
+    let observed = observed.lock().unwrap();
+    assert!(!observed[0].has_error);
+    assert_eq!(observed[0].chain, ["Missing(\"host\")"]);
+}
+
+// Setting a hook returns the previous one, which is no longer called.
+#[test]
+fn test_hook_replace() {
+    let (_guard, observed) = install();
+    let previous = impass::set_fatal_hook(Box::new(|_| {}));
+    assert!(previous.is_some());
+
+    let _ = panic_message(|| might_fail(true).or_fatal("Failed after the replacement"));
+    assert!(observed.lock().unwrap().is_empty());
+}
+
+// A panicking hook doesn't keep the failure from being reported.
+#[test]
+fn test_hook_panics() {
+    let (_guard, _) = install();
+    impass::set_fatal_hook(Box::new(|_| panic!("the crash reporter failed")));
+
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed despite the hook")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed despite the hook\n\nCaused by:\n    This operation failed"), "{message}");
+}
+
+// The hook isn't called with the lock held, so it may set another one, which
+// takes over from the next failure on.
+#[test]
+fn test_hook_sets_hook() {
+    let (_guard, observed) = install();
+    let recorder: Arc<Mutex<Vec<Observed>>> = Arc::clone(&observed);
+    impass::set_fatal_hook(Box::new(move |_| {
+        let recorder: Arc<Mutex<Vec<Observed>>> = Arc::clone(&recorder);
+        impass::set_fatal_hook(Box::new(move |report| recorder.lock().unwrap().push(Observed::new(report))));
+    }));
+
+    let _ = panic_message(|| might_fail(true).or_fatal("Failed before the replacement"));
+    let _ = panic_message(|| might_fail(true).or_fatal("Failed after the replacement"));
+    let reasons: Vec<String> = observed.lock().unwrap().iter().map(|observed| observed.reason.clone()).collect();
+    assert_eq!(reasons, ["Failed after the replacement"]);
+}
+
+// The hook is called from whichever thread fails.
+#[test]
+fn test_hook_threads() {
+    let (_guard, observed) = install();
+    let threads: Vec<thread::JoinHandle<()>> = (0..4).map(|index| thread::spawn(move || {
+        let _ = panic_message(|| might_fail(true).or_fatal(format!("Failed on thread {index}")));
+    })).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let mut reasons: Vec<String> = observed.lock().unwrap().iter().map(|observed| observed.reason.clone()).collect();
+    reasons.sort();
+    assert_eq!(reasons, ["Failed on thread 0", "Failed on thread 1", "Failed on thread 2", "Failed on thread 3"]);
+}
+
+// `fatal_unreachable!` is seen by the hook just like any other site.
+#[test]
+fn test_hook_unreachable() {
+    let (_guard, observed) = install();
+    let line: u32 = line!() + 1;
+    let _ = panic_message(|| -> u32 { impass::fatal_unreachable!("reached state {}", 3) });
+    assert_eq!(*observed.lock().unwrap(), [Observed {
+        reason:    String::from("reached state 3"),
+        chain:     vec![String::from("entered unreachable code (this indicates a bug)")],
+        has_error: true,
+        file:      file!().to_string(),
+        line,
+    }]);
+}
+
+// A failure raised through `Fatal` is seen by the hook at the call of
+// `raise()`.
+#[test]
+fn test_hook_raise() {
+    let (_guard, observed) = install();
+    let line: u32 = line!() + 1;
+    let _ = panic_message(|| impass::Fatal::new(MyError::OperationFailed.into()).reason("The worker pool collapsed").raise());
+    assert_eq!(*observed.lock().unwrap(), [Observed {
+        reason:    String::from("The worker pool collapsed"),
+        chain:     vec![String::from("This operation failed")],
+        has_error: true,
+        file:      file!().to_string(),
+        line,
+    }]);
+}
+
+// The class and the fields of a failure, as seen by the hook.
+type Extras = (Option<String>, Vec<(String, String)>);
+
+// The hook is given the class and the fields of a raised failure, which the
+// sites have none of.
+#[test]
+fn test_hook_raise_class_fields() {
+    let (_guard, _) = install();
+    let seen:     Arc<Mutex<Vec<Extras>>> = Arc::new(Mutex::new(Vec::new()));
+    let recorder: Arc<Mutex<Vec<Extras>>> = Arc::clone(&seen);
+    impass::set_fatal_hook(Box::new(move |report| {
+        recorder.lock().unwrap().push((report.class().map(str::to_string), report.fields().to_vec()));
+    }));
+
+    let _ = panic_message(|| impass::Fatal::new(MyError::OperationFailed.into())
+        .reason("The worker pool collapsed")
+        .class("runtime")
+        .field("workers_lost", 4)
+        .field("pool", "ingest")
+        .raise());
+    let _ = panic_message(|| might_fail(true).or_fatal("Failed as a method"));
+    assert_eq!(*seen.lock().unwrap(), [
+        (Some(String::from("runtime")), vec![
+            (String::from("workers_lost"), String::from("4")),
+            (String::from("pool"), String::from("ingest")),
+        ]),
+        (None, Vec::new()),
+    ]);
+}
+
+// Fails a block whose hook calls a failing function, as run by
+// `test_hook_nested`.
+#[test]
+fn child_nested() {
+    if env::var_os(CHILD_ENV).is_some() {
+        impass::set_fatal_hook(Box::new(|_| {
+            fails_within_fn();
+        }));
+        fatal! {
+            #![reason("Failed with a failing hook")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        };
+    }
+}
+
+// A hook failing in turn doesn't re-enter the failure path: its failure is
+// only reported by its short line and a note, and the outer one carries on.
+#[test]
+fn test_hook_nested() {
+    let output: Output = Command::new(env::current_exe().expect("Failed to locate the test binary"))
+        .args(["child_nested", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, "1")
+        .env_remove("RUST_BACKTRACE")
+        .output()
+        .expect("Failed to run the test `child_nested`");
+
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    let outer:  usize  = stderr.find(": Failed with a failing hook\n").unwrap_or_else(|| panic!("{stderr}"));
+    let nested: usize  = stderr.find(": Failed within a function\nfatal raised while handling a fatal\n").unwrap_or_else(|| panic!("{stderr}"));
+    assert!(outer < nested, "{stderr}");
+    assert!(stderr[nested..].contains("Failed with a failing hook\n\nCaused by:\n    This operation failed"), "{stderr}");
+    assert!(!stderr.contains("overflowed its stack"), "{stderr}");
+    assert_eq!(output.status.code(), Some(101), "{stderr}");
+}
@@ -0,0 +1,98 @@
+//! Checks that failures point back at the fatal site, both through the panic's
+//! own `Location` and through the location the report begins with.
+
+mod common;
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use impass::{fatal, fatal_fn};
+use common::might_fail;
+
+
+thread_local! {
+    static LOCATION: RefCell<Option<(String, u32, u32)>> = const { RefCell::new(None) };
+}
+
+// Runs `f`, returning the message and the location of the panic it is expected
+// to raise. The hook is global, but each panic is recorded by its own thread.
+fn panic_site<T>(f: impl FnOnce() -> T) -> (String, Option<(String, u32, u32)>) {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if let Some(location) = info.location() {
+                LOCATION.with(|cell| *cell.borrow_mut() = Some((location.file().to_string(), location.line(), location.column())));
+            }
+            default(info);
+        }));
+    });
+
+    let result: Result<T, Box<dyn Any + Send>> = panic::catch_unwind(AssertUnwindSafe(f));
+    match result {
+        Ok(_)      => panic!("Expected a panic"),
+        Err(error) => (*error.downcast::<String>().unwrap(), LOCATION.with(|cell| cell.take())),
+    }
+}
+
+
+// The line `fails_within_fn` is declared on.
+const FATAL_FN_LINE: u32 = line!() + 3;
+
+#[fatal_fn(reason = "Failed within a function")]
+fn fails_within_fn() -> i32 {
+    let value: i32 = might_fail(true)?;
+    Ok(value)
+}
+
+
+// A block's panic points at the invocation, rather than at `impass`.
+#[test]
+fn test_fatal_location() {
+    let line:          u32 = line!() + 2;
+    let (_, location)      = panic_site(|| {
+        fatal! {
+            #![reason("Failed at the call site")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        }
+    });
+    assert_eq!(location, Some((file!().to_string(), line, 9)));
+}
+
+// The short form points at the invocation as well.
+#[test]
+fn test_fatal_expr_location() {
+    let line:          u32 = line!() + 1;
+    let (_, location)      = panic_site(|| fatal!(might_fail(true), "Failed at the call site"));
+    assert_eq!(location, Some((file!().to_string(), line, 44)));
+}
+
+// A function's panic points at the function item.
+#[test]
+fn test_fatal_fn_location() {
+    let (_, location) = panic_site(fails_within_fn);
+    assert_eq!(location, Some((file!().to_string(), FATAL_FN_LINE, 1)));
+}
+
+// Without the legacy layout, the report begins with the location as well.
+#[cfg(all(feature = "std", not(feature = "legacy-format")))]
+#[test]
+fn test_report_location() {
+    let line:         u32 = line!() + 2;
+    let (message, _)      = panic_site(|| {
+        fatal! {
+            #![reason("Failed at the call site")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        }
+    });
+    let header: String = format!("fatal error at {}:{line}:9: Failed at the call site\n\nCaused by:\n    This operation failed", file!());
+    assert!(message.starts_with(&header), "{message}");
+
+    let (message, _) = panic_site(fails_within_fn);
+    let header: String = format!("fatal error at {}:{FATAL_FN_LINE}:1: Failed within a function", file!());
+    assert!(message.starts_with(&header), "{message}");
+}
@@ -1,23 +1,12 @@
-use thiserror::Error;
-use impass::{fatal, fatal_fn};
+mod common;
 
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
 
-// Declare an error type for demonstration purposes.
-#[derive(Error, Debug)]
-pub enum MyError {
-    #[error("This operation failed")]
-    OperationFailed
-}
-
-
-// A dummy fallible function to test with.
-fn might_fail(should_fail: bool) -> Result<i32, MyError> {
-    if should_fail {
-        Err(MyError::OperationFailed)
-    } else {
-        Ok(42)
-    }
-}
+use impass::{fatal, fatal_fn, fatal_impl, fatal_mod, fatal_ok};
+use common::{might_fail, panic_message, MyError};
 
 
 // This test uses the macro in a way that should succeed.
@@ -46,4 +35,1218 @@ fn test_fatal_panic() {
 fn test_fatal_reason() {
     let _: i32 = might_fail(true)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+// This function is generated along with a fallible `try_fatal_sibling` variant.
+#[fatal_fn(also_fallible = "try_fatal_sibling")]
+fn fatal_sibling(should_fail: bool) -> i32 {
+    let value: i32 = might_fail(should_fail)?;
+    Ok(value)
+}
+
+// Both entry points of an `also_fallible` function should agree on success.
+#[test]
+fn test_fatal_fn_also_fallible_success() {
+    let fatal:    i32 = fatal_sibling(false);
+    let fallible: i32 = try_fatal_sibling(false).unwrap();
+    assert_eq!(fatal, fallible);
+}
+
+// The fallible entry point should return the error instead of panicking.
+#[test]
+fn test_fatal_fn_also_fallible_error() {
+    let error: anyhow::Error = try_fatal_sibling(true).unwrap_err();
+    assert!(error.downcast_ref::<MyError>().is_some());
+}
+
+// The errors re-exported through `impass` are the ones of a direct `anyhow`
+// dependency, so its `Context` applies on both sides of the macros.
+#[test]
+fn test_fatal_anyhow_interop() {
+    use anyhow::Context;
+
+    let error: anyhow::Error = try_fatal_sibling(true).context("Failed to call the sibling").unwrap_err();
+    assert_eq!(error.to_string(), "Failed to call the sibling");
+    assert!(error.root_cause().is::<MyError>());
+
+    let message: String = panic_message(|| fatal! {
+        might_fail(true).context("Attached by the caller")?;
+        Ok(())
+    });
+    assert!(message.contains("Attached by the caller"), "{message}");
+}
+
+// The fatal entry point should still panic.
+#[test]
+#[should_panic]
+fn test_fatal_fn_also_fallible_panic() {
+    fatal_sibling(true);
+}
+
+// A lazy reason should never be evaluated when the block succeeds.
+#[test]
+fn test_fatal_reason_with_success() {
+    let calls:  Cell<u32> = Cell::new(0);
+    let result: i32       = fatal! {
+        #![reason_with(|| { calls.set(calls.get() + 1); "Never evaluated" })]
+        let value: i32 = might_fail(false)?;
+        Ok(value)
+    };
+    assert_eq!(result, 42);
+    assert_eq!(calls.get(), 0);
+}
+
+// A lazy reason should be evaluated exactly once when the block fails.
+#[test]
+fn test_fatal_reason_with_failure() {
+    let calls:  Cell<u32>                        = Cell::new(0);
+    let result: Result<i32, Box<dyn Any + Send>> = panic::catch_unwind(AssertUnwindSafe(|| fatal! {
+        #![reason_with(|| { calls.set(calls.get() + 1); format!("Failed after {} calls", calls.get()) })]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    }));
+
+    let message: String = *result.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.contains("Failed after 1 calls"));
+    assert_eq!(calls.get(), 1);
+}
+
+
+#[fatal_fn(reason_with = || { calls.set(calls.get() + 1); format!("Failed for the user {id}") })]
+fn load_user_lazily(id: u32, calls: &Cell<u32>) -> i32 {
+    let value: i32 = might_fail(id > 0)?;
+    Ok(value)
+}
+
+// The lazy reason of a `fatal_fn` is only evaluated when the function fails,
+// and may refer to its parameters.
+#[test]
+fn test_fatal_fn_reason_with() {
+    let calls: Cell<u32> = Cell::new(0);
+    assert_eq!(load_user_lazily(0, &calls), 42);
+    assert_eq!(calls.get(), 0);
+
+    let message: String = panic_message(|| load_user_lazily(7, &calls));
+    assert!(message.contains("Failed for the user 7\n\nCaused by:"), "{message}");
+    assert_eq!(calls.get(), 1);
+}
+
+// A reason followed by arguments is formatted, while a lone one is kept as-is.
+#[test]
+fn test_fatal_reason_format() {
+    let id:      u32    = 42;
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed for {} after {attempts} attempts", id, attempts = 3)]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed for 42 after 3 attempts\n\nCaused by:"), "{message}");
+
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed for {id}")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed for {id}\n\nCaused by:"), "{message}");
+}
+
+// The expression form unwraps any `Result` whose error converts into an
+// `anyhow::Error`.
+#[test]
+fn test_fatal_expr_success() {
+    let value: i32 = fatal!(might_fail(false)) + 1;
+    assert_eq!(value, 43);
+
+    let text:  &str = "7";
+    let value: u8   = fatal!(text.parse(), "Failed to parse {}", text);
+    assert_eq!(value, 7);
+}
+
+// The expression form reports the error, along with the message if any.
+#[test]
+fn test_fatal_expr_panic() {
+    let message: String = panic_message(|| fatal!(might_fail(true)));
+    assert!(message.contains("Caused by:\n    This operation failed"), "{message}");
+
+    let message: String = panic_message(|| fatal!(might_fail(true), "Failed after {} attempts", 3));
+    assert!(message.contains("Failed after 3 attempts\n\nCaused by:\n    This operation failed"), "{message}");
+}
+
+// A lone expression which only makes sense as a block is still expanded as one.
+#[test]
+fn test_fatal_expr_block_fallback() {
+    let value: i32 = fatal!(Ok(might_fail(false)?));
+    assert_eq!(value, 42);
+}
+
+// A lone method call whose error type is left to inference takes an
+// `anyhow::Error`, just as within any other block.
+#[test]
+fn test_fatal_lone_map_err() {
+    let value: i32 = fatal! { might_fail(false).map_err(Into::into) };
+    assert_eq!(value, 42);
+
+    let value: i32 = fatal! { might_fail(false).map_err(|e| e.into()) };
+    assert_eq!(value, 42);
+
+    let message: String = panic_message(|| fatal!(might_fail(true).map_err(Into::into)));
+    assert!(message.contains("Caused by:\n    This operation failed"), "{message}");
+}
+
+// `?` works on an `Option` alongside any `Result`.
+#[test]
+fn test_fatal_option_mixed() {
+    let config: HashMap<&str, &str> = HashMap::from([("port", "8080")]);
+    let port:   u16                 = fatal! {
+        let port: &str = config.get("port")?;
+        let port: u16  = port.parse()?;
+        Ok(port)
+    };
+    assert_eq!(port, 8080);
+
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to read the port")]
+        let port: &str = config.get("missing")?;
+        let port: u16  = port.parse()?;
+        Ok(port)
+    });
+    assert!(message.contains("Failed to read the port\n\nCaused by:\n    value was None"), "{message}");
+}
+
+// A block may consist of `Option`s alone.
+#[test]
+fn test_fatal_option_only() {
+    let words: Vec<&str> = vec!["first", "second"];
+    let last:  &str      = fatal! {
+        let first: &str = words.first()?;
+        let last:  &str = words.get(first.len() - 4)?;
+        Ok(last)
+    };
+    assert_eq!(last, "second");
+
+    let message: String = panic_message(|| fatal! {
+        let word: &str = words.get(2)?;
+        Ok(word.len())
+    });
+    assert!(message.contains("Caused by:\n    value was None"), "{message}");
+}
+
+#[fatal_fn(also_fallible = "try_lookup")]
+fn lookup(key: &str) -> i32 {
+    let (_, value): (&str, i32) = [("answer", 42)].into_iter().find(|(name, _)| *name == key)?;
+    Ok(value)
+}
+
+// Functions and their fallible siblings support `?` on an `Option` as well.
+#[test]
+fn test_fatal_fn_option() {
+    assert_eq!(lookup("answer"), 42);
+    assert_eq!(try_lookup("question").unwrap_err().to_string(), "value was None");
+}
+
+// A value passing every `ensure_ok` is returned as usual.
+#[test]
+fn test_fatal_ensure_ok_pass() {
+    let value: i32 = fatal! {
+        #![ensure_ok(|value| *value > 0, "the value must be positive")]
+        #![ensure_ok(|value: &i32| *value % 2 == 0, "the value must be even")]
+        let value: i32 = might_fail(false)?;
+        Ok(value)
+    };
+    assert_eq!(value, 42);
+}
+
+// The first failing `ensure_ok` is reported along with the value.
+#[test]
+fn test_fatal_ensure_ok_fail() {
+    let checked: Cell<u32> = Cell::new(0);
+    let message: String    = panic_message(|| fatal! {
+        #![reason("The value is invalid")]
+        #![ensure_ok(|_| { checked.set(checked.get() + 1); true }, "never fails")]
+        #![ensure_ok(|value| *value > 100, "the value must exceed {}", 100)]
+        #![ensure_ok(|_| { checked.set(checked.get() + 1); false }, "never reached")]
+        let value: i32 = might_fail(false)?;
+        Ok(value)
+    });
+    assert!(message.contains("The value is invalid\n\nCaused by:\n    the value must exceed 100 (value: 42)"), "{message}");
+    assert_eq!(checked.get(), 1);
+
+    // Long values are truncated.
+    let message: String = panic_message(|| fatal! {
+        #![ensure_ok(|text: &String| text.len() < 10, "the text is too long")]
+        Ok("a".repeat(1000))
+    });
+    assert!(message.contains(&format!("(value: \"{}...)", "a".repeat(255))), "{message}");
+}
+
+#[fatal_fn(ensure_ok(|port| *port != 0, "the port for {} must not be zero", name), also_fallible = "try_parse_port")]
+fn parse_port(name: &str, port: &str) -> u16 {
+    let port: u16 = port.parse()?;
+    Ok(port)
+}
+
+// A `fatal_fn` checks its return value, and may refer to its parameters. Its
+// fallible sibling returns a failed check instead.
+#[test]
+fn test_fatal_fn_ensure_ok() {
+    assert_eq!(parse_port("http", "80"), 80);
+
+    let message: String = panic_message(|| parse_port("http", "0"));
+    assert!(message.contains("the port for http must not be zero (value: 0)"), "{message}");
+    assert_eq!(try_parse_port("http", "0").unwrap_err().to_string(), "the port for http must not be zero (value: 0)");
+    assert_eq!(try_parse_port("http", "80").unwrap(), 80);
+}
+
+// A domain error which doesn't implement `std::error::Error`.
+#[derive(Debug, PartialEq)]
+enum ConfigError {
+    Missing(&'static str),
+}
+
+fn lookup_key(key: &'static str) -> Result<u16, ConfigError> {
+    match key {
+        "port" => Ok(8080),
+        _      => Err(ConfigError::Missing(key)),
+    }
+}
+
+// A block may use its own error type instead of `anyhow::Error`.
+#[test]
+fn test_fatal_error_type() {
+    let port: u16 = fatal! {
+        #![error(ConfigError)]
+        let port: u16 = lookup_key("port")?;
+        Ok(port)
+    };
+    assert_eq!(port, 8080);
+
+    let message: String = panic_message(|| fatal! {
+        #![error(ConfigError)]
+        #![reason("Failed to load the configuration")]
+        let port: u16 = lookup_key("host")?;
+        Ok(port)
+    });
+    assert!(message.contains("Failed to load the configuration\n\nCaused by:\n    Missing(\"host\")"), "{message}");
+}
+
+#[fatal_fn(error = "ConfigError", also_fallible = "try_lookup_port")]
+fn lookup_port(key: &'static str) -> u16 {
+    let port: u16 = lookup_key(key)?;
+    Ok(port)
+}
+
+// A `fatal_fn` may use its own error type, which its fallible sibling returns.
+#[test]
+fn test_fatal_fn_error_type() {
+    assert_eq!(lookup_port("port"), 8080);
+    assert_eq!(try_lookup_port("host"), Err(ConfigError::Missing("host")));
+
+    let message: String = panic_message(|| lookup_port("host"));
+    assert!(message.contains("Caused by:\n    Missing(\"host\")"), "{message}");
+}
+
+/// Looks up a port, declared with the `Result` its body produces.
+#[fatal_fn(unwrap_signature, also_fallible = "try_lookup_declared_port")]
+#[inline]
+fn lookup_declared_port(key: &'static str) -> Result<u16, ConfigError> {
+    let port: u16 = lookup_key(key)?;
+    Ok(port)
+}
+
+#[fatal_fn(unwrap_signature, reason = "Failed to check the value")]
+fn check_value(should_fail: bool) -> std::result::Result<(), MyError> {
+    might_fail(should_fail)?;
+    Ok(())
+}
+
+// A `fatal_fn` declared as returning a `Result` is called as returning its
+// success type, and fails with the error type of the signature.
+#[test]
+fn test_fatal_fn_unwrap_signature() {
+    let port: u16 = lookup_declared_port("port");
+    assert_eq!(port, 8080);
+    assert_eq!(try_lookup_declared_port("host"), Err(ConfigError::Missing("host")));
+
+    let message: String = panic_message(|| lookup_declared_port("host"));
+    assert!(message.contains("Caused by:\n    Missing(\"host\")"), "{message}");
+
+    let checked: fn(bool) = check_value;
+    checked(false);
+    let message: String = panic_message(|| check_value(true));
+    assert!(message.contains("Failed to check the value\n\nCaused by:\n    OperationFailed"), "{message}");
+}
+
+#[fatal_fn(reason = "Failed for the user {}", id, code = "E0042")]
+fn load_user(id: u32) -> i32 {
+    let value: i32 = might_fail(id > 0)?;
+    Ok(value)
+}
+
+// The reason of a `fatal_fn` may refer to the function's parameters.
+#[test]
+fn test_fatal_fn_reason_format() {
+    let message: String = panic_message(|| load_user(7));
+    #[cfg(feature = "legacy-format")]
+    assert!(message.contains("fatal error [E0042]\nFailed for the user 7"), "{message}");
+    #[cfg(not(feature = "legacy-format"))]
+    assert!(message.contains("fatal error [E0042] at tests/main.rs:") && message.contains(": Failed for the user 7"), "{message}");
+}
+
+// Trailing whitespace is stripped from the reason, whatever its form.
+#[test]
+fn test_fatal_reason_trimmed() {
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to start\n")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to start\n\nCaused by:"), "{message}");
+
+    let id:      u32    = 7;
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to start worker {}\n  ", id)]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to start worker 7\n\nCaused by:"), "{message}");
+
+    let message: String = panic_message(|| fatal! {
+        #![reason_with(|| String::from("Failed to start\r\n"))]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to start\n\nCaused by:"), "{message}");
+}
+
+// A reason spanning several lines is kept as it is without `lint_reasons`,
+// apart from its trailing newline.
+#[test]
+#[deny(deprecated)]
+fn test_fatal_reason_multiline() {
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to start the worker.\nIts queue was left as it was.\n")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to start the worker.\nIts queue was left as it was.\n\nCaused by:"), "{message}");
+}
+
+// A reason without any issue is never warned about.
+#[test]
+#[deny(deprecated)]
+fn test_fatal_lint_reasons_clean() {
+    let message: String = panic_message(|| fatal! {
+        #![lint_reasons]
+        #![reason("Failed to start the worker")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to start the worker\n\nCaused by:"), "{message}");
+}
+
+// A reason given as a constant.
+const INIT_FAIL: &str = "Failed to initialise the worker";
+
+// A reason may be any expression which is `Display`.
+#[test]
+fn test_fatal_reason_expr() {
+    let message: String = panic_message(|| fatal! {
+        #![reason(INIT_FAIL)]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to initialise the worker\n\nCaused by:"), "{message}");
+
+    let id:      u32    = 42;
+    let message: String = panic_message(|| fatal! {
+        #![reason(format!("worker {id}"))]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("worker 42\n\nCaused by:"), "{message}");
+}
+
+// An expression reason is never evaluated when the block succeeds.
+#[test]
+fn test_fatal_reason_expr_success() {
+    let calls: Cell<u32> = Cell::new(0);
+    let value: i32       = fatal! {
+        #![reason({ calls.set(calls.get() + 1); "Never evaluated" })]
+        let value: i32 = might_fail(false)?;
+        Ok(value)
+    };
+    assert_eq!(value, 42);
+    assert_eq!(calls.get(), 0);
+}
+
+#[fatal_fn(reason = format!("worker {id}"))]
+fn start_worker(id: u32) -> i32 {
+    let value: i32 = might_fail(id > 0)?;
+    Ok(value)
+}
+
+// The expression reason of a `fatal_fn` may refer to the function's
+// parameters.
+#[test]
+fn test_fatal_fn_reason_expr() {
+    let message: String = panic_message(|| start_worker(7));
+    assert!(message.contains("worker 7\n\nCaused by:"), "{message}");
+}
+
+// A `ctx` attribute on a let-statement labels its error.
+#[test]
+fn test_fatal_ctx_let() {
+    let id:      u32    = 7;
+    let message: String = panic_message(|| fatal! {
+        let _: i32 = might_fail(false)?;
+        #[ctx("loading record {}", id)]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("loading record 7\n"), "{message}");
+    assert!(message.contains("This operation failed"), "{message}");
+}
+
+// A `ctx` attribute on an expression statement labels its error.
+#[test]
+fn test_fatal_ctx_expr() {
+    let message: String = panic_message(|| fatal! {
+        #[ctx("flushing the cache")]
+        might_fail(true)?;
+        Ok(())
+    });
+    assert!(message.contains("flushing the cache"), "{message}");
+}
+
+// A `ctx` attribute applies to every `?` in the statement.
+#[test]
+fn test_fatal_ctx_multiple() {
+    let first:  String = panic_message(|| fatal! {
+        #[ctx("combining values")]
+        let value: i32 = might_fail(true)? + might_fail(false)?;
+        Ok(value)
+    });
+    let second: String = panic_message(|| fatal! {
+        #[ctx("combining values")]
+        let value: i32 = might_fail(false)? + might_fail(true)?;
+        Ok(value)
+    });
+    assert!(first.contains("combining values"), "{first}");
+    assert!(second.contains("combining values"), "{second}");
+}
+
+// Statements without a `ctx` attribute are left unlabelled.
+#[test]
+fn test_fatal_ctx_targeted() {
+    let message: String = panic_message(|| fatal! {
+        #[ctx("never reached")]
+        let _: i32 = might_fail(false)?;
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(!message.contains("never reached"), "{message}");
+}
+
+// With `context`, a failing `?` is labelled with the source of its operand,
+// even within a method chain or a match arm.
+#[test]
+fn test_fatal_context_steps() {
+    let message: String = panic_message(|| fatal! {
+        #![context]
+        let value:   i32 = might_fail(false)?;
+        let checked: i8  = match value {
+            0 => 0,
+            _ => i8::try_from(might_fail(false)? * 10)?,
+        };
+        Ok(checked)
+    });
+    assert!(message.contains("while evaluating `i8::try_from(might_fail(false)? * 10)`\n"), "{message}");
+    assert!(!message.contains("while evaluating `might_fail(false)`"), "{message}");
+
+    let message: String = panic_message(|| fatal! {
+        #![context]
+        let value: i32 = might_fail(false)?;
+        let total: i32 = might_fail(false)?.checked_add(value).ok_or(MyError::OperationFailed)? + might_fail(true)?;
+        Ok(total)
+    });
+    assert!(message.contains("while evaluating `might_fail(true)`\n"), "{message}");
+}
+
+// The `?` of a closure within the block propagates from the closure, so it
+// is left as it is.
+#[test]
+fn test_fatal_context_closures() {
+    let message: String = panic_message(|| fatal! {
+        #![context]
+        let values: Vec<i32> = [false, true].iter()
+            .map(|fail| -> Result<i32, MyError> { let value: i32 = might_fail(*fail)?; Ok(value) })
+            .collect::<Result<Vec<i32>, MyError>>()?;
+        Ok(values)
+    });
+    assert!(message.contains("while evaluating `[false, true].iter().map("), "{message}");
+    assert!(message.contains("…`\n"), "The source should be cut short: {message}");
+    assert!(!message.contains("while evaluating `might_fail(*fail)`"), "{message}");
+}
+
+// A `ctx` message is attached on top of the source.
+#[test]
+fn test_fatal_context_ctx() {
+    let message: String = panic_message(|| fatal! {
+        #![context]
+        #[ctx("loading the record")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("loading the record\n"), "{message}");
+    assert!(message.find("loading the record") < message.find("while evaluating `might_fail(true)`"), "{message}");
+}
+
+#[fatal_fn(context, reason = "Failed to count")]
+fn count_records(should_fail: bool) -> i32 {
+    let count: i32 = might_fail(should_fail)?;
+    Ok(count)
+}
+
+// A `fatal_fn` labels its own `?` just the same.
+#[test]
+fn test_fatal_fn_context() {
+    assert_eq!(count_records(false), 42);
+    let message: String = panic_message(|| count_records(true));
+    assert!(message.contains("Failed to count\n\nCaused by:\n    0: while evaluating `might_fail(should_fail)`\n    1: This operation failed"), "{message}");
+}
+
+// Wrappers producing entire invocations, attributes included, through the
+// different kinds of `macro_rules!` captures.
+macro_rules! fatal_with_literal {
+    ($reason:literal, $($body:tt)*) => { fatal! { #![reason($reason)] $($body)* } };
+}
+macro_rules! fatal_with_expr {
+    ($reason:expr, $($body:tt)*) => { fatal! { #![reason = $reason] $($body)* } };
+}
+macro_rules! fatal_with_tt {
+    ($reason:tt, $($body:tt)*) => { fatal! { #![reason($reason)] $($body)* } };
+}
+macro_rules! fatal_with_ctx {
+    ($ctx:literal, $($body:tt)*) => { fatal! { #[ctx($ctx)] $($body)* } };
+}
+macro_rules! fatal_fn_with_expr {
+    ($name:ident, $reason:expr) => {
+        #[fatal_fn(reason = $reason)]
+        fn $name() -> i32 {
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        }
+    };
+}
+fatal_fn_with_expr!(generated_fatal_fn, "Reason from an expr capture");
+
+// Reasons interpolated by declarative macros are never dropped.
+#[test]
+fn test_fatal_macro_generated_reason() {
+    let literal: String = panic_message(|| fatal_with_literal!("Reason from a literal capture", might_fail(true)?; Ok(())));
+    let expr:    String = panic_message(|| fatal_with_expr!("Reason from an expr capture", might_fail(true)?; Ok(())));
+    let tt:      String = panic_message(|| fatal_with_tt!("Reason from a tt capture", might_fail(true)?; Ok(())));
+    let ctx:     String = panic_message(|| fatal_with_ctx!("Context from a literal capture", might_fail(true)?; Ok(())));
+    let func:    String = panic_message(generated_fatal_fn);
+
+    assert!(literal.contains("Reason from a literal capture"), "{literal}");
+    assert!(expr.contains("Reason from an expr capture"), "{expr}");
+    assert!(tt.contains("Reason from a tt capture"), "{tt}");
+    assert!(ctx.contains("Context from a literal capture"), "{ctx}");
+    assert!(func.contains("Reason from an expr capture"), "{func}");
+}
+
+// An error code is rendered as a header line before the report, or next to
+// the location without the legacy layout.
+#[test]
+fn test_fatal_code() {
+    let message: String = panic_message(|| fatal! {
+        #![code("MYAPP-0422")]
+        #![reason("Failed to open the database")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    #[cfg(feature = "legacy-format")]
+    assert!(message.starts_with("\nfatal error [MYAPP-0422]\nFailed to open the database\n"), "{message}");
+    #[cfg(not(feature = "legacy-format"))]
+    assert!(message.starts_with("fatal error [MYAPP-0422] at tests/main.rs:") && message.contains(": Failed to open the database\n"), "{message}");
+}
+
+// This test uses `fatal_fn` with an error code.
+#[test]
+#[should_panic(expected = "fatal error [E0422]")]
+#[fatal_fn(code = "E0422")]
+fn test_fatal_fn_code() {
+    let _: i32 = might_fail(true)?;
+    Ok(())
+}
+
+// A `must_use` block compiles cleanly as long as its value is used.
+#[test]
+#[deny(unused_must_use)]
+fn test_fatal_must_use() {
+    let result: i32 = fatal! {
+        #![must_use]
+        let value: i32 = might_fail(false)?;
+        Ok(value)
+    };
+    assert_eq!(result, 42);
+}
+
+// `fatal_ok!` yields the block's value from within nested control flow.
+#[test]
+fn test_fatal_ok_nested() {
+    for (input, expected) in [(0, -1), (1, -2), (2, 42)] {
+        let reached: Cell<bool> = Cell::new(false);
+        let result:  i32        = fatal! {
+            if input == 0 {
+                fatal_ok!(-1);
+            }
+            let offset: i32 = match input {
+                1 => fatal_ok!(-2),
+                _ => 0,
+            };
+            reached.set(true);
+            let value: i32 = might_fail(false)?;
+            Ok(value + offset)
+        };
+        assert_eq!(result, expected);
+        assert_eq!(reached.get(), input == 2);
+    }
+}
+
+// A bare `fatal_ok!` ends a unit block, skipping the failing statements.
+#[test]
+#[fatal_fn]
+fn test_fatal_ok_unit() {
+    fatal_ok!();
+    #[allow(unreachable_code)]
+    let _: i32 = might_fail(true)?;
+    Ok(())
+}
+
+// Every free function of the module is made fatal, apart from skipped ones.
+fatal_mod! {
+    mod bootstrap {
+        use super::might_fail;
+
+        pub const ANSWER: i32 = 42;
+
+        pub fn value(should_fail: bool) -> i32 {
+            let value: i32 = might_fail(should_fail)?;
+            Ok(value)
+        }
+
+        #[fatal_fn(reason = "Failed to bootstrap the overridden value")]
+        pub fn overridden() -> i32 {
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        }
+
+        #[fatal_skip]
+        pub fn skipped() -> Result<i32, super::MyError> {
+            might_fail(true)
+        }
+
+        pub mod nested {
+            pub fn untouched() -> Result<i32, ()> {
+                Ok(super::ANSWER)
+            }
+        }
+    }
+}
+
+// Nested modules are only rewritten when asked to.
+fatal_mod! {
+    #![recurse]
+    mod recursive {
+        pub mod nested {
+            pub fn value() -> i32 {
+                let value: i32 = super::super::might_fail(true)?;
+                Ok(value)
+            }
+
+            #[fatal_skip]
+            pub mod skipped {
+                pub fn untouched() -> Result<(), ()> {
+                    Err(())
+                }
+            }
+        }
+    }
+}
+
+// Functions within a `fatal_mod!` behave like `fatal_fn` functions.
+#[test]
+fn test_fatal_mod() {
+    assert_eq!(bootstrap::value(false), bootstrap::ANSWER);
+    assert!(bootstrap::skipped().is_err());
+    assert_eq!(bootstrap::nested::untouched(), Ok(42));
+
+    let message: String = panic_message(|| bootstrap::value(true));
+    assert!(message.contains("This operation failed"), "{message}");
+
+    let message: String = panic_message(bootstrap::overridden);
+    assert!(message.contains("Failed to bootstrap the overridden value"), "{message}");
+}
+
+// Nested modules are rewritten under `recurse`, except for skipped ones.
+#[test]
+fn test_fatal_mod_recurse() {
+    let message: String = panic_message(recursive::nested::value);
+    assert!(message.contains("This operation failed"), "{message}");
+    assert_eq!(recursive::nested::skipped::untouched(), Err(()));
+}
+
+// Every method of the block is made fatal with the shared reason, apart from
+// skipped ones and those with reasons of their own.
+struct Counter {
+    count: i32,
+}
+
+#[fatal_impl(reason = "The counter failed")]
+impl Counter {
+    fn new(should_fail: bool) -> Self {
+        Ok(Counter { count: might_fail(should_fail)? })
+    }
+
+    fn count(&self) -> i32 {
+        Ok(self.count)
+    }
+
+    fn add(&mut self, should_fail: bool) {
+        self.count += might_fail(should_fail)?;
+        Ok(())
+    }
+
+    fn into_parsed<T>(self, text: &str) -> (i32, T)
+    where
+        T: std::str::FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        Ok((self.count, text.parse()?))
+    }
+
+    #[fatal_fn(reason = "The counter failed to reset")]
+    fn reset(&mut self, should_fail: bool) {
+        self.count = might_fail(should_fail)? - 42;
+        Ok(())
+    }
+
+    #[fatal_skip]
+    fn checked(&self, should_fail: bool) -> Result<i32, MyError> {
+        Ok(self.count + might_fail(should_fail)?)
+    }
+}
+
+// Methods within a `#[fatal_impl]` block behave like `fatal_fn` functions,
+// whatever their receivers.
+#[test]
+fn test_fatal_impl() {
+    let mut counter: Counter = Counter::new(false);
+    counter.add(false);
+    assert_eq!(counter.count(), 84);
+    assert!(counter.checked(true).is_err());
+
+    counter.reset(false);
+    assert_eq!(counter.into_parsed::<u8>("7"), (0, 7));
+
+    let message: String = panic_message(|| Counter::new(true));
+    assert!(message.contains("The counter failed\n\nCaused by:\n    This operation failed"), "{message}");
+
+    let message: String = panic_message(|| Counter::new(false).into_parsed::<u8>("256"));
+    assert!(message.contains("The counter failed\n\nCaused by:\n    number too large"), "{message}");
+
+    let message: String = panic_message(|| Counter::new(false).reset(true));
+    assert!(message.contains("The counter failed to reset"), "{message}");
+}
+
+// Invocations are recognized however their paths are spelled, as long as the
+// last segment is the macro's own name.
+mod aliased {
+    pub(crate) use impass::fatal as die;
+}
+macro_rules! fatal_wrapper {
+    ($($body:tt)*) => { ::impass::fatal! { $($body)* } };
+}
+mod shim {
+    // An unrelated macro which happens to share the name of `fatal_ok!`.
+    macro_rules! fatal_ok {
+        ($value:expr) => { $value };
+    }
+    pub(crate) use fatal_ok;
+}
+
+// Aliased and fully qualified paths behave like the plain ones.
+#[test]
+fn test_fatal_paths() {
+    let aliased:   i32 = aliased::die! { impass::fatal_ok!(1); };
+    let qualified: i32 = ::impass::fatal! { ::impass::fatal_ok!(2); };
+    let wrapped:   i32 = fatal_wrapper! { fatal_ok!(3); };
+    assert_eq!((aliased, qualified, wrapped), (1, 2, 3));
+
+    let message: String = panic_message(|| aliased::die! { let value: i32 = might_fail(true)?; Ok(value) });
+    assert!(message.contains("This operation failed"), "{message}");
+}
+
+// `#[fatal_opaque]` hides an unrelated `fatal_ok!` from the enclosing block.
+#[test]
+fn test_fatal_opaque() {
+    let result: i32 = fatal! {
+        #[fatal_opaque]
+        let value: i32 = shim::fatal_ok!(40);
+        let other: i32 = #[fatal_opaque] shim::fatal_ok!(2);
+        Ok(value + other)
+    };
+    assert_eq!(result, 42);
+}
+
+// A trait object returned by `fatal_fn` functions.
+trait Backend {
+    fn id(&self) -> i32;
+}
+struct Memory;
+struct Disk(i32);
+impl Backend for Memory {
+    fn id(&self) -> i32 { 0 }
+}
+impl Backend for Disk {
+    fn id(&self) -> i32 { self.0 }
+}
+
+// The arms return different concrete types, which coerce at the return type.
+#[fatal_fn]
+fn make_backend(kind: u8) -> Box<dyn Backend> {
+    match kind {
+        0 => Ok(Box::new(Memory)),
+        1 => Ok(Box::new(Disk(might_fail(false)?))),
+        _ => Ok(Box::new(Disk(might_fail(true)?))),
+    }
+}
+
+// Early successes coerce just the same.
+#[fatal_fn]
+fn make_backend_early(memory: bool) -> Box<dyn Backend> {
+    if memory {
+        fatal_ok!(Box::new(Memory));
+    }
+    Ok(Box::new(Disk(might_fail(false)?)))
+}
+
+// A borrowed return type, with its lifetime elided.
+#[fatal_fn]
+fn first_word(text: &str) -> &str {
+    let word: &str = text.split_whitespace().next().ok_or(MyError::OperationFailed)?;
+    Ok(word)
+}
+
+// An `impl Trait` return type is left to inference.
+#[fatal_fn]
+fn make_display() -> impl std::fmt::Display {
+    let value: i32 = might_fail(false)?;
+    Ok(value)
+}
+
+// Values returned by a `fatal_fn` function coerce to its declared return type.
+#[test]
+fn test_fatal_fn_return_coercion() {
+    assert_eq!(make_backend(0).id(), 0);
+    assert_eq!(make_backend(1).id(), 42);
+    assert_eq!(make_backend_early(true).id(), 0);
+    assert_eq!(make_backend_early(false).id(), 42);
+    assert_eq!(first_word("hello world"), "hello");
+    assert_eq!(make_display().to_string(), "42");
+
+    let message: String = panic_message(|| make_backend(2).id());
+    assert!(message.contains("This operation failed"), "{message}");
+}
+
+// Modules shadowing the names of the crates the expansions refer to, which
+// only absolute paths can see past.
+mod shadowed {
+    #![allow(dead_code)]
+
+    mod impass {}
+    mod anyhow {}
+    mod std {}
+
+    // Generates a builder whose `build` method is written by the caller, so
+    // that its tokens mix the caller's and the generating macro's hygiene.
+    macro_rules! builder {
+        ($name:ident { $($field:ident: $ty:ty),* } $build:item) => {
+            pub struct $name {
+                $($field: ::core::option::Option<$ty>,)*
+            }
+
+            impl $name {
+                pub fn new() -> Self {
+                    $name { $($field: ::core::option::Option::None,)* }
+                }
+
+                $(
+                    pub fn $field(mut self, value: $ty) -> Self {
+                        self.$field = ::core::option::Option::Some(value);
+                        self
+                    }
+                )*
+
+                $build
+            }
+        };
+    }
+
+    builder! {
+        PortBuilder { port: &'static str }
+
+        #[::impass::fatal_fn(reason = "Failed to build the port", also_fallible = "try_build")]
+        pub fn build(self) -> u16 {
+            #[ctx("parsing the port")]
+            let port: u16 = self.port.ok_or(super::MyError::OperationFailed)?.parse()?;
+            Ok(port)
+        }
+    }
+
+    // Both generated entry points work, wherever they are expanded.
+    #[test]
+    fn test_fatal_fn_absolute_paths() {
+        assert_eq!(PortBuilder::new().port("8080").build(), 8080);
+        assert!(PortBuilder::new().port("not a port").try_build().is_err());
+
+        let message: ::std::string::String = super::panic_message(|| PortBuilder::new().build());
+        assert!(message.contains("Failed to build the port"), "{message}");
+    }
+}
+
+// Suggestions are rendered as `try:` lines after the report.
+#[test]
+fn test_fatal_suggest() {
+    let limit:   u32    = 1024;
+    let message: String = panic_message(|| fatal! {
+        #![suggest("raise the open-file limit to {limit} and restart")]
+        #![suggest = "check the service logs"]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(
+        message.ends_with("\n\n    try: raise the open-file limit to 1024 and restart\n    try: check the service logs"),
+        "{message}"
+    );
+}
+
+// Conditional suggestions are only rendered when their predicate holds.
+#[test]
+fn test_fatal_suggest_if() {
+    let is_my_error = |e: &anyhow::Error| e.is::<MyError>();
+    let fired:   String = panic_message(|| fatal! {
+        #![suggest_if(is_my_error, "retry the operation")]
+        #![suggest_if(|e: &anyhow::Error| e.is::<std::io::Error>(), "run as the service user")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    let skipped: String = panic_message(|| fatal! {
+        #![suggest_if(|e: &anyhow::Error| e.is::<std::io::Error>(), "run as the service user")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+
+    assert!(fired.ends_with("\n\n    try: retry the operation"), "{fired}");
+    assert!(!skipped.contains("try:"), "{skipped}");
+}
+
+// This test uses `fatal_fn` with a suggestion.
+#[test]
+#[should_panic(expected = "try: restart the service")]
+#[fatal_fn(suggest = "restart the service")]
+fn test_fatal_fn_suggest() {
+    let _: i32 = might_fail(true)?;
+    Ok(())
+}
+
+// The deterministic mode renders the same message for the same failure.
+#[test]
+fn test_fatal_deterministic() {
+    let run = || panic_message(|| fatal! {
+        #![deterministic]
+        #![reason("Failed deterministically")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    // Both runs are called from the same place, as the backtrace names it.
+    let runs: Vec<String> = (0..2).map(|_| run()).collect();
+    assert_eq!(runs[0], runs[1]);
+    assert!(runs[0].contains("Failed deterministically\n\nCaused by:\n    This operation failed"), "{}", runs[0]);
+    assert!(!runs[0].contains(env!("CARGO_MANIFEST_DIR")), "{}", runs[0]);
+}
+
+// The locals of the failure path never shadow the user's.
+#[test]
+fn test_fatal_failure_path_hygiene() {
+    let reason:  &str   = "the user's reason";
+    let report:  &str   = "the user's report";
+    let message: String = panic_message(|| fatal! {
+        #![suggest("{reason} and {report}")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.ends_with("try: the user's reason and the user's report"), "{message}");
+}
+
+// A value asserting an invariant whenever it is formatted, which the failure
+// being reported has violated.
+struct Invariant;
+
+impl std::fmt::Display for Invariant {
+    fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("the invariant was violated")
+    }
+}
+
+impl std::fmt::Debug for Invariant {
+    fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("the invariant was violated")
+    }
+}
+
+// Each message formatted on the failure path is guarded on its own, so a
+// panicking `Display` only replaces the message it is part of, and is noted
+// once.
+#[test]
+fn test_fatal_guarded_display() {
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to process {}", Invariant)]
+        #![suggest("inspect {} first", Invariant)]
+        #![suggest("retry the operation")]
+        #[ctx("processing {}", Invariant)]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("«format panicked»\n\nCaused by:\n    0: «format panicked»\n    1: This operation failed"), "{message}");
+    assert!(message.contains("try: «format panicked»\n    try: retry the operation"), "{message}");
+    assert!(message.ends_with("\n\nnote: values shown as «format panicked» panicked while being formatted"), "{message}");
+    assert_eq!(message.matches("note:").count(), 1, "{message}");
+}
+
+// A success value with a panicking `Debug` is guarded just the same.
+#[test]
+fn test_fatal_guarded_debug() {
+    let message: String = panic_message(|| fatal! {
+        #![ensure_ok(|_| false, "the value is rejected")]
+        Ok(Invariant)
+    });
+    assert!(message.contains("the value is rejected (value: «format panicked»)"), "{message}");
+    assert!(message.ends_with("panicked while being formatted"), "{message}");
+
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to process the value")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(!message.contains("note:"), "{message}");
+}
+
+// Blocks which end in a statement may still yield a `Result`, by finishing
+// early or by diverging, and are accepted as such.
+#[test]
+fn test_fatal_statement_tails() {
+    let shorthand: i32 = fatal! {
+        let value: i32 = might_fail(false)?;
+        fatal_ok!(value);
+    };
+    assert_eq!(shorthand, 42);
+
+    let message: String = panic_message(|| fatal! {
+        let value: i32 = might_fail(false)?;
+        panic!("the block diverged with {value}");
+    });
+    assert_eq!(message, "the block diverged with 42");
+}
+
+
+// A `continue` within a block moves on to the next iteration of the loop the
+// block is in, and a `break` leaves it.
+#[test]
+fn test_fatal_loop_control() {
+    let mut parsed: Vec<i32> = Vec::new();
+    for text in ["1", "", "2", "stop", "3"] {
+        fatal! {
+            if text.is_empty() {
+                continue;
+            }
+            if text == "stop" {
+                break;
+            }
+            parsed.push(text.parse()?);
+            Ok(())
+        }
+    }
+    assert_eq!(parsed, [1, 2]);
+}
+
+// A labeled `break` or `continue` reaches the loop it names, while those of a
+// loop within the block stay there.
+#[test]
+fn test_fatal_loop_labels() {
+    let mut visited: Vec<(i32, i32)> = Vec::new();
+    'outer: for row in 0..3 {
+        for column in 0..3 {
+            let value: i32 = fatal! {
+                if column > row {
+                    continue 'outer;
+                }
+                if row == 2 {
+                    break 'outer;
+                }
+                let mut value: i32 = 0;
+                loop {
+                    value += 1;
+                    if value > column {
+                        break;
+                    }
+                }
+                Ok(value)
+            };
+            visited.push((row, value));
+        }
+    }
+    assert_eq!(visited, [(0, 1), (1, 1), (1, 2)]);
+}
+
+// Finds the first of `values` that parses as a positive number.
+fn first_positive(values: &[&str]) -> Option<i32> {
+    for text in values {
+        fatal! {
+            let value: i32 = text.parse()?;
+            if value > 0 {
+                return Some(value);
+            }
+            Ok(())
+        }
+    }
+    None
+}
+
+// A `return` within a block returns from the enclosing function, whereas one
+// within a closure in the block still returns from the closure.
+#[test]
+fn test_fatal_early_return() {
+    assert_eq!(first_positive(&["-1", "0", "7", "9"]), Some(7));
+    assert_eq!(first_positive(&["-1"]), None);
+
+    let doubled: Vec<i32> = fatal! {
+        let double = |value: i32| -> Result<i32, MyError> {
+            if value == 0 {
+                return Err(MyError::OperationFailed);
+            }
+            Ok(value * 2)
+        };
+        Ok(vec![double(1)?, double(might_fail(false)?)?])
+    };
+    assert_eq!(doubled, [2, 84]);
+}
+
+// Errors still take the failure path of a block which may leave early.
+#[test]
+fn test_fatal_early_return_failure() {
+    let message: String = panic_message(|| first_positive(&["-1", "oops"]));
+    assert!(message.contains("invalid digit found in string"), "{message}");
+}
@@ -0,0 +1,140 @@
+//! Pins the performance contract of the success path: a `fatal!` block which
+//! succeeds must not allocate, whichever zero-cost attributes it uses.
+//!
+//! Every allocation made by the current thread is counted by a global
+//! allocator, so the tests running in parallel don't disturb each other.
+
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::hint::black_box;
+
+use impass::{fatal, fatal_fn, fatal_ok, FatalExt};
+use common::might_fail;
+
+
+// Counts the allocations made by each thread.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// Returns the number of allocations made by the current thread while running
+// `f`, along with its result.
+fn allocations<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before: usize = ALLOCATIONS.with(Cell::get);
+    let result: T     = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+
+#[fatal_fn(reason = "Failed in a function", code = "E0001")]
+fn fatal_function() -> i32 {
+    let value: i32 = might_fail(false)?;
+    Ok(value)
+}
+
+
+// The baseline, to make sure the allocator is counting at all.
+#[test]
+fn test_counting_allocator() {
+    let (count, _) = allocations(|| black_box(Box::new(42)));
+    assert_eq!(count, 1);
+}
+
+// A named block to run, yielding 42 on success.
+type Case = (&'static str, fn() -> i32);
+
+// Every zero-cost form of `fatal!` succeeds without allocating.
+#[test]
+fn test_success_path_does_not_allocate() {
+    let cases: [Case; 17] = [
+        ("bare", || fatal! {
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("expression", || fatal!(might_fail(false))),
+        ("expression with a message", || fatal!(might_fail(false), "Failed after {} attempts", 3)),
+        ("option", || fatal! {
+            let value: i32 = might_fail(false).ok()?;
+            Ok(value)
+        }),
+        ("ensure_ok", || fatal! {
+            #![ensure_ok(|value| *value > 0, "the value must exceed {}", 0)]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("reason", || fatal! {
+            #![reason("Failed with a reason")]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("reason format", || fatal! {
+            #![reason("Failed after {} attempts", 3)]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("reason expression", || fatal! {
+            #![reason(format!("Failed after {} attempts", 3))]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("reason_with", || fatal! {
+            #![reason_with(|| format!("Failed after {} attempts", 3))]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("code", || fatal! {
+            #![code("E0001")]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("must_use", || fatal! {
+            #![must_use]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("ctx", || fatal! {
+            #[ctx("loading record {}", 7)]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("suggest", || fatal! {
+            #![suggest("raise the limit to {}", 1024)]
+            let value: i32 = might_fail(false)?;
+            Ok(value)
+        }),
+        ("fatal_ok", || fatal! {
+            if black_box(true) {
+                fatal_ok!(42);
+            }
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        }),
+        ("fatal_fn", fatal_function),
+        ("or_fatal", || might_fail(false).or_fatal("Failed as a method")),
+        ("or_fatal_with", || might_fail(false).or_fatal_with(|| format!("Failed after {} attempts", 3))),
+    ];
+
+    for (name, case) in cases {
+        let (count, value) = allocations(case);
+        assert_eq!(value, 42, "{name}");
+        assert_eq!(count, 0, "`{name}` allocated on the success path");
+    }
+}
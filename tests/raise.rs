@@ -0,0 +1,120 @@
+//! Raises failures through the `Fatal` builder, away from any fatal site.
+
+mod common;
+
+use std::env;
+use std::process::{Command, Output};
+
+use impass::{fatal, Fatal};
+use common::{might_fail, panic_message, MyError};
+
+
+// Set for the tests run on their own by `stderr_of()`, which only fail when
+// it is.
+const CHILD_ENV: &str = "IMPASS_RAISE_CHILD";
+
+// Runs the test `name` of this binary on its own in the deterministic mode,
+// returning what it wrote to stderr with the line and column of every
+// location within this file replaced by `<line>:<column>`.
+fn stderr_of(name: &str) -> String {
+    let output: Output = Command::new(env::current_exe().expect("Failed to locate the test binary"))
+        .args([name, "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, "1")
+        .env("IMPASS_DETERMINISTIC", "1")
+        .env_remove("RUST_BACKTRACE")
+        .output()
+        .unwrap_or_else(|e| panic!("Failed to run the test `{name}`: {e}"));
+
+    let stderr:         String = String::from_utf8_lossy(&output.stderr).into_owned();
+    let prefix:         String = format!("{}:", file!());
+    let mut normalized: String = String::new();
+    let mut rest:       &str   = &stderr;
+    while let Some(index) = rest.find(&prefix) {
+        let (before, after) = rest.split_at(index + prefix.len());
+        let location: usize = after.find(|c: char| !c.is_ascii_digit() && c != ':').unwrap_or(after.len());
+        normalized.push_str(before);
+        normalized.push_str("<line>:<column>:");
+        rest = &after[location..];
+    }
+    normalized.push_str(rest);
+    normalized
+}
+
+
+// Raises the failure compared by `test_raise_as_block`, whose class and
+// fields are left out of the report.
+#[test]
+fn child_raise() {
+    if env::var_os(CHILD_ENV).is_some() {
+        Fatal::new(MyError::OperationFailed.into())
+            .reason("Failed to compute the value")
+            .code("E0042")
+            .class("runtime")
+            .field("workers_lost", 4)
+            .raise();
+    }
+}
+
+// Fails the block compared by `test_raise_as_block`.
+#[test]
+fn child_block() {
+    if env::var_os(CHILD_ENV).is_some() {
+        fatal! {
+            #![reason("Failed to compute the value")]
+            #![code("E0042")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        };
+    }
+}
+
+// A raised failure writes the very same stderr as a block with the same
+// reason and code, but for where it's located.
+#[test]
+fn test_raise_as_block() {
+    let expected: &str = if cfg!(feature = "legacy-format") {
+        "fatal: 0.000 tests/raise.rs:<line>:<column>: Failed to compute the value\n\
+         \n\
+         fatal error [E0042]\n\
+         Failed to compute the value\n\
+         \n\
+         Caused by:\n    This operation failed\n"
+    } else {
+        "fatal: 0.000 tests/raise.rs:<line>:<column>: Failed to compute the value\n\
+         fatal error [E0042] at tests/raise.rs:<line>:<column>: Failed to compute the value\n\
+         \n\
+         Caused by:\n    This operation failed\n"
+    };
+    assert_eq!(stderr_of("child_raise"), expected);
+    assert_eq!(stderr_of("child_block"), expected);
+}
+
+// A failure without a reason takes the default one of a block.
+#[test]
+fn test_raise_default_reason() {
+    let line:    u32    = line!() + 1;
+    let message: String = panic_message(|| Fatal::new(MyError::OperationFailed.into()).raise());
+    let reason:  String = if cfg!(feature = "legacy-default-message") {
+        String::from(impass::DEFAULT_MESSAGE)
+    } else {
+        format!("fatal error at {}:{line} (no reason provided)", file!())
+    };
+    assert!(message.contains(&format!("{reason}\n\nCaused by:\n    This operation failed")), "{message}");
+}
+
+// The failure is located at the call of `raise()`.
+#[cfg(not(feature = "legacy-format"))]
+#[test]
+fn test_raise_location() {
+    let line:    u32    = line!() + 1;
+    let message: String = panic_message(|| Fatal::new(MyError::OperationFailed.into()).reason("Failed to start").raise());
+    assert!(message.starts_with(&format!("fatal error at {}:{line}:", file!())), "{message}");
+}
+
+// An exit code still panics while an unwind-required guard is alive.
+#[test]
+fn test_raise_exit_code_guarded() {
+    let _guard = impass::register_unwind_required_guard();
+    let message: String = panic_message(|| Fatal::new(MyError::OperationFailed.into()).reason("Failed to exit").exit_code(70).raise());
+    assert!(message.contains("Failed to exit\n\nCaused by:\n    This operation failed"), "{message}");
+}
@@ -0,0 +1,70 @@
+//! Compiles the same blocks with and without `#![recoverable]`, which returns
+//! the error from the enclosing function rather than panicking.
+
+mod common;
+
+use impass::fatal;
+use common::{might_fail, panic_message, MyError};
+
+
+// The block under test, with the given leading attributes.
+macro_rules! load_state {
+    ($should_fail:expr $(, $mode:meta)?) => {
+        fatal! {
+            $(#![$mode])?
+            #![reason("Failed to load the state")]
+            let value: i32 = might_fail($should_fail)?;
+            Ok(value + 1)
+        }
+    };
+}
+
+fn load_fatal(should_fail: bool) -> i32 {
+    load_state!(should_fail)
+}
+
+fn load_recoverable(should_fail: bool) -> anyhow::Result<i32> {
+    let value: i32 = load_state!(should_fail, recoverable);
+    Ok(value * 2)
+}
+
+
+// Both modes evaluate to the value on success.
+#[test]
+fn test_recoverable_success() {
+    assert_eq!(load_fatal(false), 43);
+    assert_eq!(load_recoverable(false).unwrap(), 86);
+}
+
+// The same block panics by default, and returns the error when recoverable.
+#[test]
+fn test_recoverable_failure() {
+    let message: String = panic_message(|| load_fatal(true));
+    assert!(message.contains("Failed to load the state\n\nCaused by:\n    This operation failed"), "{message}");
+
+    let error: anyhow::Error = load_recoverable(true).unwrap_err();
+    let chain: Vec<String>   = error.chain().map(ToString::to_string).collect();
+    assert_eq!(chain, ["Failed to load the state", "This operation failed"]);
+    assert!(matches!(error.root_cause().downcast_ref::<MyError>(), Some(MyError::OperationFailed)));
+}
+
+// The error is converted into the error type of the enclosing function.
+#[test]
+fn test_recoverable_conversion() {
+    fn load() -> Result<i32, Box<dyn std::error::Error + Send + Sync>> {
+        let value: i32 = fatal! {
+            #![recoverable]
+            #![reason("Failed to load the snapshot")]
+            #[ctx("reading the snapshot")]
+            let value: i32 = might_fail(true)?;
+            Ok(value)
+        };
+        Ok(value)
+    }
+
+    let error: Box<dyn std::error::Error + Send + Sync> = load().unwrap_err();
+    let chain: Vec<String>                               = std::iter::successors(Some(&*error as &dyn std::error::Error), |error| error.source())
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(chain, ["Failed to load the snapshot", "reading the snapshot", "This operation failed"]);
+}
@@ -0,0 +1,156 @@
+//! Takes the chain signatures of failures through the fatal hook, and compares
+//! them with `ChainDiff`.
+//!
+//! The hook is process-wide, so the tests take turns through a lock.
+
+use std::any::Any;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use impass::fatal;
+use impass::report::{self, ChainDiff, FrameChange};
+
+
+// Runs `f`, which is expected to panic.
+fn expect_panic<T>(f: impl FnOnce() -> T) {
+    let result: Result<T, Box<dyn Any + Send>> = panic::catch_unwind(AssertUnwindSafe(f));
+    assert!(result.is_err(), "Expected a panic");
+}
+
+// The signatures recorded by the hook of the current test.
+static SIGNATURES: Mutex<Vec<Vec<String>>> = Mutex::new(Vec::new());
+
+// Takes the hook for the current test, installing one which records the
+// signature of every failure it is called with.
+fn install() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    let guard: MutexGuard<'static, ()> = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    SIGNATURES.lock().unwrap().clear();
+    impass::set_fatal_hook(Box::new(|failure| SIGNATURES.lock().unwrap().push(report::chain_signature(failure))));
+    guard
+}
+
+// Turns a list of literals into a signature.
+fn signature(frames: &[&str]) -> Vec<String> {
+    frames.iter().map(|frame| frame.to_string()).collect()
+}
+
+// Reads the port of a configuration file, which never exists.
+fn read_port(directory: &str, attempt: u32) -> u16 {
+    fatal! {
+        #![reason("Failed to load the configuration")]
+        #[ctx("reading the configuration at \"{directory}/config.toml\" (attempt {attempt})")]
+        let text: String = fs::read_to_string(format!("{directory}/config.toml"))?;
+        let port: u16    = text.trim().parse()?;
+        Ok(port)
+    }
+}
+
+
+// Digits and paths are masked, and whitespace is collapsed.
+#[test]
+fn test_mask() {
+    assert_eq!(report::mask("connection to 10.0.0.12:8080 refused"), "connection to #.#.#.#:# refused");
+    assert_eq!(report::mask("failed to open \"/var/lib/app/state-2.db\":"), "failed to open \"/*/*/*/*\":");
+    assert_eq!(report::mask("reading (C:\\Users\\jo\\app.toml)"), "reading (*\\*\\*\\*)");
+    assert_eq!(report::mask("  line 12,\tcolumn   7\n"), "line #, column #");
+    assert_eq!(report::mask("no digits or paths here"), "no digits or paths here");
+}
+
+// An io error is described by its type and kind, and its context by its
+// masked message, so that two runs in different places match.
+#[test]
+fn test_chain_signature_io() {
+    let _guard = install();
+    expect_panic(|| read_port("/tmp/impass-report-missing-1", 1));
+    expect_panic(|| read_port("/tmp/impass-report-missing-22/nested", 3));
+
+    let signatures = SIGNATURES.lock().unwrap();
+    assert_eq!(signatures[0], signature(&["reading the configuration at \"/*/*/*\" (attempt #)", "std::io::Error(NotFound)"]));
+    assert_eq!(signatures[1], signature(&["reading the configuration at \"/*/*/*/*\" (attempt #)", "std::io::Error(NotFound)"]));
+}
+
+// A parse error is described by its type and kind.
+#[test]
+fn test_chain_signature_parse() {
+    let _guard = install();
+    expect_panic(|| fatal! {
+        #[ctx("parsing the port of worker 3")]
+        let port: u16 = "80 80".parse()?;
+        Ok(port)
+    });
+    expect_panic(|| fatal! {
+        let ratio: f64 = "one half".parse()?;
+        Ok(ratio)
+    });
+    expect_panic(|| fatal! {
+        let text: String = String::from_utf8(vec![0xff, 0xfe])?;
+        Ok(text)
+    });
+
+    let signatures = SIGNATURES.lock().unwrap();
+    assert_eq!(signatures[0], signature(&["parsing the port of worker #", "std::num::ParseIntError(InvalidDigit)"]));
+    assert_eq!(signatures[1], signature(&["std::num::ParseFloatError"]));
+    assert_eq!(signatures[2], signature(&["std::string::FromUtf8Error"]));
+}
+
+// A block with its own error type is described by its masked `Debug`
+// rendering.
+#[test]
+fn test_chain_signature_error_type() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum ConfigError {
+        Missing { key: &'static str, line: u32 },
+    }
+
+    let _guard = install();
+    expect_panic(|| fatal! {
+        #![error(ConfigError)]
+        let port: u16 = Err(ConfigError::Missing { key: "port", line: 42 })?;
+        Ok(port)
+    });
+    assert_eq!(SIGNATURES.lock().unwrap()[0], signature(&["Missing { key: \"port\", line: # }"]));
+}
+
+// Identical signatures have no differences.
+#[test]
+fn test_chain_diff_same() {
+    let expected: Vec<String> = signature(&["reading the configuration", "std::io::Error(NotFound)"]);
+    let diff:     ChainDiff   = ChainDiff::between(&expected, &expected);
+    assert!(diff.is_empty());
+    assert_eq!(diff.to_string(), "the chains have the same signature");
+}
+
+// A cause inserted into or removed from the chain is reported at its index.
+#[test]
+fn test_chain_diff_inserted_removed() {
+    let expected: Vec<String> = signature(&["reading the configuration", "std::io::Error(NotFound)"]);
+    let actual:   Vec<String> = signature(&["reading the configuration", "resolving the include at /*/*", "std::io::Error(NotFound)"]);
+
+    let diff: ChainDiff = ChainDiff::between(&expected, &actual);
+    assert_eq!(diff.changes(), [FrameChange::Inserted { at: 1, frame: String::from("resolving the include at /*/*") }]);
+    assert_eq!(diff.to_string(), "cause 1 inserted: resolving the include at /*/*");
+
+    let diff: ChainDiff = ChainDiff::between(&actual, &expected);
+    assert_eq!(diff.changes(), [FrameChange::Removed { at: 1, frame: String::from("resolving the include at /*/*") }]);
+}
+
+// A different root cause is reported as changed, along with anything else.
+#[test]
+fn test_chain_diff_changed() {
+    let expected: Vec<String> = signature(&["reading the configuration", "std::io::Error(NotFound)"]);
+    let actual:   Vec<String> = signature(&["loading the state", "reading the configuration", "std::io::Error(PermissionDenied)"]);
+
+    let diff: ChainDiff = ChainDiff::between(&expected, &actual);
+    assert_eq!(diff.changes(), [
+        FrameChange::Inserted { at: 0, frame: String::from("loading the state") },
+        FrameChange::Changed  { at: 2, expected: String::from("std::io::Error(NotFound)"), actual: String::from("std::io::Error(PermissionDenied)") },
+    ]);
+    assert_eq!(
+        diff.to_string(),
+        "cause 0 inserted: loading the state\ncause 2 changed: std::io::Error(NotFound) => std::io::Error(PermissionDenied)"
+    );
+}
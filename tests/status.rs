@@ -0,0 +1,169 @@
+//! Configures a status file, which the failure path and `mark_clean_exit`
+//! write their documents to.
+//!
+//! The configuration is process-wide, so the tests take turns through a lock.
+//! They only build with the `status-file` feature.
+
+#![cfg(feature = "status-file")]
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use impass::{fatal, Fatal, FatalExt};
+use common::{might_fail, panic_message, MyError};
+
+
+// Takes the configuration for the current test, pointing the status file at
+// a fresh path named after `name`.
+fn configure(name: &str, clean_exit_status: bool) -> (MutexGuard<'static, ()>, PathBuf) {
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    let guard: MutexGuard<'static, ()> = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let dir:   PathBuf                 = std::env::temp_dir().join(format!("impass-status-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let path: PathBuf = dir.join(format!("{name}.json"));
+    let _ = fs::remove_file(&path);
+    impass::config().status_file(&path).clean_exit_status(clean_exit_status);
+    (guard, path)
+}
+
+
+// A failing block writes a document describing the failure.
+#[test]
+fn test_status_fatal() {
+    let (_guard, path) = configure("fatal", false);
+    let message: String = panic_message(|| fatal! {
+        #![reason("Failed to \"load\" the state")]
+        #![code("E0042")]
+        let value: i32 = might_fail(true)?;
+        Ok(value)
+    });
+    assert!(message.contains("Failed to \"load\" the state"), "{message}");
+
+    let status: String = fs::read_to_string(&path).unwrap();
+    assert!(status.starts_with("{\"schema_version\": 2, \"status\": \"fatal\", \"timestamp\": "), "{status}");
+    assert!(status.contains("\"exit_code\": 101, \"class\": \"panic\", \"code\": \"E0042\", \"fingerprint\": \""), "{status}");
+    #[cfg(not(feature = "backtrace-budget"))]
+    assert!(status.ends_with(": Failed to \\\"load\\\" the state\"}\n"), "{status}");
+
+    // Sites without an error code, including the `FatalExt` methods, leave
+    // it out.
+    let _ = panic_message(|| might_fail(true).or_fatal("Failed as a method"));
+    let status: String = fs::read_to_string(&path).unwrap();
+    assert!(status.contains("\"code\": null, \"fingerprint\": \""), "{status}");
+    assert!(status.contains("\"fields\": {}, \"summary\": \"fatal: "), "{status}");
+    assert!(status.contains("tests/status.rs:"), "{status}");
+}
+
+// A failure raised through `Fatal` is written with its own class, and with
+// its fields.
+#[test]
+fn test_status_raise_class_fields() {
+    let (_guard, path) = configure("raise", false);
+    let _ = panic_message(|| Fatal::new(MyError::OperationFailed.into())
+        .reason("The worker pool collapsed")
+        .class("runtime")
+        .field("workers_lost", 4)
+        .field("pool", "\"ingest\"")
+        .raise());
+
+    let status: String = fs::read_to_string(&path).unwrap();
+    assert!(status.contains("\"exit_code\": 101, \"class\": \"runtime\", "), "{status}");
+    assert!(status.contains("\"fields\": {\"workers_lost\": \"4\", \"pool\": \"\\\"ingest\\\"\"}, "), "{status}");
+}
+
+// The fingerprint of a failure only depends on its reason, its site and the
+// kind of its root cause, and not on the run.
+#[test]
+fn test_status_fingerprint() {
+    let (_guard, path) = configure("fingerprint", false);
+    let line: u32 = line!() + 2;
+    let fail = |reason: &str| {
+        let _ = panic_message(|| fatal!(might_fail(true), "{}", reason));
+        fs::read_to_string(&path).unwrap()
+    };
+
+    let status: String = fail("Failed to fingerprint");
+    let expected: String = format!(
+        "\"fingerprint\": \"{:016x}\", \"fingerprint_version\": 1, ",
+        fnv1a(&["Failed to fingerprint", file!(), &line.to_string(), "OperationFailed"])
+    );
+    assert!(status.contains(&expected), "{status}");
+    assert_eq!(fingerprint(&fail("Failed to fingerprint")), fingerprint(&status));
+    assert_ne!(fingerprint(&fail("Failed otherwise")), fingerprint(&status));
+}
+
+// The fingerprint of a status document.
+fn fingerprint(status: &str) -> &str {
+    status.split("\"fingerprint\": \"").nth(1).and_then(|rest| rest.get(..16)).unwrap_or_else(|| panic!("{status}"))
+}
+
+// The first version of the fingerprint: the 64-bit FNV-1a of the fields, each
+// followed by a zero byte.
+fn fnv1a(fields: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in fields.iter().flat_map(|field| field.bytes().chain([0])) {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// With the `backtrace-budget` feature, the raw frames of the failing site are
+// listed as well.
+#[cfg(feature = "backtrace-budget")]
+#[test]
+fn test_status_raw_frames() {
+    let (_guard, path) = configure("raw_frames", false);
+    let _ = panic_message(|| fatal!(might_fail(true), "Failed with frames"));
+
+    let status: String = fs::read_to_string(&path).unwrap();
+    let frames: &str   = status.split("\"raw_frames\": [").nth(1).unwrap_or_else(|| panic!("{status}"));
+    assert!(frames.starts_with("\"0x") && frames.ends_with("\"]}\n"), "{status}");
+}
+
+// A clean exit is only recorded when asked to.
+#[test]
+fn test_status_clean_exit() {
+    let (_guard, path) = configure("clean", false);
+    impass::mark_clean_exit().unwrap();
+    assert!(!path.exists());
+
+    impass::config().clean_exit_status(true);
+    impass::mark_clean_exit().unwrap();
+    let status: String = fs::read_to_string(&path).unwrap();
+    assert!(status.starts_with("{\"schema_version\": 2, \"status\": \"ok\", \"timestamp\": "), "{status}");
+}
+
+// Documents replace the previous one as a whole, through a temporary file
+// which doesn't outlive the write.
+#[test]
+fn test_status_atomic() {
+    let (_guard, path) = configure("atomic", true);
+    fs::write(&path, "x".repeat(4096)).unwrap();
+    impass::mark_clean_exit().unwrap();
+
+    let status: String = fs::read_to_string(&path).unwrap();
+    assert!(status.starts_with('{') && status.ends_with("}\n"), "{status}");
+    let leftovers: Vec<_> = fs::read_dir(path.parent().unwrap()).unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+}
+
+// A status file which can't be written doesn't keep the block from
+// terminating as usual.
+#[test]
+fn test_status_unwritable() {
+    let (_guard, path) = configure("unwritable", true);
+    let path: PathBuf = path.join("missing").join("status.json");
+    impass::config().status_file(&path);
+
+    assert!(impass::mark_clean_exit().is_err());
+    let message: String = panic_message(|| fatal!(might_fail(true), "Failed without a status"));
+    assert!(message.contains("Failed without a status"), "{message}");
+}
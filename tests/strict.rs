@@ -0,0 +1,104 @@
+//! Exercises every macro from a consumer crate with strict crate-level lints,
+//! which the expansions must never trip on their own.
+
+#![forbid(unsafe_code)]
+#![deny(missing_docs, warnings, unused, rust_2018_idioms)]
+
+mod common;
+
+use std::sync::LazyLock;
+
+use impass::{fatal, fatal_fn, fatal_lazy, fatal_mod, fatal_ok};
+use common::{might_fail, MyError};
+
+
+/// A public function with every `fatal_fn` argument, and a fallible sibling.
+#[fatal_fn(
+    reason = "Failed to compute the value",
+    code = "E0001",
+    suggest = "try again",
+    also_fallible = "try_compute"
+)]
+pub fn compute(should_fail: bool) -> i32 {
+    if !should_fail {
+        fatal_ok!(might_fail(false)?);
+    }
+    let value: i32 = might_fail(should_fail)?;
+    Ok(value)
+}
+
+// A private function whose fallible sibling is never called.
+#[fatal_fn(also_fallible = "try_compute_private")]
+fn compute_private() -> i32 {
+    let value: i32 = might_fail(false)?;
+    Ok(value)
+}
+
+/// A function returning the failure of a recoverable block.
+pub fn recover(should_fail: bool) -> anyhow::Result<i32> {
+    let value: i32 = fatal! {
+        #![recoverable]
+        #![reason("Failed to recover the value")]
+        let value: i32 = might_fail(should_fail)?;
+        Ok(value)
+    };
+    Ok(value)
+}
+
+/// A lazily initialized static.
+pub static VALUE: LazyLock<i32> = fatal_lazy! {
+    let value: i32 = might_fail(false)?;
+    Ok(value)
+};
+
+fatal_mod! {
+    #![recurse]
+
+    /// A module of fatal functions.
+    pub mod bootstrap {
+
+        /// Returns the value.
+        pub fn value() -> i32 {
+            let value: i32 = super::might_fail(false)?;
+            Ok(value)
+        }
+
+        /// A nested module.
+        pub mod nested {
+
+            /// Returns the value, aborting on failure.
+            #[fatal_fn(no_catch)]
+            pub fn value() -> i32 {
+                let value: i32 = super::super::might_fail(false)?;
+                Ok(value)
+            }
+        }
+    }
+}
+
+
+// Every macro expands cleanly, and behaves as usual.
+#[test]
+fn test_strict_lints() {
+    let value: i32 = fatal! {
+        #![reason_with(|| "Failed to compute the value")]
+        #![code("E0002")]
+        #![must_use]
+        #![suggest_if(|e: &anyhow::Error| e.is::<MyError>(), "try again")]
+        #![ensure_ok(|value| *value > 0, "the value must be positive")]
+        #[ctx("computing the value")]
+        let value: i32 = might_fail(false)?;
+        Ok(value)
+    };
+
+    assert_eq!(value, 42);
+    assert_eq!(compute(false), 42);
+    assert!(try_compute(true).is_err());
+    assert_eq!(compute_private(), 42);
+    assert!(recover(true).is_err());
+    assert_eq!(*VALUE, 42);
+    assert_eq!(bootstrap::value(), 42);
+    assert_eq!(bootstrap::nested::value(), 42);
+    assert_eq!(fatal!(might_fail(false)), 42);
+    assert_eq!(fatal!(might_fail(false), "Failed to compute the value {}", 42), 42);
+}
@@ -0,0 +1,91 @@
+//! Runs bodies under `fatal_watchdog!` which keep making progress, and so are
+//! never considered stalled. A stalling body aborts the process, so it is run
+//! as the `watchdog` example instead, by `tests/examples.rs`.
+
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use impass::{fatal_watchdog, Heartbeat};
+
+
+// The stall window of the tests, which the bodies outlast as a whole.
+const STALL: Duration = Duration::from_millis(200);
+
+// The time between two beats, well within the window.
+const STEP: Duration = Duration::from_millis(40);
+
+// Polls `future` to completion on the current thread.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future: Pin<&mut F> = pin!(future);
+    let mut cx:     Context<'_> = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+
+// A body beating within the window returns its value, however long it runs.
+#[test]
+fn test_watchdog_healthy() {
+    let steps: u32 = fatal_watchdog!(heartbeat = hb, stall = STALL, reason = "The worker stalled", {
+        let mut steps: u32 = 0;
+        for step in 0..10 {
+            thread::sleep(STEP);
+            hb.beat_with(format!("step {step}"));
+            steps += 1;
+        }
+        steps
+    });
+    assert_eq!(steps, 10);
+}
+
+// The heartbeat may be handed to the threads doing the work.
+#[test]
+fn test_watchdog_shared() {
+    let total: u32 = fatal_watchdog!(heartbeat = hb, stall = STALL, {
+        let workers: Vec<thread::JoinHandle<u32>> = (0..4).map(|index| {
+            let hb: Heartbeat = hb.clone();
+            thread::spawn(move || {
+                for _ in 0..5 {
+                    thread::sleep(STEP);
+                    hb.beat();
+                }
+                index
+            })
+        }).collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+    });
+    assert_eq!(total, 6);
+}
+
+// A completed body disarms the watchdog, so the process outlives the window.
+#[test]
+fn test_watchdog_disarmed() {
+    let value: i32 = fatal_watchdog!(heartbeat = _hb, stall = STALL, reason = "The worker stalled", 42);
+    assert_eq!(value, 42);
+    thread::sleep(STALL * 2);
+}
+
+// An async body is monitored from its first poll.
+#[test]
+fn test_watchdog_async() {
+    let future = fatal_watchdog!(heartbeat = hb, stall = STALL, reason = "The task stalled", async {
+        let mut steps: u32 = 0;
+        for _ in 0..10 {
+            thread::sleep(STEP);
+            hb.beat();
+            steps += 1;
+        }
+        steps
+    });
+
+    // Nothing is monitored before the future is polled.
+    thread::sleep(STALL * 2);
+    assert_eq!(block_on(future), 10);
+}